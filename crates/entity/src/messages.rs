@@ -11,6 +11,8 @@ pub struct Model {
     pub content: String,
     pub is_human: bool,
     pub created_at: DateTimeWithTimeZone,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub tool_calls: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
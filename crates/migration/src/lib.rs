@@ -3,6 +3,7 @@ pub use sea_orm_migration::prelude::*;
 mod m20220101_000001_create_table;
 mod m20241111_110133_add_agent_to_conversation;
 mod m20241112_035850_add_message;
+mod m20250115_000000_add_tool_calls_to_message;
 
 pub struct Migrator;
 
@@ -13,6 +14,7 @@ impl MigratorTrait for Migrator {
             Box::new(m20220101_000001_create_table::Migration),
             Box::new(m20241111_110133_add_agent_to_conversation::Migration),
             Box::new(m20241112_035850_add_message::Migration),
+            Box::new(m20250115_000000_add_tool_calls_to_message::Migration),
         ]
     }
 }
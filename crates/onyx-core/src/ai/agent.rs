@@ -1,40 +1,52 @@
 use crate::{
     ai::utils::{record_batches_to_json, record_batches_to_markdown},
-    config::model::{FileFormat, OutputFormat},
-    connector::load_result,
+    config::model::{
+        FileFormat, ModelPricing, OutputFormat, DEFAULT_MAX_CONCURRENT_TOOL_CALLS,
+        DEFAULT_MAX_TOOL_ROUNDS,
+    },
     errors::OnyxError,
     execute::{
-        agent::{AgentEvent, AgentInput},
+        agent::{AgentEvent, AgentInput, ConversationTurn},
         core::{
             value::{AgentOutput, ContextValue},
             write::Write,
             Executable, ExecutionContext,
         },
     },
+    result_store::load_result,
     utils::{format_table_output, truncate_datasets},
 };
-use std::{collections::HashMap, sync::Arc};
-
-use super::{anonymizer::base::Anonymizer, toolbox::ToolBox, MultiTool};
-use async_openai::{
-    config::{AzureConfig, OpenAIConfig, OPENAI_API_BASE},
-    error::OpenAIError,
-    types::{
-        ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessage,
-        ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestToolMessageArgs,
-        ChatCompletionRequestUserMessageArgs, ChatCompletionResponseMessage, ChatCompletionTool,
-        ChatCompletionToolArgs, ChatCompletionToolType, CreateChatCompletionRequestArgs,
-        FunctionObjectArgs, ResponseFormat, ResponseFormatJsonSchema,
-    },
-    Client,
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use super::{
+    anonymizer::base::Anonymizer,
+    provider::{ChatProvider, ChatRequest, ChatResponse, ChatStreamEvent},
+    retry::RetryPolicy,
+    toolbox::ToolBox,
+    usage::Usage,
+    MultiTool,
+};
+use async_openai::types::{
+    ChatCompletionMessageToolCall, ChatCompletionRequestAssistantMessageArgs,
+    ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs,
+    ChatCompletionRequestToolMessageArgs, ChatCompletionRequestUserMessageArgs, ChatCompletionTool,
+    ChatCompletionToolArgs, ChatCompletionToolType, FunctionCall, FunctionObjectArgs,
+    ResponseFormat, ResponseFormatJsonSchema,
 };
 use async_trait::async_trait;
+use futures::StreamExt;
 use pyo3::pyclass;
 use schemars::{schema_for, JsonSchema};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-const CONTEXT_WINDOW_EXCEEDED_CODE: &str = "string_above_max_length";
+const FINAL_ANSWER_PROMPT: &str =
+    "Answer now with what you have found so far, without calling any more tools.";
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[pyclass(module = "onyx_py")]
@@ -55,80 +67,114 @@ pub trait LLMAgent {
         &self,
         input: &str,
         system_message: &str,
+        history: &[ConversationTurn],
         execution_context: &mut ExecutionContext<'_, AgentEvent>,
     ) -> Result<String, OnyxError>;
 }
 
-enum OpenAIClientConfig {
-    Azure(AzureConfig),
-    OpenAI(OpenAIConfig),
+/// Limits on the tool loop of an agent. Once one is reached, the model is
+/// asked for a final answer without tools.
+#[derive(Debug, Clone)]
+pub struct AgentBudget {
+    pub max_tool_rounds: usize,
+    pub tokens: Option<u64>,
+    pub timeout: Option<Duration>,
+}
+
+impl Default for AgentBudget {
+    fn default() -> Self {
+        AgentBudget {
+            max_tool_rounds: DEFAULT_MAX_TOOL_ROUNDS,
+            tokens: None,
+            timeout: None,
+        }
+    }
 }
 
-enum OpenAIClient {
-    Azure(Client<AzureConfig>),
-    OpenAI(Client<OpenAIConfig>),
+impl AgentBudget {
+    /// The limit reached after `rounds` tool call rounds that used `tokens`
+    /// over `elapsed`, if any.
+    fn exhausted(&self, rounds: usize, tokens: u64, elapsed: Duration) -> Option<String> {
+        if rounds >= self.max_tool_rounds {
+            return Some(format!(
+                "limit of {} tool call rounds",
+                self.max_tool_rounds
+            ));
+        }
+        if let Some(budget) = self.tokens.filter(|budget| tokens >= *budget) {
+            return Some(format!("budget of {} tokens", budget));
+        }
+        if let Some(timeout) = self.timeout.filter(|timeout| elapsed >= *timeout) {
+            return Some(format!("time limit of {:?}", timeout));
+        }
+        None
+    }
 }
 
-pub struct OpenAIAgent {
-    client: OpenAIClient,
-    model: String,
+/// Runs the tool loop of an agent against any `ChatProvider`: calls tools
+/// the model asks for, anonymizes what is sent and maps the final output.
+pub struct Agent {
+    provider: Box<dyn ChatProvider>,
     system_instruction: String,
-    max_tries: u8,
+    budget: AgentBudget,
+    retry_policy: RetryPolicy,
     output_format: OutputFormat,
     anonymizer: Option<Box<dyn Anonymizer + Send + Sync>>,
     file_format: FileFormat,
+    parallel_tool_calls: bool,
+    max_concurrent_tool_calls: usize,
+    pricing: Option<ModelPricing>,
     pub tools: Arc<ToolBox<MultiTool>>,
 }
 
-impl OpenAIAgent {
+impl Agent {
     pub fn new(
-        model: String,
-        api_url: Option<String>,
-        api_key: String,
-        azure_deployment_id: Option<String>,
-        azure_api_version: Option<String>,
+        provider: Box<dyn ChatProvider>,
         system_instruction: String,
         output_format: OutputFormat,
         anonymizer: Option<Box<dyn Anonymizer + Send + Sync>>,
         file_format: FileFormat,
         tools: Arc<ToolBox<MultiTool>>,
     ) -> Self {
-        let url = api_url.unwrap_or(OPENAI_API_BASE.to_string());
-        let client_config = if url.contains("azure.com") {
-            OpenAIClientConfig::Azure(
-                AzureConfig::new()
-                    .with_api_key(api_key)
-                    .with_api_base(url)
-                    .with_deployment_id(azure_deployment_id.unwrap())
-                    .with_api_version(azure_api_version.unwrap()),
-            )
-        } else {
-            OpenAIClientConfig::OpenAI(OpenAIConfig::new().with_api_key(api_key).with_api_base(url))
-        };
-
-        let client = match client_config {
-            OpenAIClientConfig::Azure(client_config) => {
-                OpenAIClient::Azure(Client::with_config(client_config))
-            }
-            OpenAIClientConfig::OpenAI(client_config) => {
-                OpenAIClient::OpenAI(Client::with_config(client_config))
-            }
-        };
-
-        let max_tries = 5;
-
-        OpenAIAgent {
-            client,
-            model,
-            max_tries,
+        Agent {
+            provider,
+            budget: AgentBudget::default(),
+            retry_policy: RetryPolicy::default(),
             system_instruction,
             output_format,
             anonymizer,
             file_format,
+            parallel_tool_calls: false,
+            max_concurrent_tool_calls: DEFAULT_MAX_CONCURRENT_TOOL_CALLS,
+            pricing: None,
             tools,
         }
     }
 
+    /// Lets the model ask for several tool calls at once, which are then run
+    /// concurrently, at most `max_concurrent_tool_calls` at a time.
+    pub fn with_parallel_tool_calls(mut self, parallel_tool_calls: bool) -> Self {
+        self.parallel_tool_calls = parallel_tool_calls;
+        self
+    }
+
+    pub fn with_max_concurrent_tool_calls(mut self, max_concurrent_tool_calls: usize) -> Self {
+        self.max_concurrent_tool_calls = max_concurrent_tool_calls;
+        self
+    }
+
+    pub fn with_budget(mut self, budget: AgentBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Prices of the model, to report the cost of requests in
+    /// `AgentEvent::Usage` events.
+    pub fn with_pricing(mut self, pricing: Option<ModelPricing>) -> Self {
+        self.pricing = pricing;
+        self
+    }
+
     pub async fn simple_request(&self, system_instruction: String) -> Result<String, OnyxError> {
         let messages = vec![ChatCompletionRequestSystemMessageArgs::default()
             .name("onyx")
@@ -136,55 +182,205 @@ impl OpenAIAgent {
             .build()
             .map_err(|e| OnyxError::RuntimeError(format!("Unable to build LLM request: {e}")))?
             .into()];
-        let response = self.completion_request(messages, vec![], None).await?;
+        let request = ChatRequest {
+            messages,
+            tools: vec![],
+            response_format: None,
+            parallel_tool_calls: false,
+            allow_tool_calls: false,
+        };
+        let response = self
+            .with_retries(None, || {
+                let request = request.clone();
+                async move { (self.provider.complete(request).await, false) }
+            })
+            .await?;
         log::info!("Response: {:?}", response);
         match response.content {
             Some(content) => Ok(content),
             None => Err(OnyxError::RuntimeError(
-                "Empty response from OpenAI".to_string(),
+                "Empty response from the model".to_string(),
             )),
         }
     }
 
+    /// Sends a completion request, retrying transient API errors with
+    /// `AgentEvent::Retry` events, unless part of the answer was already
+    /// streamed.
     async fn completion_request(
         &self,
-        messages: Vec<ChatCompletionRequestMessage>,
-        tools: Vec<ChatCompletionTool>,
-        response_format: Option<ResponseFormat>,
-    ) -> Result<ChatCompletionResponseMessage, OnyxError> {
-        let mut request_builder = CreateChatCompletionRequestArgs::default();
-        if tools.is_empty() {
-            request_builder.model(self.model.clone()).messages(messages);
-        } else {
-            request_builder
-                .model(self.model.clone())
-                .tools(tools)
-                .parallel_tool_calls(false)
-                .messages(messages);
+        request: ChatRequest,
+        execution_context: &ExecutionContext<'_, AgentEvent>,
+    ) -> Result<ChatResponse, OnyxError> {
+        self.with_retries(Some(execution_context), || {
+            let request = request.clone();
+            async move {
+                let mut streamed = false;
+                let result = self
+                    .try_completion_request(request, execution_context, &mut streamed)
+                    .await;
+                (result, streamed)
+            }
+        })
+        .await
+    }
+
+    /// Awaits `send` until it succeeds or the retry policy gives up, with an
+    /// `AgentEvent::Retry` event per retry when there is a context to notify.
+    /// `send` also returns whether part of the answer was streamed, in which
+    /// case it is not sent again.
+    async fn with_retries<T, F, Fut>(
+        &self,
+        execution_context: Option<&ExecutionContext<'_, AgentEvent>>,
+        mut send: F,
+    ) -> Result<T, OnyxError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = (Result<T, OnyxError>, bool)>,
+    {
+        let mut attempt = 0;
+        loop {
+            let (result, streamed) = send().await;
+            let err = match result {
+                Ok(response) => return Ok(response),
+                Err(err) => err,
+            };
+            let delay = if streamed {
+                None
+            } else {
+                self.retry_policy.delay(attempt, &err)
+            };
+            let Some(after) = delay else {
+                return Err(err);
+            };
+            log::warn!("Retrying completion request after {:?}: {}", after, err);
+            if let Some(execution_context) = execution_context {
+                execution_context
+                    .notify(AgentEvent::Retry { err, after })
+                    .await?;
+            }
+            tokio::time::sleep(after).await;
+            attempt += 1;
         }
-        if let Some(format) = response_format {
-            request_builder.response_format(format);
+    }
+
+    /// Sends a completion request, streaming the answer as `AgentEvent::Delta`
+    /// events. Anonymized or structured output is not streamed, as its raw
+    /// deltas differ from the output the agent returns.
+    async fn try_completion_request(
+        &self,
+        request: ChatRequest,
+        execution_context: &ExecutionContext<'_, AgentEvent>,
+        streamed: &mut bool,
+    ) -> Result<ChatResponse, OnyxError> {
+        if !self.streams_answer() {
+            return self.provider.complete(request).await;
         }
+        let mut stream = self.provider.complete_stream(request).await?;
+        while let Some(event) = stream.next().await {
+            match event? {
+                ChatStreamEvent::Delta(delta) => {
+                    *streamed = true;
+                    execution_context.notify(AgentEvent::Delta(delta)).await?;
+                }
+                ChatStreamEvent::Completed(response) => return Ok(response),
+            }
+        }
+        Err(OnyxError::LLMError(
+            "Completion stream ended without a response".to_string(),
+        ))
+    }
 
-        let request = request_builder.build().unwrap();
+    /// Whether answers are streamed as `AgentEvent::Delta` events.
+    fn streams_answer(&self) -> bool {
+        self.anonymizer.is_none() && matches!(self.output_format, OutputFormat::Default)
+    }
 
-        let result = match &self.client {
-            OpenAIClient::Azure(client) => client.chat().create(request).await,
-            OpenAIClient::OpenAI(client) => client.chat().create(request).await,
-        };
-        let response = result.map_err(|e| {
-            if let OpenAIError::ApiError(ref api_error) = e {
-                if api_error.code == Some(CONTEXT_WINDOW_EXCEEDED_CODE.to_string()) {
-                    return OnyxError::LLMError(
-                        "Context window length exceeded. Shorten the prompt being sent to the LLM."
+    /// Messages replaying previous turns of a conversation: each question, the
+    /// tool calls made to answer it along with their results, and the answer.
+    fn history_messages(
+        &self,
+        history: &[ConversationTurn],
+        anonymized_items: &mut HashMap<String, String>,
+    ) -> Result<Vec<ChatCompletionRequestMessage>, OnyxError> {
+        let mut messages: Vec<ChatCompletionRequestMessage> = vec![];
+        for turn in history {
+            messages.push(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(self.anonymize(&turn.question, anonymized_items)?)
+                    .build()
+                    .map_err(|e| {
+                        OnyxError::RuntimeError(format!("Unable to build LLM request: {e}"))
+                    })?
+                    .into(),
+            );
+            if !turn.tool_calls.is_empty() {
+                // Arguments may quote values the model found in earlier tool
+                // outputs, so they are anonymized like the rest of the turn
+                let tool_calls = turn
+                    .tool_calls
+                    .iter()
+                    .map(|tool_call| {
+                        Ok(ChatCompletionMessageToolCall {
+                            id: tool_call.id.clone(),
+                            r#type: ChatCompletionToolType::Function,
+                            function: FunctionCall {
+                                name: tool_call.name.clone(),
+                                arguments: self
+                                    .anonymize(&tool_call.arguments, anonymized_items)?,
+                            },
+                        })
+                    })
+                    .collect::<Result<Vec<_>, OnyxError>>()?;
+                messages.push(
+                    ChatCompletionRequestAssistantMessageArgs::default()
+                        .tool_calls(tool_calls)
+                        .build()
+                        .map_err(|e| {
+                            OnyxError::RuntimeError(format!("Unable to build LLM request: {e}"))
+                        })?
+                        .into(),
+                );
+                for tool_call in &turn.tool_calls {
+                    messages.push(
+                        ChatCompletionRequestToolMessageArgs::default()
+                            .tool_call_id(tool_call.id.clone())
+                            .content(self.anonymize(&tool_call.output, anonymized_items)?)
+                            .build()
+                            .map_err(|e| {
+                                OnyxError::RuntimeError(format!("Unable to build LLM request: {e}"))
+                            })?
                             .into(),
                     );
                 }
             }
-            OnyxError::RuntimeError(format!("Error in completion request: {:?}", e))
-        })?;
+            messages.push(
+                ChatCompletionRequestAssistantMessageArgs::default()
+                    .content(self.anonymize(&turn.answer, anonymized_items)?)
+                    .build()
+                    .map_err(|e| {
+                        OnyxError::RuntimeError(format!("Unable to build LLM request: {e}"))
+                    })?
+                    .into(),
+            );
+        }
+        Ok(messages)
+    }
 
-        Ok(response.choices[0].message.clone())
+    fn anonymize(
+        &self,
+        text: &str,
+        anonymized_items: &mut HashMap<String, String>,
+    ) -> Result<String, OnyxError> {
+        match self.anonymizer {
+            Some(ref anonymizer) => {
+                let (text, items) =
+                    anonymizer.anonymize(text, Some(std::mem::take(anonymized_items)))?;
+                *anonymized_items = items;
+                Ok(text)
+            }
+            None => Ok(text.to_string()),
+        }
     }
 
     fn spec_serializer(
@@ -208,11 +404,12 @@ impl OpenAIAgent {
 }
 
 #[async_trait]
-impl LLMAgent for OpenAIAgent {
+impl LLMAgent for Agent {
     async fn request(
         &self,
         input: &str,
         system_message: &str,
+        history: &[ConversationTurn],
         execution_context: &mut ExecutionContext<'_, AgentEvent>,
     ) -> Result<String, OnyxError> {
         let anonymized_items = HashMap::new();
@@ -220,7 +417,7 @@ impl LLMAgent for OpenAIAgent {
             Some(ref anonymizer) => anonymizer.anonymize(system_message, Some(anonymized_items)),
             None => Ok((system_message.to_string(), anonymized_items)),
         }?;
-        let (anonymized_user_message, anonymized_items) = match self.anonymizer {
+        let (anonymized_user_message, mut anonymized_items) = match self.anonymizer {
             Some(ref anonymizer) => anonymizer.anonymize(input, Some(anonymized_items)),
             None => Ok((input.to_string(), anonymized_items)),
         }?;
@@ -232,6 +429,7 @@ impl LLMAgent for OpenAIAgent {
                 .build()
                 .map_err(|e| OnyxError::RuntimeError(format!("Unable to build LLM request: {e}")))?
                 .into()];
+        messages.extend(self.history_messages(history, &mut anonymized_items)?);
 
         if !input.is_empty() {
             messages.push(
@@ -244,18 +442,39 @@ impl LLMAgent for OpenAIAgent {
                     .into(),
             );
         }
-        let tools = self.tools.to_spec(OpenAIAgent::spec_serializer);
+        let tools = self.tools.to_spec(Agent::spec_serializer);
 
-        let mut tries: u8 = 0;
-        let mut output = "Something went wrong".to_string();
+        let started_at = Instant::now();
+        let mut rounds: usize = 0;
+        let mut used_tokens: u64 = 0;
         let mut tool_returns = Vec::<ChatCompletionRequestMessage>::new();
         let mut tool_calls = Vec::<ChatCompletionRequestMessage>::new();
 
         let mut contextualize_anonymized_items = anonymized_items.clone();
 
-        while tries < self.max_tries {
-            let message_with_replies =
+        let output = loop {
+            let exhausted = self
+                .budget
+                .exhausted(rounds, used_tokens, started_at.elapsed());
+            let mut message_with_replies =
                 [messages.clone(), tool_calls.clone(), tool_returns.clone()].concat();
+            if let Some(reason) = &exhausted {
+                log::warn!("Agent reached its {}, asking for an answer", reason);
+                execution_context
+                    .notify(AgentEvent::BudgetExhausted {
+                        reason: reason.clone(),
+                    })
+                    .await?;
+                message_with_replies.push(
+                    ChatCompletionRequestUserMessageArgs::default()
+                        .content(FINAL_ANSWER_PROMPT)
+                        .build()
+                        .map_err(|e| {
+                            OnyxError::RuntimeError(format!("Unable to build LLM request: {e}"))
+                        })?
+                        .into(),
+                );
+            }
             tool_returns.clear();
             tool_calls.clear();
             log::debug!("Start completion request {:?}", message_with_replies);
@@ -277,24 +496,78 @@ impl LLMAgent for OpenAIAgent {
                 }
             };
             let ret_message = self
-                .completion_request(message_with_replies, tools.clone(), response_format)
+                .completion_request(
+                    ChatRequest {
+                        messages: message_with_replies,
+                        tools: tools.clone(),
+                        response_format,
+                        parallel_tool_calls: self.parallel_tool_calls,
+                        allow_tool_calls: exhausted.is_none(),
+                    },
+                    execution_context,
+                )
                 .await?;
 
-            output = ret_message
+            if let Some(usage) = ret_message.usage {
+                used_tokens += usage.total();
+            }
+            execution_context
+                .notify(AgentEvent::Usage(Usage::from_request(
+                    ret_message.usage,
+                    self.pricing.as_ref(),
+                )))
+                .await?;
+            let streamed = self.streams_answer()
+                && ret_message
+                    .content
+                    .as_deref()
+                    .is_some_and(|content| !content.is_empty());
+            let output = ret_message
                 .content
-                .unwrap_or("Empty response from OpenAI".to_string());
-            let tool_call_requests = ret_message.tool_calls.unwrap_or_default();
+                .unwrap_or("Empty response from the model".to_string());
+            if exhausted.is_some() {
+                break output;
+            }
+            let tool_call_requests = ret_message.tool_calls;
+            // Text the model wrote before calling tools is not the answer
+            if streamed && !tool_call_requests.is_empty() {
+                execution_context
+                    .notify(AgentEvent::DeltasDiscarded)
+                    .await?;
+            }
             log::info!(
                 "Number of tool calls: {} on {}",
                 &tool_call_requests.len(),
-                tries,
+                rounds,
             );
-            for tool in tool_call_requests.clone() {
-                let tool_call_ret = self
-                    .tools
-                    .run_tool(&tool.function.name, tool.function.arguments.clone())
-                    .await;
-
+            for tool in &tool_call_requests {
+                execution_context
+                    .notify(AgentEvent::ToolCallStarted {
+                        id: tool.id.clone(),
+                        name: tool.function.name.clone(),
+                        arguments: tool.function.arguments.clone(),
+                    })
+                    .await?;
+            }
+            let concurrency = if self.parallel_tool_calls {
+                self.max_concurrent_tool_calls
+            } else {
+                1
+            };
+            // Results come back in the order the model asked for the calls,
+            // which the tool messages must follow
+            let tool_call_futures = tool_call_requests
+                .iter()
+                .map(|tool| async move {
+                    let tool_call_ret = self
+                        .tools
+                        .run_tool(&tool.function.name, tool.function.arguments.clone())
+                        .await;
+                    (tool, tool_call_ret)
+                })
+                .collect::<Vec<_>>();
+            let mut tool_call_rets = futures::stream::iter(tool_call_futures).buffered(concurrency);
+            while let Some((tool, tool_call_ret)) = tool_call_rets.next().await {
                 let mut tool_ret = tool_call_ret.get_truncated_output();
 
                 if self.anonymizer.is_some() {
@@ -313,6 +586,12 @@ impl LLMAgent for OpenAIAgent {
                     tool_ret = result.0;
                 }
                 log::info!("Tool output: {}", tool_ret);
+                if let Some(usage) = &tool_call_ret.usage {
+                    used_tokens += usage.total_tokens();
+                    execution_context
+                        .notify(AgentEvent::Usage(usage.clone()))
+                        .await?;
+                }
                 tool_returns.push(
                     ChatCompletionRequestToolMessageArgs::default()
                         .tool_call_id(tool.id.clone())
@@ -329,7 +608,7 @@ impl LLMAgent for OpenAIAgent {
             }
 
             if tool_returns.is_empty() {
-                break;
+                break output;
             }
             tool_calls.push(
                 ChatCompletionRequestAssistantMessageArgs::default()
@@ -341,14 +620,8 @@ impl LLMAgent for OpenAIAgent {
                     .into(),
             );
 
-            tries += 1;
-        }
-
-        if !tool_calls.is_empty() {
-            return Err(OnyxError::AgentError(
-                "Failed to resolve tool calls. Max tries exceeded".to_string(),
-            ));
-        }
+            rounds += 1;
+        };
 
         let mut parsed_output = map_output(&output, &self.output_format, &self.file_format).await?;
         parsed_output = match self.anonymizer {
@@ -362,7 +635,7 @@ impl LLMAgent for OpenAIAgent {
 }
 
 #[async_trait]
-impl Executable<AgentInput, AgentEvent> for OpenAIAgent {
+impl Executable<AgentInput, AgentEvent> for Agent {
     async fn execute(
         &self,
         execution_context: &mut ExecutionContext<'_, AgentEvent>,
@@ -374,9 +647,10 @@ impl Executable<AgentInput, AgentEvent> for OpenAIAgent {
             .renderer
             .render_async(&self.system_instruction)
             .await?;
+        let history = input.history;
         let input = input.prompt.unwrap_or_default();
         let result = self
-            .request(&input, &system_instruction, execution_context)
+            .request(&input, &system_instruction, &history, execution_context)
             .await?;
         let event = AgentEvent::Finished {
             output: result.clone(),
@@ -430,3 +704,61 @@ async fn map_output(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::AgentBudget;
+    use std::time::Duration;
+
+    #[test]
+    fn budget_exhausted_by_tool_rounds() {
+        let budget = AgentBudget {
+            max_tool_rounds: 2,
+            ..Default::default()
+        };
+        assert_eq!(budget.exhausted(1, u64::MAX, Duration::MAX), None);
+        assert_eq!(
+            budget.exhausted(2, 0, Duration::ZERO).as_deref(),
+            Some("limit of 2 tool call rounds")
+        );
+    }
+
+    #[test]
+    fn budget_exhausted_by_tokens() {
+        let budget = AgentBudget {
+            tokens: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(budget.exhausted(0, 999, Duration::ZERO), None);
+        assert_eq!(
+            budget.exhausted(0, 1000, Duration::ZERO).as_deref(),
+            Some("budget of 1000 tokens")
+        );
+    }
+
+    #[test]
+    fn budget_exhausted_by_timeout() {
+        let budget = AgentBudget {
+            timeout: Some(Duration::from_secs(30)),
+            ..Default::default()
+        };
+        assert_eq!(budget.exhausted(0, 0, Duration::from_secs(29)), None);
+        assert_eq!(
+            budget.exhausted(0, 0, Duration::from_secs(30)).as_deref(),
+            Some("time limit of 30s")
+        );
+    }
+
+    #[test]
+    fn budget_reports_rounds_before_tokens_and_time() {
+        let budget = AgentBudget {
+            max_tool_rounds: 1,
+            tokens: Some(10),
+            timeout: Some(Duration::from_secs(1)),
+        };
+        assert_eq!(
+            budget.exhausted(1, 10, Duration::from_secs(1)).as_deref(),
+            Some("limit of 1 tool call rounds")
+        );
+    }
+}
@@ -0,0 +1,532 @@
+use async_openai::types::{
+    ChatCompletionMessageToolCall, ChatCompletionRequestAssistantMessageContent,
+    ChatCompletionRequestAssistantMessageContentPart, ChatCompletionRequestMessage,
+    ChatCompletionRequestSystemMessageContent, ChatCompletionRequestSystemMessageContentPart,
+    ChatCompletionRequestToolMessageContent, ChatCompletionRequestToolMessageContentPart,
+    ChatCompletionRequestUserMessageContent, ChatCompletionRequestUserMessageContentPart,
+    ResponseFormat,
+};
+use async_stream::stream;
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+use super::provider::{
+    event_data, ChatProvider, ChatRequest, ChatResponse, ChatStream, ChatStreamEvent, TokenUsage,
+};
+use super::retry::{is_transient_status, request_error, retry_after};
+use crate::errors::OnyxError;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const CONTEXT_WINDOW_EXCEEDED_MESSAGE: &str = "prompt is too long";
+const TRANSIENT_ERROR_TYPES: [&str; 3] = ["rate_limit_error", "overloaded_error", "api_error"];
+
+/// Provider for the Anthropic Messages API, translating tool definitions,
+/// tool calls and tool results between the OpenAI chat format and its own.
+pub struct AnthropicProvider {
+    http: reqwest::Client,
+    model: String,
+    api_url: String,
+    api_key: String,
+    max_tokens: u32,
+}
+
+impl AnthropicProvider {
+    pub fn new(model: String, api_url: String, api_key: String, max_tokens: u32) -> Self {
+        AnthropicProvider {
+            http: reqwest::Client::new(),
+            model,
+            api_url,
+            api_key,
+            max_tokens,
+        }
+    }
+
+    async fn send(&self, body: &Value) -> Result<reqwest::Response, OnyxError> {
+        log::debug!("Anthropic request: {}", body);
+        let response = self
+            .http
+            .post(format!("{}/messages", self.api_url.trim_end_matches('/')))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(body)
+            .send()
+            .await
+            .map_err(request_error)?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let retry_after = retry_after(response.headers());
+        let error = response.json::<Value>().await.unwrap_or_default();
+        Err(match api_error(&error) {
+            OnyxError::RuntimeError(message) | OnyxError::TransientLLMError { message, .. }
+                if is_transient_status(status.as_u16()) =>
+            {
+                OnyxError::TransientLLMError {
+                    message: format!("{} ({})", message, status),
+                    retry_after,
+                }
+            }
+            OnyxError::RuntimeError(message) => {
+                OnyxError::RuntimeError(format!("{} ({})", message, status))
+            }
+            err => err,
+        })
+    }
+
+    /// Builds a Messages API request from a chat request. Also returns the
+    /// name of the tool standing in for a JSON schema response format, as the
+    /// Messages API has no structured output.
+    fn to_messages_request(
+        &self,
+        request: &ChatRequest,
+    ) -> Result<(Value, Option<String>), OnyxError> {
+        let mut system = vec![];
+        let mut messages: Vec<Value> = vec![];
+        for message in &request.messages {
+            let (role, blocks) = match message {
+                ChatCompletionRequestMessage::System(message) => {
+                    system.push(system_text(&message.content));
+                    continue;
+                }
+                ChatCompletionRequestMessage::User(message) => (
+                    "user",
+                    vec![json!({"type": "text", "text": user_text(&message.content)})],
+                ),
+                ChatCompletionRequestMessage::Assistant(message) => {
+                    let mut blocks = vec![];
+                    let text = message
+                        .content
+                        .as_ref()
+                        .map(assistant_text)
+                        .unwrap_or_default();
+                    if !text.is_empty() {
+                        blocks.push(json!({"type": "text", "text": text}));
+                    }
+                    for tool_call in message.tool_calls.iter().flatten() {
+                        let input = serde_json::from_str::<Value>(&tool_call.function.arguments)
+                            .unwrap_or_else(|_| json!({}));
+                        blocks.push(json!({
+                            "type": "tool_use",
+                            "id": tool_call.id,
+                            "name": tool_call.function.name,
+                            "input": input,
+                        }));
+                    }
+                    ("assistant", blocks)
+                }
+                ChatCompletionRequestMessage::Tool(message) => (
+                    "user",
+                    vec![json!({
+                        "type": "tool_result",
+                        "tool_use_id": message.tool_call_id,
+                        "content": tool_text(&message.content),
+                    })],
+                ),
+                _ => {
+                    return Err(OnyxError::LLMError(
+                        "Only system, user, assistant and tool messages can be sent to Anthropic"
+                            .to_string(),
+                    ))
+                }
+            };
+            if blocks.is_empty() {
+                continue;
+            }
+            // User and assistant turns must alternate, so e.g. the results of
+            // several tool calls are sent as one user turn
+            match messages.last_mut() {
+                Some(last) if last["role"] == role => {
+                    if let Some(content) = last["content"].as_array_mut() {
+                        content.extend(blocks);
+                    }
+                }
+                _ => messages.push(json!({"role": role, "content": blocks})),
+            }
+        }
+        // At least one user turn is required, so a system-only request, like
+        // the ones made by evals, is sent as the user turn instead
+        if messages.is_empty() {
+            messages.push(json!({"role": "user", "content": system.join("\n\n")}));
+            system.clear();
+        }
+
+        let mut tools = request
+            .tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "name": tool.function.name,
+                    "description": tool.function.description,
+                    "input_schema": tool
+                        .function
+                        .parameters
+                        .clone()
+                        .unwrap_or_else(|| json!({"type": "object"})),
+                })
+            })
+            .collect::<Vec<Value>>();
+        let allow_tool_calls = !tools.is_empty() && request.allow_tool_calls;
+        let disable_parallel_tool_use = !request.parallel_tool_calls;
+        let mut tool_choice = None;
+        let mut output_tool = None;
+        if let Some(ResponseFormat::JsonSchema { json_schema }) = &request.response_format {
+            let name = json_schema.name.to_string();
+            // While tool calls are allowed the model may call the output tool
+            // or the others, but must call one so that it never answers in
+            // plain text. Afterwards only the output tool is left.
+            tool_choice = Some(if allow_tool_calls {
+                json!({"type": "any", "disable_parallel_tool_use": disable_parallel_tool_use})
+            } else {
+                json!({"type": "tool", "name": name})
+            });
+            tools.push(json!({
+                "name": name,
+                "description": json_schema.description,
+                "input_schema": json_schema.schema,
+            }));
+            output_tool = Some(name);
+        } else if !tools.is_empty() && !allow_tool_calls {
+            tool_choice = Some(json!({"type": "none"}));
+        } else if allow_tool_calls && disable_parallel_tool_use {
+            tool_choice = Some(json!({"type": "auto", "disable_parallel_tool_use": true}));
+        }
+
+        let mut body = json!({
+            "model": self.model,
+            "max_tokens": self.max_tokens,
+            "messages": messages,
+        });
+        if !system.is_empty() {
+            body["system"] = json!(system.join("\n\n"));
+        }
+        if !tools.is_empty() {
+            body["tools"] = json!(tools);
+        }
+        if let Some(tool_choice) = tool_choice {
+            body["tool_choice"] = tool_choice;
+        }
+        Ok((body, output_tool))
+    }
+}
+
+#[async_trait]
+impl ChatProvider for AnthropicProvider {
+    async fn complete(&self, request: ChatRequest) -> Result<ChatResponse, OnyxError> {
+        let (body, output_tool) = self.to_messages_request(&request)?;
+        let response =
+            self.send(&body).await?.json::<Value>().await.map_err(|e| {
+                OnyxError::RuntimeError(format!("Error in completion request: {}", e))
+            })?;
+        log::debug!("Anthropic response: {}", response);
+        from_messages_response(&response, output_tool.as_deref())
+    }
+
+    async fn complete_stream(&self, request: ChatRequest) -> Result<ChatStream, OnyxError> {
+        let (mut body, output_tool) = self.to_messages_request(&request)?;
+        body["stream"] = json!(true);
+        let mut events = Box::pin(event_data(self.send(&body).await?));
+
+        Ok(Box::pin(stream! {
+            let mut blocks: Vec<Value> = vec![];
+            // Tool inputs arrive as pieces of JSON text, kept apart until done
+            let mut inputs: Vec<String> = vec![];
+            let mut usage = Value::Null;
+            while let Some(data) = events.next().await {
+                let data = match data {
+                    Ok(data) => data,
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                };
+                let data: Value = match serde_json::from_str(&data) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        yield Err(OnyxError::RuntimeError(format!(
+                            "Unable to parse Anthropic response: {}",
+                            e
+                        )));
+                        return;
+                    }
+                };
+                let index = data["index"].as_u64().unwrap_or_default() as usize;
+                match data["type"].as_str().unwrap_or_default() {
+                    // Input tokens are reported when the message starts
+                    // and output tokens when it ends
+                    "message_start" => {
+                        usage = data["message"]["usage"].clone();
+                    }
+                    "message_delta" => {
+                        if let Some(output_tokens) = data["usage"].get("output_tokens") {
+                            usage["output_tokens"] = output_tokens.clone();
+                        }
+                    }
+                    "content_block_start" => {
+                        blocks.push(data["content_block"].clone());
+                        inputs.push(String::new());
+                    }
+                    "content_block_delta" if index < blocks.len() => {
+                        let delta = &data["delta"];
+                        if let Some(text) = delta["text"].as_str() {
+                            let content = blocks[index]["text"].as_str().unwrap_or_default();
+                            blocks[index]["text"] = json!(format!("{}{}", content, text));
+                            yield Ok(ChatStreamEvent::Delta(text.to_string()));
+                        }
+                        if let Some(partial_json) = delta["partial_json"].as_str() {
+                            inputs[index].push_str(partial_json);
+                        }
+                    }
+                    "error" => {
+                        yield Err(api_error(&data));
+                        return;
+                    }
+                    _ => {}
+                }
+            }
+            for (block, input) in blocks.iter_mut().zip(inputs) {
+                if block["type"] == "tool_use" && !input.is_empty() {
+                    block["input"] = serde_json::from_str(&input).unwrap_or_else(|_| json!({}));
+                }
+            }
+            let response = json!({"content": blocks, "usage": usage});
+            log::debug!("Anthropic response: {}", response);
+            yield from_messages_response(&response, output_tool.as_deref())
+                .map(ChatStreamEvent::Completed);
+        }))
+    }
+}
+
+/// Maps the content blocks of a Messages API response to a chat response:
+/// text blocks become the content and tool_use blocks become tool calls,
+/// except for `output_tool`, whose input is the structured output.
+fn from_messages_response(
+    response: &Value,
+    output_tool: Option<&str>,
+) -> Result<ChatResponse, OnyxError> {
+    let mut content = String::new();
+    let mut tool_calls = vec![];
+    for block in response["content"].as_array().into_iter().flatten() {
+        match block["type"].as_str() {
+            Some("text") => content.push_str(block["text"].as_str().unwrap_or_default()),
+            Some("tool_use") if block["name"].as_str() == output_tool => {
+                content.push_str(&block["input"].to_string())
+            }
+            Some("tool_use") => tool_calls.push(json!({
+                "id": block["id"],
+                "type": "function",
+                "function": {
+                    "name": block["name"],
+                    "arguments": block["input"].to_string(),
+                },
+            })),
+            _ => {}
+        }
+    }
+    let tool_calls = serde_json::from_value::<Vec<ChatCompletionMessageToolCall>>(json!(
+        tool_calls
+    ))
+    .map_err(|e| OnyxError::RuntimeError(format!("Unable to parse Anthropic response: {}", e)))?;
+    let usage = &response["usage"];
+    Ok(ChatResponse {
+        content: Some(content),
+        tool_calls,
+        usage: usage.is_object().then(|| TokenUsage {
+            prompt_tokens: usage["input_tokens"].as_u64().unwrap_or_default(),
+            completion_tokens: usage["output_tokens"].as_u64().unwrap_or_default(),
+        }),
+    })
+}
+
+/// Maps an error returned by the Messages API, in a response body or a
+/// stream, to an `OnyxError`.
+fn api_error(error: &Value) -> OnyxError {
+    let message = error["error"]["message"].as_str().unwrap_or_default();
+    if message.contains(CONTEXT_WINDOW_EXCEEDED_MESSAGE) {
+        return OnyxError::LLMError(
+            "Context window length exceeded. Shorten the prompt being sent to the LLM.".into(),
+        );
+    }
+    let error_type = error["error"]["type"].as_str().unwrap_or_default();
+    let message = format!("Error in completion request: {}: {}", error_type, message);
+    if TRANSIENT_ERROR_TYPES.contains(&error_type) {
+        return OnyxError::TransientLLMError {
+            message,
+            retry_after: None,
+        };
+    }
+    OnyxError::RuntimeError(message)
+}
+
+/// Text of a system message, given either as a string or as parts.
+fn system_text(content: &ChatCompletionRequestSystemMessageContent) -> String {
+    match content {
+        ChatCompletionRequestSystemMessageContent::Text(text) => text.to_string(),
+        ChatCompletionRequestSystemMessageContent::Array(parts) => parts
+            .iter()
+            .map(|ChatCompletionRequestSystemMessageContentPart::Text(part)| part.text.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n"),
+    }
+}
+
+/// Text of a user message. Parts other than text, like images, are left out.
+fn user_text(content: &ChatCompletionRequestUserMessageContent) -> String {
+    match content {
+        ChatCompletionRequestUserMessageContent::Text(text) => text.to_string(),
+        ChatCompletionRequestUserMessageContent::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part {
+                ChatCompletionRequestUserMessageContentPart::Text(part) => Some(part.text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<&str>>()
+            .join("\n"),
+    }
+}
+
+/// Text of an assistant message, leaving out refusals.
+fn assistant_text(content: &ChatCompletionRequestAssistantMessageContent) -> String {
+    match content {
+        ChatCompletionRequestAssistantMessageContent::Text(text) => text.to_string(),
+        ChatCompletionRequestAssistantMessageContent::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part {
+                ChatCompletionRequestAssistantMessageContentPart::Text(part) => {
+                    Some(part.text.as_str())
+                }
+                _ => None,
+            })
+            .collect::<Vec<&str>>()
+            .join("\n"),
+    }
+}
+
+/// Text of a tool result, given either as a string or as parts.
+fn tool_text(content: &ChatCompletionRequestToolMessageContent) -> String {
+    match content {
+        ChatCompletionRequestToolMessageContent::Text(text) => text.to_string(),
+        ChatCompletionRequestToolMessageContent::Array(parts) => parts
+            .iter()
+            .map(|ChatCompletionRequestToolMessageContentPart::Text(part)| part.text.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnthropicProvider;
+    use crate::ai::provider::ChatRequest;
+    use serde_json::{json, Value};
+
+    fn request(response_format: Value, allow_tool_calls: bool, parallel_tool_calls: bool) -> Value {
+        let request = ChatRequest {
+            messages: vec![serde_json::from_value(
+                json!({"role": "user", "content": "how many sales"}),
+            )
+            .unwrap()],
+            tools: vec![serde_json::from_value(json!({
+                "type": "function",
+                "function": {"name": "execute_sql", "parameters": {"type": "object"}}
+            }))
+            .unwrap()],
+            response_format: serde_json::from_value(response_format).unwrap(),
+            parallel_tool_calls,
+            allow_tool_calls,
+        };
+        let provider = AnthropicProvider::new(
+            "claude-test".to_string(),
+            "http://localhost".to_string(),
+            "test-key".to_string(),
+            1024,
+        );
+        provider.to_messages_request(&request).unwrap().0
+    }
+
+    fn file_path_format() -> Value {
+        json!({
+            "type": "json_schema",
+            "json_schema": {"name": "file_path", "schema": {"type": "object"}}
+        })
+    }
+
+    #[test]
+    fn output_tool_offered_next_to_tools() {
+        let body = request(file_path_format(), true, true);
+        assert_eq!(body["tools"][0]["name"], "execute_sql");
+        assert_eq!(body["tools"][1]["name"], "file_path");
+        assert_eq!(
+            body["tool_choice"],
+            json!({"type": "any", "disable_parallel_tool_use": false})
+        );
+
+        let body = request(file_path_format(), true, false);
+        assert_eq!(
+            body["tool_choice"],
+            json!({"type": "any", "disable_parallel_tool_use": true})
+        );
+    }
+
+    #[test]
+    fn output_tool_forced_once_tool_calls_are_not_allowed() {
+        let body = request(file_path_format(), false, true);
+        assert_eq!(
+            body["tool_choice"],
+            json!({"type": "tool", "name": "file_path"})
+        );
+    }
+
+    #[test]
+    fn tool_results_sent_as_one_user_turn() {
+        let request = ChatRequest {
+            messages: serde_json::from_value(json!([
+                {"role": "system", "content": "You are a data analyst"},
+                {"role": "user", "content": [{"type": "text", "text": "how many sales"}]},
+                {"role": "assistant", "tool_calls": [
+                    {"id": "call_1", "type": "function", "function": {"name": "execute_sql", "arguments": "{\"sql\":\"SELECT 1\"}"}},
+                    {"id": "call_2", "type": "function", "function": {"name": "execute_sql", "arguments": "{\"sql\":\"SELECT 2\"}"}}
+                ]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "1"},
+                {"role": "tool", "tool_call_id": "call_2", "content": "2"}
+            ]))
+            .unwrap(),
+            tools: vec![],
+            response_format: None,
+            parallel_tool_calls: true,
+            allow_tool_calls: true,
+        };
+        let provider = AnthropicProvider::new(
+            "claude-test".to_string(),
+            "http://localhost".to_string(),
+            "test-key".to_string(),
+            1024,
+        );
+        let body = provider.to_messages_request(&request).unwrap().0;
+        assert_eq!(body["system"], "You are a data analyst");
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0]["content"][0]["text"], "how many sales");
+        assert_eq!(
+            messages[1]["content"][1]["input"],
+            json!({"sql": "SELECT 2"})
+        );
+        assert_eq!(messages[2]["role"], "user");
+        assert_eq!(messages[2]["content"][1]["tool_use_id"], "call_2");
+        assert_eq!(messages[2]["content"][1]["content"], "2");
+    }
+
+    #[test]
+    fn tool_choice_without_output_tool() {
+        assert!(request(Value::Null, true, true)["tool_choice"].is_null());
+        assert_eq!(
+            request(Value::Null, true, false)["tool_choice"],
+            json!({"type": "auto", "disable_parallel_tool_use": true})
+        );
+        assert_eq!(
+            request(Value::Null, false, true)["tool_choice"],
+            json!({"type": "none"})
+        );
+    }
+}
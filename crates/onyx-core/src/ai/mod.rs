@@ -1,29 +1,39 @@
 pub mod agent;
 pub mod anonymizer;
+pub mod anthropic;
+pub mod openai;
+pub mod provider;
 pub mod retrieval;
+pub mod retry;
 pub mod toolbox;
 pub mod tools;
+pub mod usage;
 pub mod utils;
 
-use std::{path::Path, sync::Arc};
+use std::{path::Path, sync::Arc, time::Duration};
 
 use crate::{
     config::{
         load_config,
         model::{
             AgentConfig, AnonymizerConfig, FileFormat, FlashTextSourceType, Model, OutputFormat,
-            ToolConfig,
+            ToolConfig, ANTHROPIC_API_BASE,
         },
-        ConfigManager,
+        ConfigManager, SecretResolver,
     },
     connector::Connector,
     errors::OnyxError,
     execute::agent::ToolCall,
+    sql_guard::SQLGuard,
     union_tools,
 };
-use agent::OpenAIAgent;
+use agent::{Agent, AgentBudget};
 use anonymizer::{base::Anonymizer, flash_text::FlashTextAnonymizer};
+use anthropic::AnthropicProvider;
+use async_openai::config::{AzureConfig, OpenAIConfig, OPENAI_API_BASE};
 use async_trait::async_trait;
+use openai::OpenAIProvider;
+use provider::ChatProvider;
 use retrieval::get_vector_store;
 use schemars::JsonSchema;
 use serde::Deserialize;
@@ -34,7 +44,7 @@ pub async fn setup_agent<P: AsRef<Path>>(
     agent_file: P,
     file_format: &FileFormat,
     config: Arc<ConfigManager>,
-) -> Result<(OpenAIAgent, AgentConfig), OnyxError> {
+) -> Result<(Agent, AgentConfig), OnyxError> {
     let agent_config = config.resolve_agent(agent_file).await?;
     let agent = from_config(&config, &agent_config, file_format)
         .await
@@ -42,17 +52,19 @@ pub async fn setup_agent<P: AsRef<Path>>(
     Ok((agent, agent_config))
 }
 
-pub fn setup_eval_agent(prompt: &str, model: &str) -> Result<OpenAIAgent, OnyxError> {
+pub fn setup_eval_agent(prompt: &str, model: &str) -> Result<Agent, OnyxError> {
     let config = load_config(None)?;
     let model = config.find_model(model)?;
+    let api_key = SecretResolver::new(&config.project_path).resolve(&model.api_key_ref())?;
     let agent = build_agent(
         &model,
+        api_key,
         &FileFormat::Json,
         &OutputFormat::Default,
         prompt,
         Arc::new(ToolBox::new()),
         None,
-    );
+    )?;
     Ok(agent)
 }
 
@@ -60,8 +72,13 @@ async fn from_config(
     config: &ConfigManager,
     agent_config: &AgentConfig,
     file_format: &FileFormat,
-) -> Result<OpenAIAgent, OnyxError> {
+) -> Result<Agent, OnyxError> {
     let model = config.resolve_model(&agent_config.model)?;
+    if agent_config.max_concurrent_tool_calls == 0 {
+        return Err(OnyxError::ConfigurationError(
+            "max_concurrent_tool_calls must be at least 1".to_string(),
+        ));
+    }
     let anonymizer: Option<Box<dyn Anonymizer + Send + Sync>> = match &agent_config.anonymize {
         None => None,
         Some(AnonymizerConfig::FlashText {
@@ -80,68 +97,103 @@ async fn from_config(
         }
     };
     let toolbox = Arc::new(tools_from_config(config, agent_config).await?);
+    let api_key = config.resolve_secret(&model.api_key_ref()).await?;
     let agent = build_agent(
         model,
+        api_key,
         file_format,
         &agent_config.output_format,
         &agent_config.system_instructions,
         toolbox,
         anonymizer,
-    );
+    )?
+    .with_parallel_tool_calls(agent_config.parallel_tool_calls)
+    .with_max_concurrent_tool_calls(agent_config.max_concurrent_tool_calls)
+    .with_budget(AgentBudget {
+        max_tool_rounds: agent_config.max_tool_rounds,
+        tokens: agent_config.token_budget,
+        timeout: agent_config.timeout_seconds.map(Duration::from_secs),
+    });
     Ok(agent)
 }
 
 fn build_agent(
     model: &Model,
+    api_key: String,
     file_format: &FileFormat,
     output_format: &OutputFormat,
     system_instructions: &str,
     tools: Arc<ToolBox<MultiTool>>,
     anonymizer: Option<Box<dyn Anonymizer + Send + Sync>>,
-) -> OpenAIAgent {
-    match model {
+) -> Result<Agent, OnyxError> {
+    Ok(Agent::new(
+        build_provider(model, api_key)?,
+        system_instructions.to_string(),
+        output_format.clone(),
+        anonymizer,
+        file_format.clone(),
+        tools,
+    )
+    .with_pricing(model.pricing().cloned()))
+}
+
+fn build_provider(model: &Model, api_key: String) -> Result<Box<dyn ChatProvider>, OnyxError> {
+    let provider: Box<dyn ChatProvider> = match model {
         Model::OpenAI {
-            name: _,
+            name,
             model_ref,
-            key_var,
             api_url,
             azure_deployment_id,
             azure_api_version,
+            ..
         } => {
-            let api_key = std::env::var(key_var).unwrap_or_else(|_| {
-                panic!("OpenAI key not found in environment variable {}", key_var)
-            });
-            OpenAIAgent::new(
-                model_ref.to_string(),
-                api_url.clone(),
-                api_key,
-                azure_deployment_id.clone(),
-                azure_api_version.clone(),
-                system_instructions.to_string(),
-                output_format.clone(),
-                anonymizer,
-                file_format.clone(),
-                tools,
-            )
+            let api_url = api_url.clone().unwrap_or(OPENAI_API_BASE.to_string());
+            match (azure_deployment_id, azure_api_version) {
+                (None, None) => Box::new(OpenAIProvider::new(
+                    OpenAIConfig::new()
+                        .with_api_key(api_key)
+                        .with_api_base(api_url),
+                    model_ref.to_string(),
+                )),
+                (Some(deployment_id), Some(api_version)) => Box::new(OpenAIProvider::new(
+                    AzureConfig::new()
+                        .with_api_key(api_key)
+                        .with_api_base(api_url)
+                        .with_deployment_id(deployment_id)
+                        .with_api_version(api_version),
+                    model_ref.to_string(),
+                )),
+                _ => {
+                    return Err(OnyxError::ConfigurationError(format!(
+                        "Model '{}' must set both azure_deployment_id and azure_api_version to use Azure OpenAI",
+                        name
+                    )))
+                }
+            }
         }
-        Model::Ollama {
-            name: _,
+        Model::Anthropic {
             model_ref,
-            api_key,
             api_url,
-        } => OpenAIAgent::new(
+            max_tokens,
+            ..
+        } => Box::new(AnthropicProvider::new(
             model_ref.to_string(),
-            Some(api_url.clone()),
-            api_key.clone(),
-            None,
-            None,
-            system_instructions.to_string(),
-            output_format.clone(),
-            anonymizer,
-            file_format.clone(),
-            tools,
-        ),
-    }
+            api_url
+                .clone()
+                .unwrap_or_else(|| ANTHROPIC_API_BASE.to_string()),
+            api_key,
+            *max_tokens,
+        )),
+        Model::Ollama {
+            model_ref, api_url, ..
+        } => Box::new(OpenAIProvider::new(
+            OpenAIConfig::new()
+                .with_api_key(api_key)
+                .with_api_base(api_url),
+            model_ref.to_string(),
+        )),
+    };
+    Ok(provider)
 }
 
 async fn tools_from_config(
@@ -152,24 +204,40 @@ async fn tools_from_config(
     for tool_config in agent_config.tools.iter() {
         match tool_config {
             ToolConfig::ExecuteSQL(sql_tool) => {
-                let connector = Connector::from_database(&sql_tool.database, config).await?;
+                let mut connector = Connector::from_database(&sql_tool.database, config).await?;
+                if !sql_tool.allow_writes {
+                    connector = connector.read_only();
+                }
                 let tool: ExecuteSQLTool = ExecuteSQLTool {
                     tool_name: sql_tool.name.to_string(),
                     tool_description: sql_tool.description.to_string(),
                     connector,
                     output_format: agent_config.output_format.clone(),
                     validate_mode: false,
+                    guard: SQLGuard {
+                        read_only: !sql_tool.allow_writes,
+                        allowed_tables: sql_tool.allowed_tables.clone(),
+                        denied_columns: sql_tool.denied_columns.clone(),
+                    },
                 };
                 toolbox.add_tool(sql_tool.name.to_string(), tool.into());
             }
             ToolConfig::ValidateSQL(sql_tool) => {
-                let connector = Connector::from_database(&sql_tool.database, config).await?;
+                // `EXPLAIN ANALYZE` runs the statement it explains
+                let connector = Connector::from_database(&sql_tool.database, config)
+                    .await?
+                    .read_only();
                 let tool: ExecuteSQLTool = ExecuteSQLTool {
                     tool_name: sql_tool.name.to_string(),
                     tool_description: sql_tool.description.to_string(),
                     connector,
                     output_format: agent_config.output_format.clone(),
                     validate_mode: true,
+                    guard: SQLGuard {
+                        read_only: false,
+                        allowed_tables: sql_tool.allowed_tables.clone(),
+                        denied_columns: sql_tool.denied_columns.clone(),
+                    },
                 };
                 toolbox.add_tool(sql_tool.name.to_string(), tool.into());
             }
@@ -177,7 +245,8 @@ async fn tools_from_config(
                 let db_path = config
                     .resolve_file(format!(".db-{}-{}", agent_config.name, retrieval.name))
                     .await?;
-                let vector_db = get_vector_store(retrieval, &db_path)?;
+                let api_key = config.resolve_secret(&retrieval.api_key_ref()).await?;
+                let vector_db = get_vector_store(retrieval, &db_path, &api_key)?;
                 let tool = RetrieveTool::new(retrieval, vector_db);
                 toolbox.add_tool(retrieval.name.to_string(), tool.into());
             }
@@ -0,0 +1,241 @@
+use async_openai::{
+    config::Config,
+    types::{
+        ChatCompletionMessageToolCall, ChatCompletionStreamOptions, ChatCompletionToolChoiceOption,
+        ChatCompletionToolType, CompletionUsage, CreateChatCompletionRequest,
+        CreateChatCompletionRequestArgs, CreateChatCompletionResponse,
+        CreateChatCompletionStreamResponse, FunctionCall,
+    },
+};
+use async_stream::stream;
+use async_trait::async_trait;
+use futures::StreamExt;
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::time::Duration;
+
+use super::provider::{
+    event_data, ChatProvider, ChatRequest, ChatResponse, ChatStream, ChatStreamEvent, TokenUsage,
+};
+use super::retry::{is_transient_status, request_error, retry_after};
+use crate::errors::OnyxError;
+
+const CONTEXT_WINDOW_EXCEEDED_CODE: &str = "string_above_max_length";
+/// Sent with 429 once the account runs out of credits, which waiting won't fix
+const INSUFFICIENT_QUOTA_TYPE: &str = "insufficient_quota";
+const STREAM_DONE: &str = "[DONE]";
+
+/// Provider for OpenAI compatible chat completion APIs: OpenAI itself, Azure
+/// OpenAI and local servers such as Ollama, which differ only in `C`.
+/// Requests are sent with reqwest rather than the async-openai client, which
+/// retries rate limits on its own and drops the status and headers of errors
+/// that `RetryPolicy` needs.
+pub struct OpenAIProvider<C: Config> {
+    http: reqwest::Client,
+    config: C,
+    model: String,
+}
+
+impl<C: Config> OpenAIProvider<C> {
+    pub fn new(config: C, model: String) -> Self {
+        OpenAIProvider {
+            http: reqwest::Client::new(),
+            config,
+            model,
+        }
+    }
+
+    fn build_request(
+        &self,
+        request: ChatRequest,
+    ) -> Result<CreateChatCompletionRequest, OnyxError> {
+        let mut request_builder = CreateChatCompletionRequestArgs::default();
+        request_builder
+            .model(self.model.clone())
+            .messages(request.messages);
+        if !request.tools.is_empty() {
+            request_builder.tools(request.tools);
+            if request.allow_tool_calls {
+                request_builder.parallel_tool_calls(request.parallel_tool_calls);
+            } else {
+                request_builder.tool_choice(ChatCompletionToolChoiceOption::None);
+            }
+        }
+        if let Some(format) = request.response_format {
+            request_builder.response_format(format);
+        }
+        request_builder
+            .build()
+            .map_err(|e| OnyxError::RuntimeError(format!("Unable to build LLM request: {e}")))
+    }
+
+    async fn send(
+        &self,
+        request: &CreateChatCompletionRequest,
+    ) -> Result<reqwest::Response, OnyxError> {
+        let response = self
+            .http
+            .post(self.config.url("/chat/completions"))
+            .query(&self.config.query())
+            .headers(self.config.headers())
+            .json(request)
+            .send()
+            .await
+            .map_err(request_error)?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let retry_after = retry_after(response.headers());
+        let error = response.json::<Value>().await.unwrap_or_default();
+        Err(api_error(&error, Some((status, retry_after))))
+    }
+}
+
+#[async_trait]
+impl<C: Config + Send + Sync> ChatProvider for OpenAIProvider<C> {
+    async fn complete(&self, request: ChatRequest) -> Result<ChatResponse, OnyxError> {
+        let request = self.build_request(request)?;
+        let response = self
+            .send(&request)
+            .await?
+            .json::<CreateChatCompletionResponse>()
+            .await
+            .map_err(|e| {
+                OnyxError::RuntimeError(format!("Unable to parse OpenAI response: {}", e))
+            })?;
+
+        let usage = response.usage.map(to_token_usage);
+        let message = response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| OnyxError::LLMError("Empty response from OpenAI".to_string()))?
+            .message;
+        Ok(ChatResponse {
+            content: message.content,
+            tool_calls: message.tool_calls.unwrap_or_default(),
+            usage,
+        })
+    }
+
+    async fn complete_stream(&self, request: ChatRequest) -> Result<ChatStream, OnyxError> {
+        let mut request = self.build_request(request)?;
+        request.stream = Some(true);
+        // Usage is only reported for streams that ask for it, in a last chunk
+        // without choices
+        request.stream_options = Some(ChatCompletionStreamOptions {
+            include_usage: true,
+        });
+        let mut events = Box::pin(event_data(self.send(&request).await?));
+
+        Ok(Box::pin(stream! {
+            let mut content = String::new();
+            let mut tool_calls: Vec<ChatCompletionMessageToolCall> = vec![];
+            let mut usage = None;
+            while let Some(data) = events.next().await {
+                let data = match data {
+                    Ok(data) if data == STREAM_DONE => break,
+                    Ok(data) => data,
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                };
+                let chunk = serde_json::from_str::<CreateChatCompletionStreamResponse>(&data);
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        // Errors after the stream started are sent as an event
+                        let error = serde_json::from_str::<Value>(&data).unwrap_or_default();
+                        yield Err(if error.get("error").is_some() {
+                            api_error(&error, None)
+                        } else {
+                            OnyxError::RuntimeError(format!(
+                                "Unable to parse OpenAI response: {}",
+                                e
+                            ))
+                        });
+                        return;
+                    }
+                };
+                if let Some(chunk_usage) = chunk.usage {
+                    usage = Some(to_token_usage(chunk_usage));
+                }
+                for choice in chunk.choices {
+                    if let Some(delta) = choice.delta.content.filter(|d| !d.is_empty()) {
+                        content.push_str(&delta);
+                        yield Ok(ChatStreamEvent::Delta(delta));
+                    }
+                    // Tool calls arrive in pieces, the first one of each
+                    // carrying its id and name and the rest its arguments
+                    for chunk in choice.delta.tool_calls.unwrap_or_default() {
+                        let index = chunk.index as usize;
+                        while tool_calls.len() <= index {
+                            tool_calls.push(ChatCompletionMessageToolCall {
+                                id: String::new(),
+                                r#type: ChatCompletionToolType::Function,
+                                function: FunctionCall {
+                                    name: String::new(),
+                                    arguments: String::new(),
+                                },
+                            });
+                        }
+                        let tool_call = &mut tool_calls[index];
+                        if let Some(id) = chunk.id {
+                            tool_call.id = id;
+                        }
+                        if let Some(function) = chunk.function {
+                            if let Some(name) = function.name {
+                                tool_call.function.name.push_str(&name);
+                            }
+                            if let Some(arguments) = function.arguments {
+                                tool_call.function.arguments.push_str(&arguments);
+                            }
+                        }
+                    }
+                }
+            }
+            yield Ok(ChatStreamEvent::Completed(ChatResponse {
+                content: (!content.is_empty()).then_some(content),
+                tool_calls,
+                usage,
+            }));
+        }))
+    }
+}
+
+fn to_token_usage(usage: CompletionUsage) -> TokenUsage {
+    TokenUsage {
+        prompt_tokens: usage.prompt_tokens.into(),
+        completion_tokens: usage.completion_tokens.into(),
+    }
+}
+
+/// Maps an error returned by the chat completions API, along with the status
+/// and `Retry-After` of its response unless it came within a stream, to an
+/// `OnyxError`. Rate limits, timeouts and server errors are transient.
+fn api_error(error: &Value, response: Option<(StatusCode, Option<Duration>)>) -> OnyxError {
+    let error = &error["error"];
+    if error["code"] == CONTEXT_WINDOW_EXCEEDED_CODE {
+        return OnyxError::LLMError(
+            "Context window length exceeded. Shorten the prompt being sent to the LLM.".into(),
+        );
+    }
+    let message = format!(
+        "Error in completion request: {}",
+        error["message"].as_str().unwrap_or_default()
+    );
+    match response {
+        Some((status, retry_after))
+            if is_transient_status(status.as_u16()) && error["type"] != INSUFFICIENT_QUOTA_TYPE =>
+        {
+            OnyxError::TransientLLMError {
+                message: format!("{} ({})", message, status),
+                retry_after,
+            }
+        }
+        Some((status, _)) => OnyxError::RuntimeError(format!("{} ({})", message, status)),
+        None => OnyxError::RuntimeError(message),
+    }
+}
@@ -0,0 +1,173 @@
+use async_openai::types::{
+    ChatCompletionMessageToolCall, ChatCompletionRequestMessage, ChatCompletionTool, ResponseFormat,
+};
+use async_stream::stream;
+use async_trait::async_trait;
+use futures::{stream, Stream, StreamExt};
+use std::pin::Pin;
+
+use crate::errors::OnyxError;
+
+/// A completion request, in the OpenAI chat format the agent loop builds its
+/// messages and tool specs in.
+#[derive(Debug, Clone)]
+pub struct ChatRequest {
+    pub messages: Vec<ChatCompletionRequestMessage>,
+    pub tools: Vec<ChatCompletionTool>,
+    /// JSON schema the final answer must follow, if any
+    pub response_format: Option<ResponseFormat>,
+    pub parallel_tool_calls: bool,
+    /// Whether the model may call tools. `tools` are still sent when it may
+    /// not, as earlier messages can refer to them.
+    pub allow_tool_calls: bool,
+}
+
+/// The model's reply: text content and the tool calls it asked for, if any.
+#[derive(Debug, Clone, Default)]
+pub struct ChatResponse {
+    pub content: Option<String>,
+    pub tool_calls: Vec<ChatCompletionMessageToolCall>,
+    /// Tokens the request used, if the API reported them
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// An event of a streamed completion.
+#[derive(Debug, Clone)]
+pub enum ChatStreamEvent {
+    /// The next piece of the text content
+    Delta(String),
+    /// The whole response, sent last
+    Completed(ChatResponse),
+}
+
+pub type ChatStream = Pin<Box<dyn Stream<Item = Result<ChatStreamEvent, OnyxError>> + Send>>;
+
+/// A model vendor's chat completion API. Providers translate requests to
+/// their wire format, parse tool calls and structured output from replies,
+/// and map vendor errors to `OnyxError`, so that the agent loop, tools,
+/// anonymization and output mapping stay the same for every vendor.
+#[async_trait]
+pub trait ChatProvider: Send + Sync {
+    async fn complete(&self, request: ChatRequest) -> Result<ChatResponse, OnyxError>;
+
+    /// Streams the response as it is generated. Providers that cannot stream
+    /// send the whole content as a single delta.
+    async fn complete_stream(&self, request: ChatRequest) -> Result<ChatStream, OnyxError> {
+        let response = self.complete(request).await?;
+        let mut events = vec![];
+        if let Some(content) = response.content.clone().filter(|c| !c.is_empty()) {
+            events.push(Ok(ChatStreamEvent::Delta(content)));
+        }
+        events.push(Ok(ChatStreamEvent::Completed(response)));
+        Ok(Box::pin(stream::iter(events)))
+    }
+}
+
+/// The data of each server-sent event of a streamed response, in order.
+pub fn event_data(response: reqwest::Response) -> impl Stream<Item = Result<String, OnyxError>> {
+    let mut bytes = response.bytes_stream();
+    stream! {
+        let mut buffer: Vec<u8> = vec![];
+        while let Some(chunk) = bytes.next().await {
+            match chunk {
+                Ok(chunk) => buffer.extend_from_slice(&chunk),
+                Err(e) => {
+                    yield Err(OnyxError::RuntimeError(format!(
+                        "Error in completion request: {}",
+                        e
+                    )));
+                    return;
+                }
+            }
+            for data in take_events(&mut buffer) {
+                yield Ok(data);
+            }
+        }
+        // A `\r` left at the end can no longer be followed by a `\n`
+        if buffer.ends_with(b"\r") {
+            buffer.push(b'\n');
+            for data in take_events(&mut buffer) {
+                yield Ok(data);
+            }
+        }
+    }
+}
+
+/// Removes the complete events from the start of `buffer` and returns their
+/// data. Lines end in `\r\n`, `\n` or `\r`, and events with a blank line.
+fn take_events(buffer: &mut Vec<u8>) -> Vec<String> {
+    let mut events = vec![];
+    while let Some((end, next)) = event_end(buffer) {
+        let event = String::from_utf8_lossy(&buffer[..end]).to_string();
+        buffer.drain(..next);
+        if let Some(data) = event
+            .split(['\r', '\n'])
+            .find_map(|line| line.strip_prefix("data:"))
+        {
+            events.push(data.trim().to_string());
+        }
+    }
+    events
+}
+
+/// Where the first event of `buffer` ends and the next one starts, if the
+/// blank line ending it has been received.
+fn event_end(buffer: &[u8]) -> Option<(usize, usize)> {
+    let mut line_start = 0;
+    let mut i = 0;
+    while i < buffer.len() {
+        let next = match buffer[i] {
+            b'\n' => i + 1,
+            // Wait for the next chunk to tell `\r\n` from `\r`
+            b'\r' if i + 1 == buffer.len() => return None,
+            b'\r' if buffer[i + 1] == b'\n' => i + 2,
+            b'\r' => i + 1,
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        if i == line_start {
+            return Some((line_start, next));
+        }
+        line_start = next;
+        i = next;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::take_events;
+
+    #[test]
+    fn takes_events_with_any_line_ending() {
+        for separator in ["\n", "\r\n", "\r"] {
+            let stream = format!("event: a{0}data: 1{0}{0}data: 2{0}{0}data: 3", separator);
+            let mut buffer = stream.as_bytes().to_vec();
+            assert_eq!(take_events(&mut buffer), vec!["1", "2"]);
+            assert_eq!(buffer, b"data: 3");
+        }
+    }
+
+    #[test]
+    fn waits_for_crlf_split_across_chunks() {
+        let mut buffer = b"data: 1\r\n\r".to_vec();
+        assert!(take_events(&mut buffer).is_empty());
+        buffer.push(b'\n');
+        assert_eq!(take_events(&mut buffer), vec!["1"]);
+        assert!(buffer.is_empty());
+    }
+}
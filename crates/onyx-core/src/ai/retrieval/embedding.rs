@@ -40,10 +40,18 @@ pub struct Document {
     pub embeddings: Vec<f32>,
 }
 
+/// Documents found for a query and the tokens used to embed the query.
+#[derive(Debug)]
+pub struct SearchResults {
+    pub documents: Vec<Document>,
+    pub embedding_tokens: u64,
+}
+
 #[async_trait]
 pub trait VectorStore {
-    async fn embed(&self, documents: &Vec<Document>) -> anyhow::Result<()>;
-    async fn search(&self, query: &str) -> anyhow::Result<Vec<Document>>;
+    /// Embeds and stores the documents, returning the tokens used to embed them.
+    async fn embed(&self, documents: &Vec<Document>) -> anyhow::Result<u64>;
+    async fn search(&self, query: &str) -> anyhow::Result<SearchResults>;
 }
 
 impl std::fmt::Debug for dyn VectorStore + Send + Sync {
@@ -63,10 +71,10 @@ pub struct LanceDBStore {
 }
 
 impl LanceDBStore {
-    pub fn with_config(tool_config: &RetrievalTool, db_path: &str) -> Self {
+    pub fn with_config(tool_config: &RetrievalTool, db_path: &str, api_key: &str) -> Self {
         let client = Client::with_config(
             OpenAIConfig::new()
-                .with_api_key(tool_config.get_api_key())
+                .with_api_key(api_key)
                 .with_api_base(tool_config.api_url.to_string()),
         );
 
@@ -165,20 +173,25 @@ impl LanceDBStore {
         Ok(())
     }
 
-    async fn embed_query(&self, query: &str) -> anyhow::Result<Vec<f32>> {
+    /// The embedding of the query and the tokens it used.
+    async fn embed_query(&self, query: &str) -> anyhow::Result<(Vec<f32>, u64)> {
         let embeddings_request = CreateEmbeddingRequestArgs::default()
             .model(self.embed_model.clone())
             .input(EmbeddingInput::String(query.to_string()))
             .dimensions(self.n_dims as u32)
             .build()?;
         let embeddings_response = self.client.embeddings().create(embeddings_request).await?;
-        Ok(embeddings_response.data[0].embedding.clone())
+        Ok((
+            embeddings_response.data[0].embedding.clone(),
+            embeddings_response.usage.total_tokens.into(),
+        ))
     }
 
+    /// The embeddings of the documents and the tokens they used.
     async fn embed_documents(
         &self,
         documents: &Vec<Document>,
-    ) -> anyhow::Result<Vec<Option<Vec<Option<f32>>>>> {
+    ) -> anyhow::Result<(Vec<Option<Vec<Option<f32>>>>, u64)> {
         let embedding_contents = documents
             .iter()
             .map(|doc| doc.content.clone())
@@ -189,17 +202,25 @@ impl LanceDBStore {
             .dimensions(self.n_dims as u32)
             .build()?;
         let embeddings_response = self.client.embeddings().create(embeddings_request).await?;
-        Ok(embeddings_response
-            .data
-            .iter()
-            .map(|e| Some(e.embedding.iter().map(|v| Some(v.to_owned())).collect()))
-            .collect())
+        log::info!(
+            "Embedded {} documents with {} tokens",
+            documents.len(),
+            embeddings_response.usage.total_tokens
+        );
+        Ok((
+            embeddings_response
+                .data
+                .iter()
+                .map(|e| Some(e.embedding.iter().map(|v| Some(v.to_owned())).collect()))
+                .collect(),
+            embeddings_response.usage.total_tokens.into(),
+        ))
     }
 }
 
 #[async_trait]
 impl VectorStore for LanceDBStore {
-    async fn embed(&self, documents: &Vec<Document>) -> anyhow::Result<()> {
+    async fn embed(&self, documents: &Vec<Document>) -> anyhow::Result<u64> {
         let table = self.get_database_metadata_table().await?;
         let schema = table.schema().await?;
         let contents = Arc::new(StringArray::from_iter_values(
@@ -211,7 +232,7 @@ impl VectorStore for LanceDBStore {
         let source_identifiers = Arc::new(StringArray::from_iter_values(
             documents.iter().map(|doc| doc.source_identifier.clone()),
         ));
-        let embedding_iter = self.embed_documents(documents).await?;
+        let (embedding_iter, embedding_tokens) = self.embed_documents(documents).await?;
 
         let embeddings: Arc<FixedSizeListArray> = Arc::new(
             FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
@@ -233,12 +254,12 @@ impl VectorStore for LanceDBStore {
         );
         self.add_batches(&table, Box::new(batches)).await?;
         log::info!("{} documents embedded!", documents.len());
-        Ok(())
+        Ok(embedding_tokens)
     }
 
-    async fn search(&self, query: &str) -> anyhow::Result<Vec<Document>> {
+    async fn search(&self, query: &str) -> anyhow::Result<SearchResults> {
         log::info!("Embedding search query: {}", query);
-        let query_vector = self.embed_query(query).await?;
+        let (query_vector, embedding_tokens) = self.embed_query(query).await?;
 
         if query_vector.is_empty() {
             anyhow::bail!(OnyxError::RuntimeError(
@@ -264,7 +285,10 @@ impl VectorStore for LanceDBStore {
         let record_batch = ReciprocalRankingFusion::default()
             .rerank(&mut results, &mut fts_results, Some(self.top_k))
             .await?;
-        let docs: Vec<Document> = from_record_batch(&record_batch)?;
-        Ok(docs)
+        let documents: Vec<Document> = from_record_batch(&record_batch)?;
+        Ok(SearchResults {
+            documents,
+            embedding_tokens,
+        })
     }
 }
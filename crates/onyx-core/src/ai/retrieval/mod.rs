@@ -1,5 +1,6 @@
 use embedding::{Document, LanceDBStore, VectorStore};
 
+use crate::ai::usage::Usage;
 use crate::config::model::{RetrievalTool, ToolConfig};
 use crate::config::ConfigManager;
 use crate::errors::OnyxError;
@@ -28,7 +29,9 @@ async fn get_documents_from_files(
     Ok(documents)
 }
 
-pub async fn build_embeddings(config: &ConfigManager) -> Result<(), OnyxError> {
+/// Embeds the documents of every retrieval tool, returning the tokens used.
+pub async fn build_embeddings(config: &ConfigManager) -> Result<Usage, OnyxError> {
+    let mut usage = Usage::default();
     for agent_dir in config.list_agents().await? {
         println!(
             "{}",
@@ -41,7 +44,8 @@ pub async fn build_embeddings(config: &ConfigManager) -> Result<(), OnyxError> {
                 let db_path = config
                     .resolve_file(format!(".db-{}-{}", &agent.name, retrieval.name))
                     .await?;
-                let db = get_vector_store(&retrieval, &db_path)?;
+                let api_key = config.resolve_secret(&retrieval.api_key_ref()).await?;
+                let db = get_vector_store(&retrieval, &db_path, &api_key)?;
                 let documents = get_documents_from_files(&retrieval.src, config).await?;
                 if documents.is_empty() {
                     println!(
@@ -54,17 +58,19 @@ pub async fn build_embeddings(config: &ConfigManager) -> Result<(), OnyxError> {
                     );
                     continue;
                 }
-                db.embed(&documents).await?;
+                let embedding_tokens = db.embed(&documents).await?;
+                usage.add(&Usage::from_embedding(embedding_tokens));
             }
         }
     }
-    Ok(())
+    Ok(usage)
 }
 
 pub fn get_vector_store(
     tool_config: &RetrievalTool,
     db_path: &str,
+    api_key: &str,
 ) -> anyhow::Result<Box<dyn VectorStore + Send + Sync>> {
-    let db = LanceDBStore::with_config(tool_config, db_path);
+    let db = LanceDBStore::with_config(tool_config, db_path, api_key);
     Ok(Box::new(db))
 }
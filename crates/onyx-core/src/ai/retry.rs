@@ -0,0 +1,160 @@
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::time::Duration;
+
+use crate::errors::OnyxError;
+
+/// When and how long to wait before sending a chat request again after a
+/// `OnyxError::TransientLLMError`. Other errors are not retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Longest `Retry-After` to wait for; the error is returned if the API
+    /// asks for more
+    pub max_retry_after: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 4,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            max_retry_after: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait before the next attempt, after `attempt` retries
+    /// already failed with `error`, or `None` to give up. Waits for the
+    /// `Retry-After` the API sent, else backs off exponentially with jitter,
+    /// so that agents of a workflow don't retry in lockstep.
+    pub fn delay(&self, attempt: u32, error: &OnyxError) -> Option<Duration> {
+        let OnyxError::TransientLLMError { retry_after, .. } = error else {
+            return None;
+        };
+        if attempt >= self.max_retries {
+            return None;
+        }
+        match retry_after {
+            Some(retry_after) if *retry_after > self.max_retry_after => None,
+            Some(retry_after) => Some(*retry_after),
+            None => {
+                let backoff = self
+                    .initial_backoff
+                    .saturating_mul(2u32.saturating_pow(attempt))
+                    .min(self.max_backoff);
+                Some(backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0)))
+            }
+        }
+    }
+}
+
+/// Whether a response with this status may succeed when sent again: rate
+/// limits, timeouts and server errors, including Anthropic's 529 overloaded.
+pub fn is_transient_status(status: u16) -> bool {
+    matches!(status, 408 | 429 | 500..=599)
+}
+
+/// Maps a request that got no response: timeouts and failed connections may
+/// go away, other errors won't.
+pub fn request_error(e: reqwest::Error) -> OnyxError {
+    let message = format!("Error in completion request: {}", e);
+    if e.is_timeout() || e.is_connect() {
+        return OnyxError::TransientLLMError {
+            message,
+            retry_after: None,
+        };
+    }
+    OnyxError::RuntimeError(message)
+}
+
+/// The `Retry-After` header of a response, given in seconds.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{retry_after, RetryPolicy};
+    use crate::errors::OnyxError;
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use std::time::Duration;
+
+    fn transient(retry_after: Option<Duration>) -> OnyxError {
+        OnyxError::TransientLLMError {
+            message: "overloaded".to_string(),
+            retry_after,
+        }
+    }
+
+    #[test]
+    fn backs_off_exponentially_with_jitter() {
+        let policy = RetryPolicy::default();
+        for (attempt, backoff) in [(0, 1), (1, 2), (2, 4), (3, 8)] {
+            let backoff = Duration::from_secs(backoff);
+            let delay = policy.delay(attempt, &transient(None)).unwrap();
+            assert!(delay >= backoff / 2 && delay <= backoff, "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn caps_backoff() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            ..Default::default()
+        };
+        let delay = policy.delay(9, &transient(None)).unwrap();
+        assert!(delay <= policy.max_backoff);
+    }
+
+    #[test]
+    fn gives_up_after_max_retries_or_permanent_errors() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(4, &transient(None)), None);
+        assert_eq!(
+            policy.delay(0, &OnyxError::RuntimeError("bad request".to_string())),
+            None
+        );
+    }
+
+    #[test]
+    fn waits_for_retry_after_up_to_max_retry_after() {
+        let policy = RetryPolicy::default();
+        let asked = Duration::from_secs(5);
+        assert_eq!(policy.delay(0, &transient(Some(asked))), Some(asked));
+        assert_eq!(
+            policy.delay(0, &transient(Some(Duration::ZERO))),
+            Some(Duration::ZERO)
+        );
+        let too_long = policy.max_retry_after + Duration::from_secs(1);
+        assert_eq!(policy.delay(0, &transient(Some(too_long))), None);
+    }
+
+    #[test]
+    fn parses_retry_after_seconds() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("1.5"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_millis(1500)));
+        // HTTP dates are not supported
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("-1"));
+        assert_eq!(retry_after(&headers), None);
+    }
+}
@@ -1,5 +1,5 @@
 use super::tools::Tool;
-use crate::{execute::agent::ToolCall, utils::truncate_with_ellipsis};
+use crate::{errors::OnyxError, execute::agent::ToolCall, utils::truncate_with_ellipsis};
 use serde_json::Value;
 use std::{
     collections::HashMap,
@@ -59,17 +59,27 @@ where
                 name: name.to_string(),
                 output: format!("Tool {} not found", name),
                 metadata: None,
+                usage: None,
             },
             Some(tool) => match tool.call(&parameters).await {
                 Ok(tool_call) => tool_call,
                 Err(e) => {
-                    let err_msg =
-                        truncate_with_ellipsis(&format!("Error executing tool: {:?}", e), None);
+                    let err_msg = match e.downcast_ref::<OnyxError>() {
+                        Some(OnyxError::QueryTimeout(msg)) => format!(
+                            "Error executing tool: {}. Try a cheaper query, e.g. filter earlier, aggregate or add a LIMIT.",
+                            msg
+                        ),
+                        Some(err @ OnyxError::SQLPolicyError(_)) => {
+                            format!("Error executing tool: {}", err)
+                        }
+                        _ => truncate_with_ellipsis(&format!("Error executing tool: {:?}", e), None),
+                    };
                     log::info!("{}", err_msg);
                     ToolCall {
                         name: name.to_string(),
                         output: err_msg,
                         metadata: None,
+                        usage: None,
                     }
                 }
             },
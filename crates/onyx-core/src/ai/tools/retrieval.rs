@@ -1,6 +1,7 @@
 use super::Tool;
 use crate::{
-    ai::retrieval::embedding::VectorStore, config::model::RetrievalTool as Retrieval,
+    ai::{retrieval::embedding::VectorStore, usage::Usage},
+    config::model::RetrievalTool as Retrieval,
     execute::agent::ToolCall,
 };
 use async_trait::async_trait;
@@ -50,11 +51,13 @@ impl Tool for RetrieveTool {
     async fn call_internal(&self, parameters: &RetrieveParams) -> anyhow::Result<ToolCall> {
         let results = self.vector_db.search(&parameters.query).await;
         let mut output = String::new();
+        let mut usage = None;
         match results {
             Ok(results) => {
-                for result in results {
+                for result in results.documents {
                     output.push_str(&format!("{}\n", result.content));
                 }
+                usage = Some(Usage::from_embedding(results.embedding_tokens));
             }
             Err(e) => {
                 log::error!("Error: {e}");
@@ -64,6 +67,7 @@ impl Tool for RetrieveTool {
             name: self.name(),
             output,
             metadata: None,
+            usage,
         })
     }
 }
@@ -2,8 +2,10 @@ use super::Tool;
 use crate::{
     ai::utils::record_batches_to_markdown,
     config::model::OutputFormat,
-    connector::{load_result, Connector},
+    connector::Connector,
     execute::agent::{ToolCall, ToolMetadata},
+    result_store::load_result,
+    sql_guard::SQLGuard,
 };
 use async_trait::async_trait;
 use schemars::JsonSchema;
@@ -21,6 +23,7 @@ pub struct ExecuteSQLTool {
     pub output_format: OutputFormat,
     pub connector: Connector,
     pub validate_mode: bool,
+    pub guard: SQLGuard,
 }
 
 #[async_trait]
@@ -41,19 +44,19 @@ impl Tool for ExecuteSQLTool {
     }
 
     async fn call_internal(&self, parameters: &ExecuteSQLParams) -> anyhow::Result<ToolCall> {
+        self.guard
+            .check(&parameters.sql, &self.connector.dialect())?;
         let (output, metadata) = match self.validate_mode {
             true => {
-                let (dataset, schema) = self
-                    .connector
-                    .run_query_and_load(&format!("EXPLAIN ({})", &parameters.sql))
-                    .await?;
+                let (dataset, schema) = self.connector.explain_query(&parameters.sql).await?;
                 log::info!("Validate mode");
                 log::info!("Schema: {:?}", schema);
                 log::info!("Dataset: {:?}", dataset);
                 (true.to_string(), None)
             }
             false => {
-                let file_path = self.connector.run_query(&parameters.sql).await?;
+                let (file_path, cache_status) =
+                    self.connector.run_query_cached(&parameters.sql).await?;
                 let output = match self.output_format {
                     OutputFormat::Default => {
                         let (datasets, schema) = load_result(&file_path)?;
@@ -67,6 +70,7 @@ impl Tool for ExecuteSQLTool {
                     Some(ToolMetadata::ExecuteSQL {
                         sql_query: parameters.sql.to_string(),
                         output_file: file_path,
+                        cache_status,
                     }),
                 )
             }
@@ -76,6 +80,7 @@ impl Tool for ExecuteSQLTool {
             name: self.name(),
             output,
             metadata,
+            usage: None,
         })
     }
 }
@@ -0,0 +1,166 @@
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
+
+use crate::config::model::ModelPricing;
+
+use super::provider::TokenUsage;
+
+const TOKENS_PER_PRICE_UNIT: f64 = 1_000_000.0;
+
+/// Tokens used by the requests of a run and what they cost.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[pyclass(module = "onyx_py")]
+pub struct Usage {
+    /// Completion requests sent to the model
+    #[pyo3(get)]
+    pub requests: u64,
+    #[pyo3(get)]
+    pub prompt_tokens: u64,
+    #[pyo3(get)]
+    pub completion_tokens: u64,
+    /// Tokens embedded to search retrieval tools
+    #[pyo3(get)]
+    pub embedding_tokens: u64,
+    /// Cost in USD, unknown unless every request was sent to a model with
+    /// `pricing`
+    #[pyo3(get)]
+    pub cost: Option<f64>,
+}
+
+impl Usage {
+    /// Usage of a completion request, which the API may not have reported.
+    pub fn from_request(usage: Option<TokenUsage>, pricing: Option<&ModelPricing>) -> Self {
+        let usage = match usage {
+            Some(usage) => usage,
+            None => {
+                return Usage {
+                    requests: 1,
+                    ..Default::default()
+                }
+            }
+        };
+        Usage {
+            requests: 1,
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            embedding_tokens: 0,
+            cost: pricing.map(|pricing| {
+                (usage.prompt_tokens as f64 * pricing.prompt
+                    + usage.completion_tokens as f64 * pricing.completion)
+                    / TOKENS_PER_PRICE_UNIT
+            }),
+        }
+    }
+
+    pub fn from_embedding(tokens: u64) -> Self {
+        Usage {
+            embedding_tokens: tokens,
+            ..Default::default()
+        }
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens + self.embedding_tokens
+    }
+
+    pub fn add(&mut self, other: &Usage) {
+        // Without requests there is no cost to be unknown
+        self.cost = match (self.requests, other.requests) {
+            (0, _) => other.cost,
+            (_, 0) => self.cost,
+            _ => self.cost.zip(other.cost).map(|(cost, other)| cost + other),
+        };
+        self.requests += other.requests;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.embedding_tokens += other.embedding_tokens;
+    }
+}
+
+impl std::fmt::Display for Usage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}, {} prompt + {} completion tokens",
+            self.requests,
+            if self.requests == 1 {
+                "request"
+            } else {
+                "requests"
+            },
+            self.prompt_tokens,
+            self.completion_tokens
+        )?;
+        if self.embedding_tokens > 0 {
+            write!(f, ", {} embedding tokens", self.embedding_tokens)?;
+        }
+        match self.cost {
+            Some(cost) => write!(f, ", ${:.4}", cost),
+            None if self.requests > 0 => write!(f, ", cost unknown"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Usage;
+    use crate::ai::provider::TokenUsage;
+    use crate::config::model::ModelPricing;
+
+    const PRICING: ModelPricing = ModelPricing {
+        prompt: 3.0,
+        completion: 15.0,
+    };
+
+    fn request(prompt_tokens: u64, completion_tokens: u64) -> Option<TokenUsage> {
+        Some(TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+        })
+    }
+
+    #[test]
+    fn prices_tokens_per_million() {
+        let usage = Usage::from_request(request(1000, 100), Some(&PRICING));
+        assert_eq!(usage.requests, 1);
+        assert_eq!(usage.total_tokens(), 1100);
+        assert!((usage.cost.unwrap() - 0.0045).abs() < 1e-12);
+        assert_eq!(
+            usage.to_string(),
+            "1 request, 1000 prompt + 100 completion tokens, $0.0045"
+        );
+    }
+
+    #[test]
+    fn cost_unknown_without_pricing_or_reported_usage() {
+        let unpriced = Usage::from_request(request(1000, 100), None);
+        assert_eq!(unpriced.cost, None);
+        let unreported = Usage::from_request(None, Some(&PRICING));
+        assert_eq!(unreported.requests, 1);
+        assert_eq!(unreported.cost, None);
+        assert_eq!(
+            unreported.to_string(),
+            "1 request, 0 prompt + 0 completion tokens, cost unknown"
+        );
+    }
+
+    #[test]
+    fn adds_costs_only_when_all_known() {
+        let mut total = Usage::default();
+        total.add(&Usage::from_request(request(1000, 100), Some(&PRICING)));
+        total.add(&Usage::from_request(request(2000, 100), Some(&PRICING)));
+        // Embeddings are not priced, which doesn't make the cost unknown
+        total.add(&Usage::from_embedding(50));
+        assert_eq!(total.requests, 2);
+        assert_eq!(total.total_tokens(), 3250);
+        assert!((total.cost.unwrap() - 0.012).abs() < 1e-12);
+        assert_eq!(
+            total.to_string(),
+            "2 requests, 3000 prompt + 200 completion tokens, 50 embedding tokens, $0.0120"
+        );
+
+        total.add(&Usage::from_request(request(10, 10), None));
+        assert_eq!(total.cost, None);
+    }
+}
@@ -0,0 +1,315 @@
+use clap::Parser;
+use migration::{Migrator, MigratorTrait};
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::config::model::FileFormat;
+use crate::config::{ConfigBuilder, ConfigManager};
+use crate::db::client::establish_connection;
+use crate::db::conversations::{create_conversation, get_conversation_by_agent};
+use crate::db::message::{save_message, save_message_with_id};
+use crate::errors::OnyxError;
+use crate::execute::agent::{
+    run_agent_with_handler, AgentEvent, AgentReceiver, ConversationTurn, ToolCallRecorder,
+    ToolMetadata,
+};
+use crate::execute::core::event::Handler;
+use crate::execute::exporter::{export_csv, get_file_directories};
+use crate::result_store::load_result;
+use crate::service::agent::conversation_history;
+use crate::theme::StyledText;
+use crate::utils::{find_project_path, print_colored_sql};
+
+const DEFAULT_EXPORT_PATH: &str = "results.csv";
+
+const HELP: &str = "Commands:
+  /sql                 Show the last SQL query the agent ran
+  /export csv [PATH]   Save the result of the last query as CSV (default: results.csv)
+  /reset               Start a new conversation
+  /help                Show this help
+  /exit                Quit";
+
+#[derive(Parser, Debug)]
+pub struct ChatArgs {
+    /// Path to the agent file to chat with
+    #[clap(long)]
+    agent: String,
+
+    /// Start a new conversation instead of continuing the last one
+    #[clap(long, default_value_t = false)]
+    new: bool,
+}
+
+impl ChatArgs {
+    pub fn new(agent: String) -> Self {
+        ChatArgs { agent, new: false }
+    }
+}
+
+/// A query the agent ran and the file its result was stored in.
+#[derive(Clone)]
+struct Query {
+    sql: String,
+    output_file: String,
+}
+
+#[derive(Default)]
+struct Answer {
+    tool_calls: ToolCallRecorder,
+    queries: Vec<Query>,
+}
+
+/// Prints the agent's events like `onyx run` does and keeps its tool calls,
+/// to save them with the answer and to look up the last query.
+struct ChatReceiver {
+    receiver: AgentReceiver,
+    answer: Arc<Mutex<Answer>>,
+}
+
+impl Handler for ChatReceiver {
+    type Event = AgentEvent;
+
+    fn handle(&self, event: &Self::Event) {
+        self.receiver.handle(event);
+        let mut answer = self.answer.lock().unwrap();
+        answer.tool_calls.record(event);
+        if let AgentEvent::ToolCall(tool_call) = event {
+            if let Some(ToolMetadata::ExecuteSQL {
+                sql_query,
+                output_file,
+                ..
+            }) = &tool_call.metadata
+            {
+                answer.queries.push(Query {
+                    sql: sql_query.clone(),
+                    output_file: output_file.clone(),
+                });
+            }
+        }
+    }
+}
+
+struct ChatSession {
+    agent_file: PathBuf,
+    /// Agent path relative to the project, which conversations are saved under
+    agent_ref: String,
+    config: Arc<ConfigManager>,
+    conversation_id: Option<Uuid>,
+    history: Vec<ConversationTurn>,
+    last_query: Option<Query>,
+}
+
+impl ChatSession {
+    async fn ask(&mut self, question: &str) -> Result<(), OnyxError> {
+        let answer = Arc::new(Mutex::new(Answer::default()));
+        let result = run_agent_with_handler(
+            &self.agent_file,
+            &FileFormat::Markdown,
+            Some(question.to_string()),
+            self.history.clone(),
+            self.config.clone(),
+            ChatReceiver {
+                receiver: AgentReceiver::new(),
+                answer: answer.clone(),
+            },
+        )
+        .await?;
+        let output = result.output.to_string();
+        let Answer {
+            mut tool_calls,
+            queries,
+        } = std::mem::take(&mut *answer.lock().unwrap());
+        let tool_calls = tool_calls.take();
+        if let Some(query) = queries.last() {
+            self.last_query = Some(query.clone());
+        }
+
+        let conversation_id = match self.conversation_id {
+            Some(conversation_id) => conversation_id,
+            None => create_conversation(&self.agent_ref, question).await.id,
+        };
+        self.conversation_id = Some(conversation_id);
+        save_message(conversation_id, question, true).await;
+        let saved_tool_calls = if tool_calls.is_empty() {
+            None
+        } else {
+            serde_json::to_string(&tool_calls).ok()
+        };
+        save_message_with_id(
+            Uuid::new_v4(),
+            conversation_id,
+            &output,
+            false,
+            saved_tool_calls,
+        )
+        .await;
+        self.history.push(ConversationTurn {
+            question: question.to_string(),
+            tool_calls,
+            answer: output,
+        });
+        Ok(())
+    }
+
+    fn show_sql(&self) {
+        match &self.last_query {
+            Some(query) => print_colored_sql(&query.sql),
+            None => println!("{}", "No query has been run yet.".warning()),
+        }
+    }
+
+    fn export(&self, args: &[&str]) -> Result<(), OnyxError> {
+        let (format, path) = match args {
+            [format] => (*format, DEFAULT_EXPORT_PATH),
+            [format, path] => (*format, *path),
+            _ => {
+                return Err(OnyxError::ArgumentError(
+                    "Usage: /export csv [PATH]".to_string(),
+                ))
+            }
+        };
+        if !format.eq_ignore_ascii_case("csv") {
+            return Err(OnyxError::ArgumentError(format!(
+                "Unsupported export format '{}'. Only csv is supported",
+                format
+            )));
+        }
+        let query = self
+            .last_query
+            .as_ref()
+            .ok_or_else(|| OnyxError::ArgumentError("No query has been run yet".to_string()))?;
+        let (batches, schema) = load_result(&query.output_file)?;
+        let path = get_file_directories(Path::new(path))?;
+        export_csv(path, &schema, &batches)
+            .map_err(|e| OnyxError::IOError(format!("Failed to export result: {}", e)))?;
+        println!("{}", format!("Exported to {}", path.display()).success());
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.conversation_id = None;
+        self.history.clear();
+        self.last_query = None;
+        println!("{}", "Started a new conversation.".success());
+    }
+}
+
+/// Reads questions and slash commands from stdin until it ends or `/exit`,
+/// asking `first_question` first if given.
+pub async fn chat(args: ChatArgs, first_question: Option<String>) -> Result<(), OnyxError> {
+    let project_path = find_project_path()?;
+    let config = ConfigBuilder::new()
+        .with_project_path(&project_path)?
+        .build()
+        .await?;
+    let agent_file = PathBuf::from(&args.agent);
+    if !agent_file.is_file() {
+        return Err(OnyxError::ArgumentError(format!(
+            "Agent file not found: {}",
+            args.agent
+        )));
+    }
+    let agent_ref = std::fs::canonicalize(&agent_file)
+        .ok()
+        .zip(std::fs::canonicalize(&project_path).ok())
+        .and_then(|(agent, project)| {
+            agent
+                .strip_prefix(project)
+                .ok()
+                .map(|path| path.display().to_string())
+        })
+        .unwrap_or_else(|| args.agent.clone());
+
+    let connection = establish_connection().await;
+    Migrator::up(&connection, None)
+        .await
+        .map_err(|e| OnyxError::RuntimeError(format!("Failed to migrate the database: {}", e)))?;
+    let conversation = if args.new {
+        None
+    } else {
+        get_conversation_by_agent(&agent_ref).await
+    };
+    let history = match &conversation {
+        Some(conversation) => conversation_history(conversation.id).await,
+        None => vec![],
+    };
+
+    let mut session = ChatSession {
+        agent_file,
+        agent_ref,
+        config: Arc::new(config),
+        conversation_id: conversation.map(|conversation| conversation.id),
+        history,
+        last_query: None,
+    };
+    println!(
+        "{}",
+        format!(
+            "Chatting with {}{}. Type /help for commands, /exit to quit.",
+            session.agent_ref,
+            match session.history.len() {
+                0 => String::new(),
+                turns => format!(" ({} previous questions)", turns),
+            }
+        )
+        .text()
+    );
+
+    if let Some(question) = first_question.filter(|question| !question.trim().is_empty()) {
+        if let Err(e) = session.ask(question.trim()).await {
+            eprintln!("{}", e.to_string().error());
+        }
+    }
+
+    let stdin = std::io::stdin();
+    let interactive = stdin.is_terminal();
+    let mut lines = stdin.lock().lines();
+    loop {
+        if interactive {
+            print!("{}", "\n> ".primary());
+            let _ = std::io::stdout().flush();
+        }
+        let Some(line) = lines.next() else {
+            break;
+        };
+        let line = line.map_err(|e| OnyxError::IOError(format!("Failed to read input: {}", e)))?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let result = match line.strip_prefix('/') {
+            Some(command) => {
+                let mut words = command.split_whitespace();
+                let name = words.next().unwrap_or_default();
+                let args = words.collect::<Vec<_>>();
+                match name {
+                    "exit" | "quit" => break,
+                    "help" => {
+                        println!("{}", HELP);
+                        Ok(())
+                    }
+                    "sql" => {
+                        session.show_sql();
+                        Ok(())
+                    }
+                    "export" => session.export(&args),
+                    "reset" => {
+                        session.reset();
+                        Ok(())
+                    }
+                    _ => Err(OnyxError::ArgumentError(format!(
+                        "Unknown command /{}. Type /help for commands",
+                        name
+                    ))),
+                }
+            }
+            None => session.ask(line).await,
+        };
+        if let Err(e) = result {
+            eprintln!("{}", e.to_string().error());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,317 @@
+use arrow::array::{ArrayRef, BooleanArray, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use clap::Parser;
+use sqlparser::ast::Ident;
+use sqlparser::dialect::{dialect_from_str, GenericDialect};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::ai::utils::record_batches_to_table;
+use crate::config::ConfigManager;
+use crate::connector::{Connector, DatabaseInfo, TableInfo};
+use crate::errors::OnyxError;
+use crate::theme::StyledText;
+
+const DEFAULT_SAMPLE_LIMIT: usize = 10;
+
+#[derive(Parser, Debug)]
+pub struct DbArgs {
+    #[clap(subcommand)]
+    command: DbCommand,
+}
+
+#[derive(Parser, Debug)]
+enum DbCommand {
+    /// Connect to a database and report what fails, if anything
+    Ping(DbDatabaseArgs),
+    /// List the tables of a database
+    Tables(DbDatabaseArgs),
+    /// Show the columns of a table
+    Describe(DbTableArgs),
+    /// Show the first rows of a table
+    Sample(DbSampleArgs),
+}
+
+#[derive(Parser, Debug)]
+struct DbDatabaseArgs {
+    /// Name of the database in config.yml
+    database: String,
+}
+
+#[derive(Parser, Debug)]
+struct DbTableArgs {
+    /// Name of the database in config.yml
+    database: String,
+    /// Table name, optionally qualified with its schema
+    table: String,
+}
+
+#[derive(Parser, Debug)]
+struct DbSampleArgs {
+    /// Name of the database in config.yml
+    database: String,
+    /// Table name, optionally qualified with its schema
+    table: String,
+    /// Number of rows to show
+    #[clap(long, default_value_t = DEFAULT_SAMPLE_LIMIT)]
+    limit: usize,
+}
+
+pub async fn handle_db_command(args: DbArgs, config: &ConfigManager) -> Result<(), OnyxError> {
+    match args.command {
+        DbCommand::Ping(args) => ping(config, &args.database).await,
+        DbCommand::Tables(args) => {
+            let info = database_info(config, &args.database).await?;
+            print_tables(&info.tables)
+        }
+        DbCommand::Describe(args) => {
+            let info = database_info(config, &args.database).await?;
+            print_columns(find_table(&info, &args.table)?)
+        }
+        DbCommand::Sample(args) => sample(config, &args.database, &args.table, args.limit).await,
+    }
+}
+
+/// Resolves the database config, connects and runs a trivial query, printing
+/// each step so that it is clear which one fails.
+async fn ping(config: &ConfigManager, database_ref: &str) -> Result<(), OnyxError> {
+    let database = config.resolve_database(database_ref)?;
+    println!(
+        "Database {} ({}): {}",
+        database_ref.text(),
+        database.dialect(),
+        database.db_name()
+    );
+
+    let started = Instant::now();
+    // A cached result would hide a broken connection
+    let connector = Connector::from_database(database_ref, config)
+        .await
+        .map_err(|err| with_hint("Failed to connect", err))?
+        .without_cache();
+    connector
+        .run_query_and_load("SELECT 1")
+        .await
+        .map_err(|err| with_hint("Failed to run a query", err))?;
+    println!(
+        "{}",
+        format!(
+            "Connected and ran a query in {} ms",
+            started.elapsed().as_millis()
+        )
+        .success()
+    );
+
+    let info = connector
+        .database_info()
+        .await
+        .map_err(|err| with_hint("Failed to list tables", err))?;
+    println!("Found {} table(s)", info.tables.len());
+    Ok(())
+}
+
+/// Prints a likely cause of a connection error next to the step that failed.
+fn with_hint(step: &str, err: OnyxError) -> OnyxError {
+    eprintln!("{}", format!("{}.", step).error());
+    if let Some(hint) = diagnose(&err) {
+        eprintln!("{}", format!("Hint: {}", hint).warning());
+    }
+    err
+}
+
+fn diagnose(err: &OnyxError) -> Option<&'static str> {
+    let message = err.to_string().to_lowercase();
+    let contains_any = |patterns: &[&str]| patterns.iter().any(|p| message.contains(p));
+    if contains_any(&["failed to resolve secret"]) {
+        Some("the credential could not be read. Check that the environment variable, file or keyring entry it references exists.")
+    } else if contains_any(&[
+        "password authentication failed",
+        "access denied",
+        "authentication failed",
+        "invalid_grant",
+        "unauthenticated",
+    ]) {
+        Some("the server rejected the credentials. Check `user` and the password or key file.")
+    } else if contains_any(&[
+        "failed to lookup address",
+        "name or service not known",
+        "could not translate host name",
+        "nodename nor servname",
+    ]) {
+        Some("the host name could not be resolved. Check `host`.")
+    } else if contains_any(&[
+        "connection refused",
+        "no route to host",
+        "could not connect",
+    ]) {
+        Some("nothing accepted the connection. Check `host` and `port`, and that the server is running and reachable from this machine.")
+    } else if contains_any(&["certificate", "ssl", "tls"]) {
+        Some("the TLS handshake failed. Check `sslmode` and `sslrootcert`.")
+    } else if contains_any(&["timed out", "did not finish within", "timeout"]) {
+        Some("the server did not answer in time. Check network access and firewalls, or raise `timeout`.")
+    } else if contains_any(&["does not exist", "unknown database", "not found"]) {
+        Some("the database, dataset or schema does not exist. Check `database` or `dataset`.")
+    } else if contains_any(&["no such file", "unable to open", "cannot open"]) {
+        Some("the database file could not be opened. Check the path, which is relative to the project directory.")
+    } else {
+        None
+    }
+}
+
+async fn database_info(
+    config: &ConfigManager,
+    database_ref: &str,
+) -> Result<DatabaseInfo, OnyxError> {
+    Connector::from_database(database_ref, config)
+        .await?
+        .database_info()
+        .await
+}
+
+/// Finds `table` by its full name, or else by its trailing name parts, so
+/// that e.g. `orders` finds `main.orders`.
+fn find_table<'a>(info: &'a DatabaseInfo, table: &str) -> Result<&'a TableInfo, OnyxError> {
+    let wanted = table.trim_matches('\'').to_lowercase();
+    let name_of = |table: &TableInfo| table.name.trim_matches('\'').to_lowercase();
+    info.tables
+        .iter()
+        .find(|table| name_of(table) == wanted)
+        .or_else(|| {
+            info.tables.iter().find(|table| {
+                let name = name_of(table);
+                name.ends_with(&format!(".{}", wanted)) || name.ends_with(&format!("/{}", wanted))
+            })
+        })
+        .ok_or_else(|| {
+            OnyxError::ArgumentError(format!(
+                "Table '{}' not found in database '{}'. Run `onyx db tables` to list its tables.",
+                table, info.name
+            ))
+        })
+}
+
+async fn sample(
+    config: &ConfigManager,
+    database_ref: &str,
+    table: &str,
+    limit: usize,
+) -> Result<(), OnyxError> {
+    let connector = Connector::from_database(database_ref, config).await?;
+    let info = connector.database_info().await?;
+    // Only names reported by the database are queried, never the raw argument
+    let table = find_table(&info, table)?;
+    let name = match info.dialect.as_str() {
+        "bigquery" if !table.name.contains('.') => format!("{}.{}", info.name, table.name),
+        _ => table.name.to_string(),
+    };
+    let relation = quote_relation(&info.dialect, &name);
+    let (batches, schema) = connector
+        .run_query_and_load(&format!("SELECT * FROM {} LIMIT {}", relation, limit))
+        .await?;
+    print_batches(&batches, &schema)
+}
+
+/// Quotes each part of a table name with the identifier quote of the database
+/// dialect, so names holding upper case letters, spaces or keywords are kept
+/// as they are. DuckDB data files are already quoted as string literals.
+fn quote_relation(dialect: &str, name: &str) -> String {
+    if name.starts_with('\'') {
+        return name.to_string();
+    }
+    let default_quote = match dialect {
+        "bigquery" => '`',
+        _ => '"',
+    };
+    let parser_dialect = dialect_from_str(dialect).unwrap_or_else(|| Box::new(GenericDialect {}));
+    name.split('.')
+        .map(|part| {
+            let quote = parser_dialect
+                .identifier_quote_style(part)
+                .unwrap_or(default_quote);
+            Ident::with_quote(quote, part).to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn print_tables(tables: &[TableInfo]) -> Result<(), OnyxError> {
+    let names = tables.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+    let columns = tables
+        .iter()
+        .map(|t| t.columns.len() as i64)
+        .collect::<Vec<_>>();
+    print_table(vec![
+        ("table", DataType::Utf8, Arc::new(StringArray::from(names))),
+        (
+            "columns",
+            DataType::Int64,
+            Arc::new(Int64Array::from(columns)),
+        ),
+    ])
+}
+
+fn print_columns(table: &TableInfo) -> Result<(), OnyxError> {
+    let columns = &table.columns;
+    let names = columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>();
+    let data_types = columns
+        .iter()
+        .map(|c| c.data_type.as_str())
+        .collect::<Vec<_>>();
+    let nullable = columns.iter().map(|c| c.nullable).collect::<Vec<_>>();
+    let primary_keys = columns.iter().map(|c| c.primary_key).collect::<Vec<_>>();
+    let foreign_keys = columns
+        .iter()
+        .map(|c| c.foreign_key.as_deref())
+        .collect::<Vec<_>>();
+    let comments = columns
+        .iter()
+        .map(|c| c.comment.as_deref())
+        .collect::<Vec<_>>();
+    println!("{}", table.name.as_str().text());
+    print_table(vec![
+        ("column", DataType::Utf8, Arc::new(StringArray::from(names))),
+        (
+            "type",
+            DataType::Utf8,
+            Arc::new(StringArray::from(data_types)),
+        ),
+        (
+            "nullable",
+            DataType::Boolean,
+            Arc::new(BooleanArray::from(nullable)),
+        ),
+        (
+            "primary_key",
+            DataType::Boolean,
+            Arc::new(BooleanArray::from(primary_keys)),
+        ),
+        (
+            "foreign_key",
+            DataType::Utf8,
+            Arc::new(StringArray::from(foreign_keys)),
+        ),
+        (
+            "comment",
+            DataType::Utf8,
+            Arc::new(StringArray::from(comments)),
+        ),
+    ])
+}
+
+fn print_table(columns: Vec<(&str, DataType, ArrayRef)>) -> Result<(), OnyxError> {
+    let (fields, arrays): (Vec<Field>, Vec<ArrayRef>) = columns
+        .into_iter()
+        .map(|(name, data_type, array)| (Field::new(name, data_type, true), array))
+        .unzip();
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(schema.clone(), arrays)
+        .map_err(|e| OnyxError::RuntimeError(format!("Failed to build table: {}", e)))?;
+    print_batches(&[batch], &schema)
+}
+
+fn print_batches(batches: &[RecordBatch], schema: &SchemaRef) -> Result<(), OnyxError> {
+    let display = record_batches_to_table(batches, schema)
+        .map_err(|e| OnyxError::RuntimeError(format!("Failed to display results: {}", e)))?;
+    println!("{}", display);
+    Ok(())
+}
@@ -1,5 +1,7 @@
 use crate::cli::model::{BigQuery, Config, DatabaseType, DuckDB};
-use crate::config::model::Postgres;
+use crate::config::model::{
+    MySQL, Postgres, SQLite, ANTHROPIC_API_BASE, DEFAULT_ANTHROPIC_MAX_TOKENS,
+};
 use crate::utils::find_project_path;
 use include_dir::{include_dir, Dir};
 use std::io::{self, Write};
@@ -68,6 +70,8 @@ fn collect_databases() -> Result<Vec<Database>, InitError> {
         let database_type = choose_database_type()?;
         let database = Database {
             name,
+            pool_size: None,
+            timeout: None,
             database_type,
         };
 
@@ -86,6 +90,8 @@ fn choose_database_type() -> Result<DatabaseType, InitError> {
     println!("\t\t1. DuckDB");
     println!("\t\t2. BigQuery");
     println!("\t\t3. Postgres");
+    println!("\t\t4. MySQL");
+    println!("\t\t5. SQLite");
 
     loop {
         let choice = prompt_with_default("Type (1 or 2 or ..<number>..)", "1", None)?;
@@ -97,6 +103,8 @@ fn choose_database_type() -> Result<DatabaseType, InitError> {
                         ".db/",
                         Some("Enter the directory where your files are located."),
                     )?,
+                    path: None,
+                    init_sql: vec![],
                 }))
             }
             "2" => {
@@ -123,12 +131,19 @@ fn choose_database_type() -> Result<DatabaseType, InitError> {
                     )?);
 
                     return Ok(DatabaseType::Postgres(Postgres {
+                        connection_string: None,
                         connection_string_file: Some(postgres_connection_string_file),
                         host: None,
                         port: None,
                         user: None,
+                        password: None,
                         password_file: None,
                         database: None,
+                        schemas: vec!["public".to_string()],
+                        search_path: None,
+                        sslmode: None,
+                        sslrootcert: None,
+                        application_name: None,
                     }));
                 } else {
                     let host = prompt_with_default("Host", "localhost", None)?;
@@ -150,15 +165,85 @@ fn choose_database_type() -> Result<DatabaseType, InitError> {
                     }
 
                     return Ok(DatabaseType::Postgres(Postgres {
+                        connection_string: None,
                         connection_string_file: None,
                         host: Some(host),
                         port: Some(port),
                         user: Some(user),
+                        password: None,
                         password_file: Some(password_file),
                         database: Some(database),
+                        schemas: vec!["public".to_string()],
+                        search_path: None,
+                        sslmode: None,
+                        sslrootcert: None,
+                        application_name: None,
                     }));
                 }
             }
+            "4" => {
+                let use_connection_string_file = prompt_with_default(
+                    "Use connection string file? (y/N)",
+                    "N",
+                    Some("If 'N', you will be prompted for individual connection parameters."),
+                )?;
+                if use_connection_string_file.to_lowercase() == "y" {
+                    let mysql_connection_string_file = PathBuf::from(prompt_with_default(
+                        "Connection string file",
+                        "mysql_connection_string.txt",
+                        None,
+                    )?);
+
+                    return Ok(DatabaseType::MySQL(MySQL {
+                        connection_string: None,
+                        connection_string_file: Some(mysql_connection_string_file),
+                        host: None,
+                        port: None,
+                        user: None,
+                        password: None,
+                        password_file: None,
+                        database: None,
+                    }));
+                } else {
+                    let host = prompt_with_default("Host", "localhost", None)?;
+                    let port = prompt_with_default("Port", "3306", None)?;
+                    let user = prompt_with_default("User", "root", None)?;
+                    let password_file =
+                        PathBuf::from(prompt_with_default("Password file", "password.txt", None)?);
+                    let database = prompt_with_default("Database", "mysql", None)?;
+
+                    if host.is_empty()
+                        || port.is_empty()
+                        || user.is_empty()
+                        || password_file.to_str().unwrap().is_empty()
+                        || database.is_empty()
+                    {
+                        return Err(InitError::ExtractionError(
+                            REQUIRED_FIELDS_ERROR.to_string(),
+                        ));
+                    }
+
+                    return Ok(DatabaseType::MySQL(MySQL {
+                        connection_string: None,
+                        connection_string_file: None,
+                        host: Some(host),
+                        port: Some(port),
+                        user: Some(user),
+                        password: None,
+                        password_file: Some(password_file),
+                        database: Some(database),
+                    }));
+                }
+            }
+            "5" => {
+                return Ok(DatabaseType::SQLite(SQLite {
+                    path: prompt_with_default(
+                        "Database file",
+                        "database.sqlite",
+                        Some("Enter the path to your SQLite database file."),
+                    )?,
+                }))
+            }
             _ => println!("  {}", INVALID_CHOICE),
         }
     }
@@ -171,8 +256,9 @@ fn collect_models() -> Result<Vec<Model>, InitError> {
         println!("  Select model type:");
         println!("  1. OpenAI");
         println!("  2. Ollama");
+        println!("  3. Anthropic");
 
-        let model_type = prompt_with_default("Type (1 or 2)", "1", None)?;
+        let model_type = prompt_with_default("Type (1, 2 or 3)", "1", None)?;
 
         let model = match model_type.as_str() {
             "1" => {
@@ -193,10 +279,12 @@ fn collect_models() -> Result<Vec<Model>, InitError> {
                 Model::OpenAI {
                     name: prompt_with_default("Name", "openai-4o", None)?,
                     model_ref: prompt_with_default("Model reference", "gpt-4o", None)?,
-                    key_var: prompt_with_default("Key variable", "OPENAI_API_KEY", None)?,
+                    api_key: None,
+                    key_var: Some(prompt_with_default("Key variable", "OPENAI_API_KEY", None)?),
                     api_url: Some(api_url),
                     azure_deployment_id,
                     azure_api_version,
+                    pricing: None,
                 }
             }
             "2" => Model::Ollama {
@@ -204,13 +292,32 @@ fn collect_models() -> Result<Vec<Model>, InitError> {
                 model_ref: prompt_with_default("Model reference", "llama3.2:latest", None)?,
                 api_key: prompt_with_default("API Key", "secret", None)?,
                 api_url: prompt_with_default("API URL", "http://localhost:11434/v1", None)?,
+                pricing: None,
+            },
+            "3" => Model::Anthropic {
+                name: prompt_with_default("Name", "claude-sonnet", None)?,
+                model_ref: prompt_with_default(
+                    "Model reference",
+                    "claude-3-5-sonnet-latest",
+                    None,
+                )?,
+                api_key: None,
+                key_var: Some(prompt_with_default(
+                    "Key variable",
+                    "ANTHROPIC_API_KEY",
+                    None,
+                )?),
+                api_url: Some(ANTHROPIC_API_BASE.to_string()),
+                max_tokens: DEFAULT_ANTHROPIC_MAX_TOKENS,
+                pricing: None,
             },
             _ => {
                 println!("Invalid model type selected. Using OpenAI as default.");
                 Model::OpenAI {
                     name: prompt_with_default("Name", "openai-4o", None)?,
                     model_ref: prompt_with_default("Model reference", "gpt-4o", None)?,
-                    key_var: prompt_with_default("Key variable", "OPENAI_API_KEY", None)?,
+                    api_key: None,
+                    key_var: Some(prompt_with_default("Key variable", "OPENAI_API_KEY", None)?),
                     api_url: Some(prompt_with_default(
                         "API URL",
                         "https://api.openai.com/v1",
@@ -218,6 +325,7 @@ fn collect_models() -> Result<Vec<Model>, InitError> {
                     )?),
                     azure_deployment_id: None,
                     azure_api_version: None,
+                    pricing: None,
                 }
             }
         };
@@ -340,6 +448,8 @@ fn create_config_file(config_path: &Path) -> Result<(), InitError> {
             database: Some(default_database),
             agent: Some("sql-generator.agent.yml".to_string()),
         }),
+        results: None,
+        query_cache: None,
         project_path: PathBuf::new(),
     };
 
@@ -353,13 +463,24 @@ fn create_config_file(config_path: &Path) -> Result<(), InitError> {
     fs::write(config_path, content)?;
 
     for database in &databases {
-        if let DatabaseType::Postgres(postgres) = &database.database_type {
-            if let Some(password_file) = &postgres.password_file {
-                sensitive_files.push(password_file.as_path());
+        match &database.database_type {
+            DatabaseType::Postgres(postgres) => {
+                if let Some(password_file) = &postgres.password_file {
+                    sensitive_files.push(password_file.as_path());
+                }
+                if let Some(connection_string_file) = &postgres.connection_string_file {
+                    sensitive_files.push(connection_string_file.as_path());
+                }
             }
-            if let Some(connection_string_file) = &postgres.connection_string_file {
-                sensitive_files.push(connection_string_file.as_path());
+            DatabaseType::MySQL(mysql) => {
+                if let Some(password_file) = &mysql.password_file {
+                    sensitive_files.push(password_file.as_path());
+                }
+                if let Some(connection_string_file) = &mysql.connection_string_file {
+                    sensitive_files.push(connection_string_file.as_path());
+                }
             }
+            _ => {}
         }
     }
     ignore_sensitive_files(&config.project_path, &sensitive_files)?;
@@ -1,3 +1,5 @@
+mod chat;
+mod db;
 mod init;
 
 use crate::ai::agent::AgentResult;
@@ -27,16 +29,20 @@ use pyo3::PyAny;
 use pyo3::PyErr;
 use pyo3::Python;
 use std::backtrace;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::path::PathBuf;
 use std::process::exit;
 use std::process::Command;
 
+use chat::{chat, ChatArgs};
+use db::{handle_db_command, DbArgs};
 use init::init;
 
 use crate::api::server;
 use crate::connector::Connector;
+use crate::result_store::load_result;
+use crate::sql_params::ParamValue;
 use crate::theme::*;
 use crate::{build, vector_search};
 use tower_serve_static::ServeDir;
@@ -74,7 +80,7 @@ fn parse_variable(env: &str) -> Result<Variable, OnyxError> {
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-    /// The question to ask or command to execute
+    /// The question to ask first when chatting with --agent
     #[clap(default_value = "")]
     input: String,
 
@@ -112,6 +118,12 @@ enum SubCommand {
     TestTheme,
     /// Generate JSON schemas for config files
     GenConfigSchema(GenConfigSchemaArgs),
+    /// Manage stored query results
+    Results(ResultsArgs),
+    /// Inspect the databases configured in config.yml
+    Db(DbArgs),
+    /// Chat with an agent, with follow-up questions and slash commands
+    Chat(ChatArgs),
 }
 
 #[derive(Parser, Debug)]
@@ -124,6 +136,14 @@ pub struct RunArgs {
     #[clap(long, short = 'v', value_parser=ValueParser::new(parse_variable), num_args = 1..)]
     variables: Vec<(String, String)>,
 
+    /// Query parameters bound to `:name` placeholders, as NAME=VALUE or NAME:TYPE=VALUE
+    #[clap(long, short = 'p', value_parser=ValueParser::new(parse_variable), num_args = 1..)]
+    params: Vec<(String, String)>,
+
+    /// Print the result of every statement of a SQL file, not only the last one
+    #[clap(long, default_value_t = false)]
+    all_results: bool,
+
     question: Option<String>,
 }
 
@@ -138,6 +158,8 @@ pub struct TestArgs {
 pub struct RunOptions {
     database: Option<String>,
     variables: Option<Vec<(String, String)>>,
+    params: Option<Vec<(String, String)>>,
+    all_results: Option<bool>,
     question: Option<String>,
 }
 
@@ -151,6 +173,14 @@ impl<'py> FromPyObject<'py> for RunOptions {
             .get_item("variables")
             .map(|v| v.extract::<Option<Vec<(String, String)>>>().unwrap_or(None))
             .unwrap_or(None);
+        let params = ob
+            .get_item("params")
+            .map(|v| v.extract::<Option<Vec<(String, String)>>>().unwrap_or(None))
+            .unwrap_or(None);
+        let all_results = ob
+            .get_item("all_results")
+            .map(|v| v.extract::<Option<bool>>().unwrap_or(None))
+            .unwrap_or(None);
         let question = ob
             .get_item("question")
             .map(|v| v.extract::<Option<String>>().unwrap_or(None))
@@ -158,6 +188,8 @@ impl<'py> FromPyObject<'py> for RunOptions {
         Ok(RunOptions {
             database,
             variables,
+            params,
+            all_results,
             question,
         })
     }
@@ -170,12 +202,16 @@ impl RunArgs {
                 file,
                 database: options.database,
                 variables: options.variables.unwrap_or(vec![]),
+                params: options.params.unwrap_or(vec![]),
+                all_results: options.all_results.unwrap_or(false),
                 question: options.question,
             },
             None => Self {
                 file,
                 database: None,
                 variables: vec![],
+                params: vec![],
+                all_results: false,
                 question: None,
             },
         }
@@ -193,6 +229,25 @@ struct GenConfigSchemaArgs {
     check: bool,
 }
 
+#[derive(Parser, Debug)]
+struct ResultsArgs {
+    #[clap(subcommand)]
+    command: ResultsCommand,
+}
+
+#[derive(Parser, Debug)]
+enum ResultsCommand {
+    /// Delete query results older than the configured TTL
+    Gc(ResultsGcArgs),
+}
+
+#[derive(Parser, Debug)]
+struct ResultsGcArgs {
+    /// Delete every stored result regardless of age
+    #[clap(long)]
+    all: bool,
+}
+
 async fn handle_workflow_file(workflow_name: &PathBuf) -> Result<WorkflowResult, OnyxError> {
     run_workflow(workflow_name).await
 }
@@ -311,6 +366,35 @@ pub async fn cli() -> Result<(), Box<dyn Error>> {
                 }
             }
         }
+        Some(SubCommand::Results(results_args)) => match results_args.command {
+            ResultsCommand::Gc(gc_args) => {
+                let config = ConfigBuilder::new()
+                    .with_project_path(&find_project_path()?)?
+                    .build()
+                    .await?;
+                let result_store = config.result_store().await?;
+                let removed = result_store.gc(gc_args.all)?;
+                println!(
+                    "{}",
+                    format!(
+                        "Removed {} result file(s) from {}",
+                        removed,
+                        result_store.dir().display()
+                    )
+                    .success()
+                );
+            }
+        },
+        Some(SubCommand::Db(db_args)) => {
+            let config = ConfigBuilder::new()
+                .with_project_path(&find_project_path()?)?
+                .build()
+                .await?;
+            handle_db_command(db_args, &config).await?;
+        }
+        Some(SubCommand::Chat(chat_args)) => {
+            chat(chat_args, None).await?;
+        }
         Some(SubCommand::Serve) => {
             start_server_and_web_app().await;
         }
@@ -331,9 +415,12 @@ pub async fn cli() -> Result<(), Box<dyn Error>> {
             println!("{}", "text".text());
         }
 
-        None => {
-            Args::command().print_help().unwrap();
-        }
+        None => match args.agent {
+            Some(agent) => chat(ChatArgs::new(agent), Some(args.input)).await?,
+            None => {
+                Args::command().print_help().unwrap();
+            }
+        },
     }
 
     Ok(())
@@ -362,6 +449,8 @@ async fn handle_sql_file(
     database: Option<String>,
     config: &ConfigManager,
     variables: &[(String, String)],
+    params: &[(String, String)],
+    all_results: bool,
 ) -> Result<String, OnyxError> {
     let database = database.ok_or_else(|| OnyxError::ArgumentError("Database is required for running SQL file. Please provide the database using --database or set a default database in config.yml".to_string()))?;
     let content = std::fs::read_to_string(file_path)
@@ -386,18 +475,34 @@ async fn handle_sql_file(
             .map_err(|e| OnyxError::RuntimeError(format!("Failed to render SQL template: {}", e)))?
     }
 
+    let params = params
+        .iter()
+        .map(|(key, value)| ParamValue::from_arg(key, value))
+        .collect::<Result<HashMap<_, _>, _>>()?;
+
     // Print colored SQL and execute query
     print_colored_sql(&query);
-    let (datasets, schema) = Connector::from_database(&database, config)
+    let connector = Connector::from_database(&database, config)
         .await?
-        .run_query_and_load(&query)
-        .await?;
-    let batches_display = record_batches_to_table(&datasets, &schema)
-        .map_err(|e| OnyxError::RuntimeError(format!("Failed to display query results: {}", e)))?;
-    println!("\n\x1b[1;32mResults:\x1b[0m");
-    println!("{}", batches_display);
-
-    Ok(batches_display.to_string())
+        .with_params(params);
+    let statements = connector.split_statements(&query);
+    let file_paths = if statements.len() > 1 {
+        connector.run_statements(&statements, all_results).await?
+    } else {
+        vec![connector.run_query(&query).await?]
+    };
+    let mut outputs = vec![];
+    for file_path in file_paths {
+        let (datasets, schema) = load_result(&file_path)?;
+        let batches_display = record_batches_to_table(&datasets, &schema).map_err(|e| {
+            OnyxError::RuntimeError(format!("Failed to display query results: {}", e))
+        })?;
+        println!("\n\x1b[1;32mResults:\x1b[0m");
+        println!("{}", batches_display);
+        outputs.push(batches_display.to_string());
+    }
+
+    Ok(outputs.join("\n\n"))
 }
 
 pub enum RunResult {
@@ -471,8 +576,15 @@ pub async fn handle_run_command(run_args: RunArgs) -> Result<RunResult, OnyxErro
                     "Database is required for running SQL file. Please provide the database using --database or set a default database in config.yml".into(),
                 ));
             }
-            let sql_result =
-                handle_sql_file(&file_path, database, &config, &run_args.variables).await?;
+            let sql_result = handle_sql_file(
+                &file_path,
+                database,
+                &config,
+                &run_args.variables,
+                &run_args.params,
+                run_args.all_results,
+            )
+            .await?;
             Ok(RunResult::Sql(sql_result))
         }
         _ => Err(OnyxError::ArgumentError(
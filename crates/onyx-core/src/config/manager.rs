@@ -1,9 +1,18 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use tokio::sync::{Mutex, OnceCell};
 
-use crate::errors::OnyxError;
+use crate::{
+    connector::Connector, errors::OnyxError, query_cache::QueryCache, result_store::ResultStore,
+};
 
 use super::{
     model::{AgentConfig, Config, Database, Model, Workflow},
+    secrets::SecretResolver,
     storage::{ConfigSource, ConfigStorage},
 };
 
@@ -11,11 +20,27 @@ use super::{
 pub struct ConfigManager {
     storage: ConfigSource,
     config: Config,
+    connectors: Mutex<HashMap<String, Connector>>,
+    result_store: OnceCell<ResultStore>,
+    /// Set once expired results have been removed
+    results_collected: OnceCell<()>,
 }
 
 impl ConfigManager {
     pub(super) fn new(storage: ConfigSource, config: Config) -> Self {
-        Self { storage, config }
+        Self {
+            storage,
+            config,
+            connectors: Mutex::new(HashMap::new()),
+            result_store: OnceCell::new(),
+            results_collected: OnceCell::new(),
+        }
+    }
+
+    /// Connectors opened against this config, keyed by database name, so that
+    /// tasks and tool calls share connections instead of reconnecting.
+    pub(crate) fn connectors(&self) -> &Mutex<HashMap<String, Connector>> {
+        &self.connectors
     }
 
     pub fn resolve_model(&self, model_name: &str) -> Result<&Model, OnyxError> {
@@ -26,6 +51,7 @@ impl ConfigManager {
             .find(|m| {
                 match match m {
                     Model::OpenAI { name, .. } => name,
+                    Model::Anthropic { name, .. } => name,
                     Model::Ollama { name, .. } => name,
                 } {
                     name => name == model_name,
@@ -40,6 +66,7 @@ impl ConfigManager {
     pub fn default_model(&self) -> Option<&String> {
         self.config.models.first().map(|m| match m {
             Model::OpenAI { name, .. } => name,
+            Model::Anthropic { name, .. } => name,
             Model::Ollama { name, .. } => name,
         })
     }
@@ -63,10 +90,69 @@ impl ConfigManager {
         self.config.defaults.as_ref().map(|d| d.database.as_ref())?
     }
 
+    pub async fn result_store(&self) -> Result<ResultStore, OnyxError> {
+        let store = self
+            .result_store
+            .get_or_try_init(|| async {
+                let store = match &self.config.results {
+                    Some(results) => ResultStore::new(
+                        match &results.dir {
+                            Some(dir) => PathBuf::from(self.resolve_file(dir).await?),
+                            None => ResultStore::default_dir(),
+                        },
+                        Duration::from_secs(results.ttl_seconds),
+                    ),
+                    None => ResultStore::default(),
+                };
+                Ok::<ResultStore, OnyxError>(store)
+            })
+            .await?;
+        Ok(store.clone())
+    }
+
+    /// Removes expired result files the first time it is called, on the
+    /// blocking pool. Results are written as databases get queried, so this
+    /// runs when the first one connects rather than only on `onyx results gc`.
+    pub(crate) async fn remove_expired_results(&self) -> Result<(), OnyxError> {
+        let result_store = self.result_store().await?;
+        self.results_collected
+            .get_or_init(|| async move {
+                match tokio::task::spawn_blocking(move || result_store.gc(false)).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => log::debug!("Failed to remove expired result files: {}", e),
+                    Err(e) => log::debug!("Failed to remove expired result files: {}", e),
+                }
+            })
+            .await;
+        Ok(())
+    }
+
+    /// Returns the query cache, or `None` when `query_cache` is not configured.
+    pub async fn query_cache(&self) -> Result<Option<QueryCache>, OnyxError> {
+        let cache = match &self.config.query_cache {
+            Some(query_cache) => Some(QueryCache::new(
+                match &query_cache.dir {
+                    Some(dir) => PathBuf::from(self.resolve_file(dir).await?),
+                    None => QueryCache::default_dir(),
+                },
+                Duration::from_secs(query_cache.ttl_seconds),
+                query_cache.max_size_mb * 1024 * 1024,
+            )),
+            None => None,
+        };
+        Ok(cache)
+    }
+
     pub async fn resolve_file<P: AsRef<Path>>(&self, file_ref: P) -> Result<String, OnyxError> {
         self.storage.fs_link(file_ref).await
     }
 
+    /// Resolves a secret reference such as `env:NAME` or `file:path`, see
+    /// [`SecretResolver`].
+    pub async fn resolve_secret(&self, reference: &str) -> Result<String, OnyxError> {
+        SecretResolver::new(self.resolve_file("").await?).resolve(reference)
+    }
+
     pub async fn resolve_glob(&self, paths: &Vec<String>) -> Result<Vec<String>, OnyxError> {
         let mut expanded_paths = Vec::new();
         for path in paths {
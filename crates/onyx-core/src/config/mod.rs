@@ -5,6 +5,7 @@ pub mod validate;
 use garde::Validate;
 mod builder;
 mod manager;
+mod secrets;
 mod storage;
 
 use anyhow;
@@ -20,6 +21,7 @@ use crate::{errors::OnyxError, utils::find_project_path};
 
 pub use builder::ConfigBuilder;
 pub use manager::ConfigManager;
+pub use secrets::{SecretRef, SecretResolver};
 
 // These are settings stored as strings derived from the config.yml file's defaults section
 #[derive(Debug, Deserialize)]
@@ -151,6 +153,7 @@ impl Config {
     pub fn default_model(&self) -> Option<String> {
         self.models.first().map(|m| match m {
             Model::OpenAI { name, .. } => name.clone(),
+            Model::Anthropic { name, .. } => name.clone(),
             Model::Ollama { name, .. } => name.clone(),
         })
     }
@@ -161,6 +164,7 @@ impl Config {
             .find(|m| {
                 match match m {
                     Model::OpenAI { name, .. } => name,
+                    Model::Anthropic { name, .. } => name,
                     Model::Ollama { name, .. } => name,
                 } {
                     name => name == model_name,
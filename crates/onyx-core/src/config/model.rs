@@ -7,12 +7,20 @@ use std::path::PathBuf;
 
 use crate::config::validate::validate_file_path;
 use crate::config::validate::{
-    validate_agent_exists, validate_database_exists, validate_env_var, ValidationContext,
+    validate_agent_exists, validate_database_exists, validate_optional_env_var, ValidationContext,
 };
+use crate::query_cache::{DEFAULT_QUERY_CACHE_MAX_SIZE_MB, DEFAULT_QUERY_CACHE_TTL_SECONDS};
+use crate::result_store::DEFAULT_RESULT_TTL_SECONDS;
 use schemars::JsonSchema;
 
 use super::validate::validate_task;
 
+pub const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com/v1";
+pub const DEFAULT_ANTHROPIC_MAX_TOKENS: u32 = 4096;
+pub const DEFAULT_HISTORY_WINDOW: usize = 10;
+pub const DEFAULT_MAX_TOOL_ROUNDS: usize = 5;
+pub const DEFAULT_MAX_CONCURRENT_TOOL_CALLS: usize = 4;
+
 #[derive(Serialize, Deserialize, Validate, Debug, Clone, JsonSchema)]
 #[garde(context(ValidationContext))]
 pub struct Config {
@@ -22,6 +30,10 @@ pub struct Config {
     pub models: Vec<Model>,
     #[garde(dive)]
     pub databases: Vec<Database>,
+    #[garde(dive)]
+    pub results: Option<ResultStoreConfig>,
+    #[garde(dive)]
+    pub query_cache: Option<QueryCacheConfig>,
 
     #[serde(skip)]
     #[garde(skip)]
@@ -29,6 +41,29 @@ pub struct Config {
     pub project_path: PathBuf,
 }
 
+#[derive(Serialize, Deserialize, Validate, Debug, Clone, JsonSchema)]
+#[garde(context(ValidationContext))]
+pub struct ResultStoreConfig {
+    #[garde(length(min = 1))]
+    pub dir: Option<String>,
+    #[serde(default = "default_result_ttl_seconds")]
+    #[garde(skip)]
+    pub ttl_seconds: u64,
+}
+
+#[derive(Serialize, Deserialize, Validate, Debug, Clone, JsonSchema)]
+#[garde(context(ValidationContext))]
+pub struct QueryCacheConfig {
+    #[garde(length(min = 1))]
+    pub dir: Option<String>,
+    #[serde(default = "default_query_cache_ttl_seconds")]
+    #[garde(skip)]
+    pub ttl_seconds: u64,
+    #[serde(default = "default_query_cache_max_size_mb")]
+    #[garde(range(min = 1))]
+    pub max_size_mb: u64,
+}
+
 #[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct SemanticModels {
     pub table: String,
@@ -75,6 +110,23 @@ pub struct AgentConfig {
     pub anonymize: Option<AnonymizerConfig>,
     #[serde(default)]
     pub tests: Vec<Eval>,
+    /// Number of previous turns of a conversation sent along with a question
+    #[serde(default = "default_history_window")]
+    pub history_window: usize,
+    /// Let the model ask for several tool calls at once and run them concurrently
+    #[serde(default)]
+    pub parallel_tool_calls: bool,
+    /// Number of tool calls run at the same time with `parallel_tool_calls`
+    #[serde(default = "default_max_concurrent_tool_calls")]
+    pub max_concurrent_tool_calls: usize,
+    /// Number of tool call rounds before the agent must answer
+    #[serde(default = "default_max_tool_rounds")]
+    pub max_tool_rounds: usize,
+    /// Tokens the agent may use to answer a question, across all requests and
+    /// the embeddings of retrieval tools
+    pub token_budget: Option<u64>,
+    /// Seconds the agent may spend calling tools before it must answer
+    pub timeout_seconds: Option<u64>,
 }
 
 #[derive(Debug, Validate, Deserialize, Serialize, Clone, JsonSchema)]
@@ -157,11 +209,41 @@ pub struct DuckDB {
     #[serde(alias = "dataset", rename = "dataset")]
     #[garde(length(min = 1))]
     pub file_search_path: String,
+    #[garde(length(min = 1))]
+    pub path: Option<String>,
+    #[serde(default)]
+    #[garde(skip)]
+    pub init_sql: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum PostgresSSLMode {
+    Disable,
+    Prefer,
+    Require,
+    VerifyCa,
+    VerifyFull,
+}
+
+impl fmt::Display for PostgresSSLMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PostgresSSLMode::Disable => write!(f, "disable"),
+            PostgresSSLMode::Prefer => write!(f, "prefer"),
+            PostgresSSLMode::Require => write!(f, "require"),
+            PostgresSSLMode::VerifyCa => write!(f, "verify-ca"),
+            PostgresSSLMode::VerifyFull => write!(f, "verify-full"),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Validate, JsonSchema)]
 #[garde(context(ValidationContext))]
 pub struct Postgres {
+    /// Secret reference to the connection string, e.g. `env:DATABASE_URL`
+    #[garde(length(min = 1))]
+    pub connection_string: Option<String>,
     #[garde(custom(validate_file_path))]
     pub connection_string_file: Option<PathBuf>,
     #[garde(length(min = 1))]
@@ -170,6 +252,53 @@ pub struct Postgres {
     pub port: Option<String>,
     #[garde(length(min = 1))]
     pub user: Option<String>,
+    /// Secret reference to the password, e.g. `keyring:analytics`
+    #[garde(length(min = 1))]
+    pub password: Option<String>,
+    #[garde(custom(validate_file_path))]
+    pub password_file: Option<PathBuf>,
+    #[garde(length(min = 1))]
+    pub database: Option<String>,
+    /// Schemas whose tables are described to agents
+    #[serde(default = "default_postgres_schemas")]
+    #[garde(length(min = 1), inner(length(min = 1)))]
+    pub schemas: Vec<String>,
+    /// Schemas unqualified table names resolve against, in order
+    #[garde(inner(length(min = 1)))]
+    pub search_path: Option<Vec<String>>,
+    #[garde(skip)]
+    pub sslmode: Option<PostgresSSLMode>,
+    /// CA certificate used to verify the server with `verify-ca` or `verify-full`
+    #[garde(custom(validate_file_path))]
+    pub sslrootcert: Option<PathBuf>,
+    #[garde(length(min = 1))]
+    pub application_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Validate, Clone, JsonSchema)]
+#[garde(context(ValidationContext))]
+pub struct SQLite {
+    #[garde(length(min = 1))]
+    pub path: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate, JsonSchema)]
+#[garde(context(ValidationContext))]
+pub struct MySQL {
+    /// Secret reference to the connection string, e.g. `env:DATABASE_URL`
+    #[garde(length(min = 1))]
+    pub connection_string: Option<String>,
+    #[garde(custom(validate_file_path))]
+    pub connection_string_file: Option<PathBuf>,
+    #[garde(length(min = 1))]
+    pub host: Option<String>,
+    #[garde(length(min = 1))]
+    pub port: Option<String>,
+    #[garde(length(min = 1))]
+    pub user: Option<String>,
+    /// Secret reference to the password, e.g. `keyring:analytics`
+    #[garde(length(min = 1))]
+    pub password: Option<String>,
     #[garde(custom(validate_file_path))]
     pub password_file: Option<PathBuf>,
     #[garde(length(min = 1))]
@@ -186,6 +315,10 @@ pub enum DatabaseType {
     DuckDB(#[garde(dive)] DuckDB),
     #[serde(rename = "postgres")]
     Postgres(#[garde(dive)] Postgres),
+    #[serde(rename = "mysql")]
+    MySQL(#[garde(dive)] MySQL),
+    #[serde(rename = "sqlite")]
+    SQLite(#[garde(dive)] SQLite),
 }
 
 impl fmt::Display for DatabaseType {
@@ -194,6 +327,8 @@ impl fmt::Display for DatabaseType {
             DatabaseType::Bigquery(_) => write!(f, "bigquery"),
             DatabaseType::DuckDB(_) => write!(f, "duckdb"),
             DatabaseType::Postgres(_) => write!(f, "postgres"),
+            DatabaseType::MySQL(_) => write!(f, "mysql"),
+            DatabaseType::SQLite(_) => write!(f, "sqlite"),
         }
     }
 }
@@ -204,40 +339,48 @@ pub struct Database {
     #[garde(length(min = 1))]
     pub name: String,
 
+    #[garde(range(min = 1))]
+    pub pool_size: Option<usize>,
+
+    #[garde(range(min = 1))]
+    pub timeout: Option<u64>,
+
     #[serde(flatten)]
     #[garde(dive)]
     pub database_type: DatabaseType,
 }
 
 impl Database {
+    /// Human readable name of the database. Never includes credentials, so it
+    /// is safe to log or show to a model.
     pub fn db_name(&self) -> String {
         match &self.database_type {
             DatabaseType::Bigquery(bq) => bq.dataset.to_owned(),
             DatabaseType::DuckDB(ddb) => ddb.file_search_path.to_owned(),
-            DatabaseType::Postgres(pg) => {
-                let conn_string = if let Some(file_path) = &pg.connection_string_file {
-                    std::fs::read_to_string(file_path)
-                        .unwrap_or_default()
-                        .trim()
-                        .to_string()
-                } else {
-                    let password = std::fs::read_to_string(
-                        pg.password_file.as_ref().unwrap_or(&PathBuf::new()),
-                    )
-                    .unwrap_or_default()
-                    .trim()
-                    .to_string();
-                    format!(
-                        "{}:{}@{}:{}/{}",
-                        pg.user.clone().unwrap_or_default(),
-                        password,
-                        pg.host.clone().unwrap_or_default(),
-                        pg.port.clone().unwrap_or_default(),
-                        pg.database.clone().unwrap_or_default()
-                    )
-                };
-                conn_string
-            }
+            DatabaseType::SQLite(sqlite) => sqlite.path.to_owned(),
+            DatabaseType::Postgres(Postgres {
+                host,
+                port,
+                user,
+                database,
+                ..
+            })
+            | DatabaseType::MySQL(MySQL {
+                host,
+                port,
+                user,
+                database,
+                ..
+            }) => match host {
+                Some(host) => format!(
+                    "{}@{}:{}/{}",
+                    user.clone().unwrap_or_default(),
+                    host,
+                    port.clone().unwrap_or_default(),
+                    database.clone().unwrap_or_default()
+                ),
+                None => database.clone().unwrap_or_else(|| self.name.to_string()),
+            },
         }
     }
 
@@ -246,6 +389,8 @@ impl Database {
             DatabaseType::Bigquery(_) => "bigquery".to_string(),
             DatabaseType::DuckDB(_) => "duckdb".to_string(),
             DatabaseType::Postgres(_) => "postgres".to_string(),
+            DatabaseType::MySQL(_) => "mysql".to_string(),
+            DatabaseType::SQLite(_) => "sqlite".to_string(),
         }
     }
 }
@@ -260,8 +405,11 @@ pub enum Model {
         name: String,
         #[garde(length(min = 1))]
         model_ref: String,
-        #[garde(custom(validate_env_var))]
-        key_var: String,
+        /// Secret reference to the API key, takes precedence over `key_var`
+        #[garde(skip)]
+        api_key: Option<String>,
+        #[garde(custom(validate_optional_env_var))]
+        key_var: Option<String>,
         #[serde(default = "default_openai_api_url")]
         #[garde(skip)]
         api_url: Option<String>,
@@ -269,6 +417,29 @@ pub enum Model {
         azure_deployment_id: Option<String>,
         #[garde(skip)]
         azure_api_version: Option<String>,
+        #[garde(dive)]
+        pricing: Option<ModelPricing>,
+    },
+    #[serde(rename = "anthropic")]
+    Anthropic {
+        #[garde(length(min = 1))]
+        name: String,
+        #[garde(length(min = 1))]
+        model_ref: String,
+        /// Secret reference to the API key, takes precedence over `key_var`
+        #[garde(skip)]
+        api_key: Option<String>,
+        #[garde(custom(validate_optional_env_var))]
+        key_var: Option<String>,
+        #[serde(default = "default_anthropic_api_url")]
+        #[garde(skip)]
+        api_url: Option<String>,
+        /// Maximum number of tokens in a response
+        #[serde(default = "default_anthropic_max_tokens")]
+        #[garde(range(min = 1))]
+        max_tokens: u32,
+        #[garde(dive)]
+        pricing: Option<ModelPricing>,
     },
     #[serde(rename = "ollama")]
     Ollama {
@@ -276,12 +447,58 @@ pub enum Model {
         name: String,
         #[garde(length(min = 1))]
         model_ref: String,
+        /// API key or a secret reference to it
         #[garde(length(min = 1))]
         api_key: String,
         #[garde(length(min = 1))]
         api_url: String,
+        #[garde(dive)]
+        pricing: Option<ModelPricing>,
     },
 }
+
+/// Prices of a model in USD per million tokens, used to estimate the cost of
+/// runs.
+#[derive(Deserialize, Debug, Clone, Validate, Serialize, JsonSchema)]
+#[garde(context(ValidationContext))]
+pub struct ModelPricing {
+    #[garde(range(min = 0.0))]
+    pub prompt: f64,
+    #[garde(range(min = 0.0))]
+    pub completion: f64,
+}
+
+impl Model {
+    /// Secret reference to the API key of the model, see [`SecretResolver`].
+    ///
+    /// [`SecretResolver`]: crate::config::SecretResolver
+    pub fn api_key_ref(&self) -> String {
+        match self {
+            Model::OpenAI {
+                api_key, key_var, ..
+            } => api_key.clone().unwrap_or_else(|| {
+                format!("env:{}", key_var.clone().unwrap_or_else(default_key_var))
+            }),
+            Model::Anthropic {
+                api_key, key_var, ..
+            } => api_key.clone().unwrap_or_else(|| {
+                format!(
+                    "env:{}",
+                    key_var.clone().unwrap_or_else(default_anthropic_key_var)
+                )
+            }),
+            Model::Ollama { api_key, .. } => api_key.to_string(),
+        }
+    }
+
+    pub fn pricing(&self) -> Option<&ModelPricing> {
+        match self {
+            Model::OpenAI { pricing, .. } => pricing.as_ref(),
+            Model::Anthropic { pricing, .. } => pricing.as_ref(),
+            Model::Ollama { pricing, .. } => pricing.as_ref(),
+        }
+    }
+}
 #[derive(Serialize, Deserialize, Default, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum OutputFormat {
@@ -331,6 +548,11 @@ pub enum FileFormat {
 #[derive(Serialize, Deserialize, Debug, Clone, Validate, JsonSchema)]
 #[garde(context(ValidationContext))]
 pub struct AgentTask {
+    /// Name of the task the agent runs in, set when the task runs
+    #[serde(skip)]
+    #[schemars(skip)]
+    #[garde(skip)]
+    pub name: String,
     #[garde(length(min = 1))]
     pub prompt: String,
     #[garde(custom(validate_agent_exists))]
@@ -395,6 +617,60 @@ pub enum SQL {
     },
 }
 
+#[derive(Serialize, Deserialize, Default, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamType {
+    #[default]
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Date,
+    Timestamp,
+}
+
+impl fmt::Display for ParamType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamType::String => write!(f, "string"),
+            ParamType::Integer => write!(f, "integer"),
+            ParamType::Float => write!(f, "float"),
+            ParamType::Boolean => write!(f, "boolean"),
+            ParamType::Date => write!(f, "date"),
+            ParamType::Timestamp => write!(f, "timestamp"),
+        }
+    }
+}
+
+impl std::str::FromStr for ParamType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "string" => Ok(ParamType::String),
+            "integer" => Ok(ParamType::Integer),
+            "float" => Ok(ParamType::Float),
+            "boolean" => Ok(ParamType::Boolean),
+            "date" => Ok(ParamType::Date),
+            "timestamp" => Ok(ParamType::Timestamp),
+            _ => Err(format!(
+                "Unknown parameter type '{}'. Must be one of string, integer, float, boolean, date or timestamp",
+                s
+            )),
+        }
+    }
+}
+
+/// Query parameter bound to a `:name` placeholder. `value` is rendered as a
+/// template first, then parsed as `type` and passed to the database separately
+/// from the query text.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct SQLParam {
+    #[serde(default, rename = "type")]
+    pub param_type: ParamType,
+    pub value: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Validate, JsonSchema)]
 #[garde(context(ValidationContext))]
 pub struct ExecuteSQLTask {
@@ -408,11 +684,30 @@ pub struct ExecuteSQLTask {
     #[serde(default)]
     #[garde(skip)]
     pub variables: Option<HashMap<String, String>>,
+    #[serde(default)]
+    #[garde(skip)]
+    pub params: Option<HashMap<String, SQLParam>>,
+    #[garde(range(min = 1))]
+    pub timeout: Option<u64>,
+    /// Results to keep when the SQL holds several statements
+    #[serde(default)]
+    #[garde(skip)]
+    pub results: StatementResults,
 
     #[garde(dive)]
     pub export: Option<TaskExport>,
 }
 
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StatementResults {
+    /// Only the result of the last statement
+    #[default]
+    Last,
+    /// The result of every statement, as a list of tables
+    All,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Validate, JsonSchema)]
 #[garde(context(ValidationContext))]
 pub struct FormatterTask {
@@ -548,6 +843,7 @@ pub struct RetrievalTool {
     pub embed_model: String,
     #[serde(default = "default_api_url")]
     pub api_url: String,
+    /// Secret reference to the API key, takes precedence over `key_var`
     pub api_key: Option<String>,
     #[serde(default = "default_key_var")]
     pub key_var: String,
@@ -560,16 +856,10 @@ pub struct RetrievalTool {
 }
 
 impl RetrievalTool {
-    pub fn get_api_key(&self) -> String {
-        match &self.api_key {
-            Some(key) => key.to_string(),
-            None => std::env::var(&self.key_var).unwrap_or_else(|_| {
-                panic!(
-                    "OpenAI key not found in environment variable {}",
-                    self.key_var
-                )
-            }),
-        }
+    pub fn api_key_ref(&self) -> String {
+        self.api_key
+            .clone()
+            .unwrap_or_else(|| format!("env:{}", self.key_var))
     }
 }
 
@@ -579,6 +869,11 @@ pub struct ExecuteSQLTool {
     #[serde(default = "default_sql_tool_description")]
     pub description: String,
     pub database: String,
+    #[serde(default)]
+    pub allow_writes: bool,
+    pub allowed_tables: Option<Vec<String>>,
+    #[serde(default)]
+    pub denied_columns: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, JsonSchema, Clone)]
@@ -587,6 +882,9 @@ pub struct ValidateSQLTool {
     #[serde(default = "default_validate_sql_tool_description")]
     pub description: String,
     pub database: String,
+    pub allowed_tables: Option<Vec<String>>,
+    #[serde(default)]
+    pub denied_columns: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, JsonSchema, Clone)]
@@ -600,10 +898,22 @@ pub enum ToolConfig {
     Retrieval(RetrievalTool),
 }
 
+fn default_postgres_schemas() -> Vec<String> {
+    vec!["public".to_string()]
+}
+
 fn default_openai_api_url() -> Option<String> {
     Some("https://api.openai.com/v1".to_string())
 }
 
+fn default_anthropic_api_url() -> Option<String> {
+    Some(ANTHROPIC_API_BASE.to_string())
+}
+
+fn default_anthropic_max_tokens() -> u32 {
+    DEFAULT_ANTHROPIC_MAX_TOKENS
+}
+
 fn default_anonymizer_replacement() -> String {
     "FLASH".to_string()
 }
@@ -644,6 +954,10 @@ fn default_key_var() -> String {
     "OPENAI_API_KEY".to_string()
 }
 
+fn default_anthropic_key_var() -> String {
+    "ANTHROPIC_API_KEY".to_string()
+}
+
 fn default_retrieval_n_dims() -> usize {
     512
 }
@@ -668,6 +982,30 @@ fn default_tools() -> Vec<ToolConfig> {
     vec![]
 }
 
+fn default_history_window() -> usize {
+    DEFAULT_HISTORY_WINDOW
+}
+
+fn default_max_tool_rounds() -> usize {
+    DEFAULT_MAX_TOOL_ROUNDS
+}
+
+fn default_max_concurrent_tool_calls() -> usize {
+    DEFAULT_MAX_CONCURRENT_TOOL_CALLS
+}
+
+fn default_result_ttl_seconds() -> u64 {
+    DEFAULT_RESULT_TTL_SECONDS
+}
+
+fn default_query_cache_ttl_seconds() -> u64 {
+    DEFAULT_QUERY_CACHE_TTL_SECONDS
+}
+
+fn default_query_cache_max_size_mb() -> u64 {
+    DEFAULT_QUERY_CACHE_MAX_SIZE_MB
+}
+
 fn default_cache_enabled() -> bool {
     false
 }
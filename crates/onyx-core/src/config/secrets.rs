@@ -0,0 +1,82 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::errors::OnyxError;
+
+const KEYRING_DEFAULT_SERVICE: &str = "onyx";
+
+/// Where a credential is read from. Config fields holding credentials accept
+/// `env:NAME`, `file:path` or `keyring:[service/]user`; any other value is
+/// used as is, which keeps plaintext configs working.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SecretRef {
+    Env(String),
+    File(String),
+    Keyring { service: String, user: String },
+    Plain(String),
+}
+
+impl SecretRef {
+    pub fn parse(reference: &str) -> Self {
+        if let Some(var) = reference.strip_prefix("env:") {
+            SecretRef::Env(var.to_string())
+        } else if let Some(path) = reference.strip_prefix("file:") {
+            SecretRef::File(path.to_string())
+        } else if let Some(entry) = reference.strip_prefix("keyring:") {
+            let (service, user) = entry
+                .split_once('/')
+                .unwrap_or((KEYRING_DEFAULT_SERVICE, entry));
+            SecretRef::Keyring {
+                service: service.to_string(),
+                user: user.to_string(),
+            }
+        } else {
+            SecretRef::Plain(reference.to_string())
+        }
+    }
+}
+
+/// Describes the reference without ever showing a plaintext value.
+impl fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretRef::Env(var) => write!(f, "env:{}", var),
+            SecretRef::File(path) => write!(f, "file:{}", path),
+            SecretRef::Keyring { service, user } => write!(f, "keyring:{}/{}", service, user),
+            SecretRef::Plain(_) => write!(f, "<inline secret>"),
+        }
+    }
+}
+
+/// Resolves secret references, reading `file:` paths relative to the project.
+#[derive(Debug, Clone)]
+pub struct SecretResolver {
+    base_dir: PathBuf,
+}
+
+impl SecretResolver {
+    pub fn new<P: Into<PathBuf>>(base_dir: P) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+        }
+    }
+
+    pub fn resolve(&self, reference: &str) -> Result<String, OnyxError> {
+        let secret_ref = SecretRef::parse(reference);
+        let unresolved = |e: &dyn fmt::Display| {
+            OnyxError::ConfigurationError(format!("Failed to resolve secret {}: {}", secret_ref, e))
+        };
+        let value = match &secret_ref {
+            SecretRef::Env(var) => std::env::var(var).map_err(|e| unresolved(&e))?,
+            SecretRef::File(path) => std::fs::read_to_string(self.base_dir.join(path))
+                .map_err(|e| unresolved(&e))?
+                .trim()
+                .to_string(),
+            SecretRef::Keyring { service, user } => keyring::Entry::new(service, user)
+                .and_then(|entry| entry.get_password())
+                .map_err(|e| unresolved(&e))?,
+            SecretRef::Plain(value) => value.to_string(),
+        };
+        Ok(value)
+    }
+}
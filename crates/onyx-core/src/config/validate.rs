@@ -52,6 +52,16 @@ pub fn validate_env_var(env_var: &str, _: &ValidationContext) -> garde::Result {
     }
 }
 
+pub fn validate_optional_env_var(
+    env_var: &Option<String>,
+    context: &ValidationContext,
+) -> garde::Result {
+    match env_var {
+        Some(env_var) => validate_env_var(env_var, context),
+        None => Ok(()),
+    }
+}
+
 pub struct ValidationContext {
     pub config: Config,
 }
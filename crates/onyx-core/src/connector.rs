@@ -1,17 +1,60 @@
-use arrow::datatypes::{Schema, SchemaRef};
-use arrow::ipc::{reader::FileReader, writer::FileWriter};
-use arrow::{array::as_string_array, error::ArrowError, record_batch::RecordBatch};
-use connectorx::prelude::{get_arrow, CXQuery, SourceConn};
+use arrow::compute::cast;
+use arrow::datatypes::{
+    DataType, Field, IntervalMonthDayNano, IntervalUnit, Schema, SchemaRef, TimeUnit,
+    DECIMAL128_MAX_PRECISION,
+};
+use arrow::{
+    array::{
+        as_string_array, Array, ArrayRef, BinaryArray, BooleanArray, Date32Array, Decimal128Array,
+        Float32Array, Float64Array, Int16Array, Int32Array, Int64Array, IntervalMonthDayNanoArray,
+        StringArray, Time64MicrosecondArray, TimestampMicrosecondArray, UInt64Array,
+    },
+    error::ArrowError,
+    record_batch::{RecordBatch, RecordBatchOptions},
+};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+use deadpool_postgres::{Manager, Pool};
 use duckdb::Connection;
+use futures::future::BoxFuture;
+use gcp_bigquery_client::error::BQError;
+use gcp_bigquery_client::model::{
+    field_type::FieldType, get_query_results_parameters::GetQueryResultsParameters, job::Job,
+    job_configuration::JobConfiguration, job_configuration_query::JobConfigurationQuery,
+    query_parameter::QueryParameter, query_parameter_type::QueryParameterType,
+    query_parameter_value::QueryParameterValue, table_field_schema::TableFieldSchema,
+    table_row::TableRow,
+};
+use gcp_bigquery_client::Client as BigQueryClient;
 use log::debug;
-use std::fs::File;
-use std::path::PathBuf;
-use std::sync::Arc;
-use uuid::Uuid;
+use mysql::prelude::Queryable;
+use mysql::Value as MySQLValue;
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use postgres_openssl::MakeTlsConnector;
+use rusqlite::types::Value as SQLiteValue;
+use rusqlite::OpenFlags;
+use sqlparser::ast::Statement;
+use sqlparser::dialect::DuckDbDialect;
+use sqlparser::parser::Parser;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OnceCell, Semaphore};
+use tokio::task::JoinHandle;
+use tokio_postgres::types::{FromSql, Kind, ToSql, Type};
+use yup_oauth2::ServiceAccountKey;
 
-use crate::config::model::DatabaseType;
+use crate::config::model::{DatabaseType, Postgres as PostgresSettings, PostgresSSLMode};
 use crate::config::ConfigManager;
 use crate::errors::OnyxError;
+use crate::query_cache::{CacheStatus, QueryCache};
+use crate::result_store::{load_result, ResultStore};
+use crate::sql_guard::SQLGuard;
+use crate::sql_params::{bind_params, ParamValue};
+use crate::sql_script::split_statements;
 
 const CREATE_CONN: &str = "Failed to open connection";
 const EXECUTE_QUERY: &str = "Failed to execute query";
@@ -19,27 +62,179 @@ const LOAD_RESULT: &str = "Error loading query results";
 const WRITE_RESULT: &str = "Failed to write result to IPC";
 const SET_FILE_SEARCH_PATH: &str = "Failed to set file search path";
 const FAILED_TO_RUN_BLOCKING_TASK: &str = "Failed to run blocking task";
+const DEFAULT_POOL_SIZE: usize = 4;
 
 // duckdb errors
 const PREPARE_DUCKDB_STMT: &str = "Failed to prepare DuckDB statement";
+const RUN_DUCKDB_INIT_SQL: &str = "Failed to run DuckDB init SQL";
+
+// postgres errors
+const PREPARE_POSTGRES_STMT: &str = "Failed to prepare Postgres statement";
+const CAST_POSTGRES_COLUMNS: &str =
+    "Failed to load a column of a type arrow cannot hold, cast it to a supported type in the query";
+const SETUP_POSTGRES_TLS: &str = "Failed to set up TLS for Postgres";
+
+// sqlite errors
+const PREPARE_SQLITE_STMT: &str = "Failed to prepare SQLite statement";
 
 // arrow errors
 const LOAD_ARROW_RESULT: &str = "Failed to load arrow result";
 
+// file types DuckDB can query by path
+const DUCKDB_FILE_EXTENSIONS: [&str; 5] = ["csv", "parquet", "json", "jsonl", "ndjson"];
+
+// schema introspection queries, all returning `table_name, column_name, data_type,
+// is_nullable, is_primary_key, foreign_key, comment` ordered by table
+const POSTGRES_COLUMNS_QUERY: &str = "
+SELECT
+    CASE WHEN c.table_schema = 'public' THEN c.table_name ELSE c.table_schema || '.' || c.table_name END::text,
+    c.column_name::text, c.data_type::text, c.is_nullable::text,
+    CASE WHEN pk.column_name IS NULL THEN 'NO' ELSE 'YES' END,
+    fk.foreign_key,
+    col_description(format('%I.%I', c.table_schema, c.table_name)::regclass, c.ordinal_position::int)
+FROM information_schema.columns c
+LEFT JOIN (
+    SELECT kcu.table_schema, kcu.table_name, kcu.column_name
+    FROM information_schema.table_constraints tc
+    JOIN information_schema.key_column_usage kcu
+        ON tc.constraint_name = kcu.constraint_name AND tc.table_schema = kcu.table_schema
+    WHERE tc.constraint_type = 'PRIMARY KEY'
+) pk ON pk.table_schema = c.table_schema AND pk.table_name = c.table_name AND pk.column_name = c.column_name
+LEFT JOIN (
+    SELECT kcu.table_schema, kcu.table_name, kcu.column_name,
+        (ccu.table_name || '.' || ccu.column_name)::text AS foreign_key
+    FROM information_schema.table_constraints tc
+    JOIN information_schema.key_column_usage kcu
+        ON tc.constraint_name = kcu.constraint_name AND tc.table_schema = kcu.table_schema
+    JOIN information_schema.constraint_column_usage ccu
+        ON tc.constraint_name = ccu.constraint_name AND tc.table_schema = ccu.table_schema
+    WHERE tc.constraint_type = 'FOREIGN KEY'
+) fk ON fk.table_schema = c.table_schema AND fk.table_name = c.table_name AND fk.column_name = c.column_name
+WHERE c.table_schema IN ({schemas})
+ORDER BY c.table_schema, c.table_name, c.ordinal_position";
+
+const MYSQL_COLUMNS_QUERY: &str = "
+SELECT CAST(c.table_name AS CHAR), CAST(c.column_name AS CHAR), CAST(c.column_type AS CHAR),
+    CAST(c.is_nullable AS CHAR),
+    CAST(IF(c.column_key = 'PRI', 'YES', 'NO') AS CHAR),
+    CAST(CONCAT(k.referenced_table_name, '.', k.referenced_column_name) AS CHAR),
+    CAST(c.column_comment AS CHAR)
+FROM information_schema.columns c
+LEFT JOIN information_schema.key_column_usage k
+    ON k.table_schema = c.table_schema AND k.table_name = c.table_name
+    AND k.column_name = c.column_name AND k.referenced_table_name IS NOT NULL
+WHERE c.table_schema = DATABASE()
+ORDER BY c.table_name, c.ordinal_position";
+
+const BIGQUERY_COLUMNS_QUERY: &str = "
+SELECT c.table_name, c.column_name, c.data_type, c.is_nullable,
+    IF(pk.column_name IS NULL, 'NO', 'YES'),
+    fk.foreign_key,
+    f.description
+FROM `{dataset}`.INFORMATION_SCHEMA.COLUMNS c
+LEFT JOIN `{dataset}`.INFORMATION_SCHEMA.COLUMN_FIELD_PATHS f
+    ON f.table_name = c.table_name AND f.column_name = c.column_name AND f.field_path = c.column_name
+LEFT JOIN (
+    SELECT k.table_name, k.column_name
+    FROM `{dataset}`.INFORMATION_SCHEMA.TABLE_CONSTRAINTS t
+    JOIN `{dataset}`.INFORMATION_SCHEMA.KEY_COLUMN_USAGE k ON t.constraint_name = k.constraint_name
+    WHERE t.constraint_type = 'PRIMARY KEY'
+) pk ON pk.table_name = c.table_name AND pk.column_name = c.column_name
+LEFT JOIN (
+    SELECT k.table_name, k.column_name, CONCAT(u.table_name, '.', u.column_name) AS foreign_key
+    FROM `{dataset}`.INFORMATION_SCHEMA.TABLE_CONSTRAINTS t
+    JOIN `{dataset}`.INFORMATION_SCHEMA.KEY_COLUMN_USAGE k ON t.constraint_name = k.constraint_name
+    JOIN `{dataset}`.INFORMATION_SCHEMA.CONSTRAINT_COLUMN_USAGE u ON t.constraint_name = u.constraint_name
+    WHERE t.constraint_type = 'FOREIGN KEY'
+) fk ON fk.table_name = c.table_name AND fk.column_name = c.column_name
+ORDER BY c.table_name, c.ordinal_position";
+
+const SQLITE_COLUMNS_QUERY: &str = "
+SELECT m.name, p.name, p.type,
+    CASE WHEN p.\"notnull\" = 0 THEN 'YES' ELSE 'NO' END,
+    CASE WHEN p.pk > 0 THEN 'YES' ELSE 'NO' END,
+    (SELECT f.\"table\" || COALESCE('.' || f.\"to\", '')
+        FROM pragma_foreign_key_list(m.name) f WHERE f.\"from\" = p.name LIMIT 1),
+    NULL
+FROM sqlite_master m
+JOIN pragma_table_info(m.name) p
+WHERE m.type IN ('table', 'view') AND m.name NOT LIKE 'sqlite_%'
+ORDER BY m.name, p.cid";
+
+const DUCKDB_COLUMNS_QUERY: &str = "
+SELECT c.database_name || '.' || c.schema_name || '.' || c.table_name, c.column_name, c.data_type,
+    c.is_nullable,
+    EXISTS (
+        SELECT 1 FROM duckdb_constraints() k
+        WHERE k.constraint_type = 'PRIMARY KEY' AND k.database_name = c.database_name
+            AND k.schema_name = c.schema_name AND k.table_name = c.table_name
+            AND list_contains(k.constraint_column_names, c.column_name)
+    ),
+    (
+        SELECT k.referenced_table || '.'
+            || k.referenced_column_names[list_position(k.constraint_column_names, c.column_name)]
+        FROM duckdb_constraints() k
+        WHERE k.constraint_type = 'FOREIGN KEY' AND k.database_name = c.database_name
+            AND k.schema_name = c.schema_name AND k.table_name = c.table_name
+            AND list_contains(k.constraint_column_names, c.column_name)
+        LIMIT 1
+    ),
+    c.comment
+FROM duckdb_columns() c
+WHERE NOT c.internal
+ORDER BY c.database_name, c.schema_name, c.table_name, c.column_index";
+
 fn connector_internal_error(message: &str, e: &impl std::fmt::Display) -> OnyxError {
     log::error!("{}: {}", message, e);
     OnyxError::DBError(format!("{}: {}", message, e))
 }
 
+/// Settings of the session a query runs in.
+#[derive(Debug, Clone, Copy, Default)]
+struct QueryOptions {
+    timeout: Option<Duration>,
+    /// Makes the session read-only where the database supports it, so that
+    /// e.g. functions with side effects fail as well
+    read_only: bool,
+}
+
 #[enum_dispatch::enum_dispatch]
 trait Engine {
-    async fn run_query(&self, query: &str) -> Result<String, OnyxError>;
+    async fn run_query(
+        &self,
+        query: &str,
+        params: &[ParamValue],
+        options: QueryOptions,
+    ) -> Result<String, OnyxError>;
+    /// Runs `statements` in order, on a single session, and returns the result
+    /// file of every statement when `all` is set, otherwise of the last one.
+    async fn run_statements(
+        &self,
+        statements: &[(String, Vec<ParamValue>)],
+        options: QueryOptions,
+        all: bool,
+    ) -> Result<Vec<String>, OnyxError>;
     async fn load_database_info(&self) -> Result<DatabaseInfo, OnyxError>;
+    fn dialect(&self) -> String;
+    fn explain_query(&self, query: &str) -> String {
+        format!("EXPLAIN ({})", query)
+    }
+    /// Rewrites `:name` placeholders into the engine's syntax and returns the
+    /// values to bind, in order.
+    fn bind(
+        &self,
+        query: &str,
+        params: &HashMap<String, ParamValue>,
+    ) -> Result<(String, Vec<ParamValue>), OnyxError> {
+        Ok(bind_params(query, params, |_, _| "?".to_string()))
+    }
     async fn run_query_and_load(
         &self,
         query: &str,
+        params: &[ParamValue],
+        options: QueryOptions,
     ) -> Result<(Vec<RecordBatch>, SchemaRef), OnyxError> {
-        let file_path = self.run_query(query).await?;
+        let file_path = self.run_query(query, params, options).await?;
         load_result(&file_path).map_err(|e| connector_internal_error(LOAD_RESULT, &e))
     }
 }
@@ -48,135 +243,2035 @@ trait Engine {
 #[derive(Debug)]
 enum EngineType {
     DuckDB,
-    ConnectorX,
+    MySQL,
+    BigQuery,
+    Postgres,
+    SQLite,
 }
 
 #[derive(Debug)]
 struct DuckDB {
     file_search_path: String,
+    db_path: Option<String>,
+    /// Database instance shared by every query, opened and initialized once
+    instance: Arc<Mutex<Connection>>,
+    /// Statements of `init_sql` whose effect only lasts for a session, run
+    /// again on every connection
+    session_sql: Arc<Vec<String>>,
+    result_store: ResultStore,
 }
 
-impl Engine for DuckDB {
-    async fn run_query(&self, query: &str) -> Result<String, OnyxError> {
-        let query = query.to_string();
-        let conn = Connection::open_in_memory()
-            .map_err(|err| connector_internal_error(CREATE_CONN, &err))?;
-        let dir_set_stmt = format!("SET file_search_path = '{}'", &self.file_search_path);
+impl DuckDB {
+    fn new(
+        file_search_path: String,
+        db_path: Option<String>,
+        init_sql: &[String],
+        result_store: ResultStore,
+    ) -> Result<Self, OnyxError> {
+        let conn = match &db_path {
+            Some(db_path) => Connection::open(db_path),
+            None => Connection::open_in_memory(),
+        }
+        .map_err(|err| connector_internal_error(CREATE_CONN, &err))?;
+        Self::set_file_search_path(&conn, &file_search_path)?;
+        let mut session_sql = vec![];
+        for stmt in init_sql.iter() {
+            if Self::is_session_scoped(stmt)? {
+                session_sql.push(stmt.to_string());
+            }
+            conn.execute_batch(stmt)
+                .map_err(|err| connector_internal_error(RUN_DUCKDB_INIT_SQL, &err))?;
+        }
+        Ok(DuckDB {
+            file_search_path,
+            db_path,
+            instance: Arc::new(Mutex::new(conn)),
+            session_sql: Arc::new(session_sql),
+            result_store,
+        })
+    }
+
+    /// Tells whether `stmt` only changes the session it runs on, e.g. `SET`
+    /// or `USE`. Temporary objects are rejected, as recreating them for every
+    /// query would be costly and they would be gone once the query finishes.
+    /// Statements sqlparser cannot parse, e.g. `SET GLOBAL`, are run once.
+    fn is_session_scoped(stmt: &str) -> Result<bool, OnyxError> {
+        let Ok(statements) = Parser::parse_sql(&DuckDbDialect {}, stmt) else {
+            return Ok(false);
+        };
+        let mut session_scoped = false;
+        for statement in statements.iter() {
+            let temporary = match statement {
+                Statement::CreateTable(create) => create.temporary,
+                Statement::CreateView { temporary, .. }
+                | Statement::CreateMacro { temporary, .. }
+                | Statement::CreateSequence { temporary, .. } => *temporary,
+                _ => false,
+            };
+            if temporary {
+                return Err(OnyxError::ConfigurationError(format!(
+                    "DuckDB init_sql must not create temporary objects, as every query runs on its own connection: {}",
+                    stmt
+                )));
+            }
+            session_scoped |= matches!(
+                statement,
+                Statement::SetVariable { .. }
+                    | Statement::SetTimeZone { .. }
+                    | Statement::SetRole { .. }
+                    | Statement::Use(_)
+            );
+        }
+        Ok(session_scoped)
+    }
+
+    fn set_file_search_path(conn: &Connection, file_search_path: &str) -> Result<(), OnyxError> {
+        let dir_set_stmt = format!("SET file_search_path = '{}'", file_search_path);
         conn.execute(&dir_set_stmt, [])
             .map_err(|err| connector_internal_error(SET_FILE_SEARCH_PATH, &err))?;
+        Ok(())
+    }
+
+    /// Runs `f` on the blocking pool with a new connection to the database
+    /// instance, as opening one runs the session's statements and `f` may
+    /// scan files.
+    fn spawn_with_connection<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(Connection) -> anyhow::Result<T> + Send + 'static,
+    ) -> JoinHandle<anyhow::Result<T>> {
+        let instance = self.instance.clone();
+        let file_search_path = self.file_search_path.clone();
+        let session_sql = self.session_sql.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = instance
+                .lock()
+                .map_err(|err| connector_internal_error(CREATE_CONN, &err))?
+                .try_clone()
+                .map_err(|err| connector_internal_error(CREATE_CONN, &err))?;
+            Self::set_file_search_path(&conn, &file_search_path)?;
+            for stmt in session_sql.iter() {
+                conn.execute_batch(stmt)
+                    .map_err(|err| connector_internal_error(RUN_DUCKDB_INIT_SQL, &err))?;
+            }
+            f(conn)
+        })
+    }
+
+    /// Lists data files DuckDB can query directly, relative to `file_search_path`.
+    fn list_data_files(file_search_path: &str, dir: &PathBuf) -> Vec<String> {
+        let mut files = Vec::new();
+
+        if let Ok(entries) = std::fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    files.extend(Self::list_data_files(file_search_path, &path));
+                } else if path.is_file()
+                    && path
+                        .extension()
+                        .and_then(|s| s.to_str())
+                        .map(|s| DUCKDB_FILE_EXTENSIONS.contains(&s.to_lowercase().as_str()))
+                        .unwrap_or(false)
+                {
+                    if let Ok(relative) = path.strip_prefix(file_search_path) {
+                        files.push(relative.to_string_lossy().to_string());
+                    }
+                }
+            }
+        }
+
+        files.sort();
+        files
+    }
+
+    fn query(
+        conn: &Connection,
+        query: &str,
+        params: Vec<duckdb::types::Value>,
+    ) -> Result<(Vec<RecordBatch>, SchemaRef), OnyxError> {
         let mut stmt = conn
-            .prepare(&query)
+            .prepare(query)
             .map_err(|err| connector_internal_error(PREPARE_DUCKDB_STMT, &err))?;
         let arrow_stream = stmt
-            .query_arrow([])
+            .query_arrow(duckdb::params_from_iter(params))
             .map_err(|err| connector_internal_error(EXECUTE_QUERY, &err))?;
         let schema = arrow_stream.get_schema();
-        let arrow_chunks = arrow_stream.collect();
+        let arrow_chunks: Vec<RecordBatch> = arrow_stream.collect();
         debug!("Query results: {:?}", arrow_chunks);
-        let file_path = format!("/tmp/{}.arrow", Uuid::new_v4());
-        write_to_ipc(&arrow_chunks, &file_path, &schema)
-            .map_err(|err| connector_internal_error(WRITE_RESULT, &err))?;
-        Ok(file_path)
+        Ok((arrow_chunks, schema))
+    }
+
+    fn describe_file(conn: &Connection, file: &str) -> Result<TableInfo, OnyxError> {
+        let mut stmt = conn
+            .prepare(&format!(
+                "DESCRIBE SELECT * FROM '{}'",
+                file.replace('\'', "''")
+            ))
+            .map_err(|err| connector_internal_error(PREPARE_DUCKDB_STMT, &err))?;
+        let columns = stmt
+            .query_map([], |row| {
+                Ok(ColumnInfo {
+                    name: row.get(0)?,
+                    data_type: row.get(1)?,
+                    nullable: row.get::<_, Option<String>>(2)?.as_deref() != Some("NO"),
+                    primary_key: row.get::<_, Option<String>>(3)?.as_deref() == Some("PRI"),
+                    foreign_key: None,
+                    comment: None,
+                })
+            })
+            .map_err(|err| connector_internal_error(EXECUTE_QUERY, &err))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| connector_internal_error(EXECUTE_QUERY, &err))?;
+        Ok(TableInfo {
+            name: format!("'{}'", file),
+            columns,
+        })
+    }
+
+    fn describe_catalog_tables(conn: &Connection) -> Result<Vec<TableInfo>, OnyxError> {
+        let mut stmt = conn
+            .prepare(DUCKDB_COLUMNS_QUERY)
+            .map_err(|err| connector_internal_error(PREPARE_DUCKDB_STMT, &err))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    ColumnInfo {
+                        name: row.get(1)?,
+                        data_type: row.get(2)?,
+                        nullable: row.get(3)?,
+                        primary_key: row.get(4)?,
+                        foreign_key: row.get(5)?,
+                        comment: row.get(6)?,
+                    },
+                ))
+            })
+            .map_err(|err| connector_internal_error(EXECUTE_QUERY, &err))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| connector_internal_error(EXECUTE_QUERY, &err))?;
+
+        let mut tables = vec![];
+        for (table_name, column) in rows {
+            push_column(&mut tables, table_name, column);
+        }
+        Ok(tables)
+    }
+}
+
+impl Engine for DuckDB {
+    async fn run_query(
+        &self,
+        query: &str,
+        params: &[ParamValue],
+        options: QueryOptions,
+    ) -> Result<String, OnyxError> {
+        let query = query.to_string();
+        let params = params.iter().map(duckdb_value).collect::<Vec<_>>();
+        let result_store = self.result_store.clone();
+        let task = self.spawn_with_connection(move |conn| {
+            let (arrow_chunks, schema) = Self::query(&conn, &query, params)?;
+            let file_path = result_store
+                .write(&arrow_chunks, &schema)
+                .map_err(|err| connector_internal_error(WRITE_RESULT, &err))?;
+            Ok::<String, anyhow::Error>(file_path)
+        });
+
+        // duckdb-rs 1.1 cannot interrupt a connection, so a query that times
+        // out runs to completion in the background, holding its connection
+        // and the database file lock until then
+        with_timeout(options.timeout, task, || {}).await
+    }
+
+    async fn run_statements(
+        &self,
+        statements: &[(String, Vec<ParamValue>)],
+        options: QueryOptions,
+        all: bool,
+    ) -> Result<Vec<String>, OnyxError> {
+        let statements = statements
+            .iter()
+            .map(|(query, params)| (query.clone(), params.iter().map(duckdb_value).collect()))
+            .collect::<Vec<(String, Vec<_>)>>();
+        let result_store = self.result_store.clone();
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let task = self.spawn_with_connection({
+            let timed_out = timed_out.clone();
+            move |conn| {
+                let mut file_paths = vec![];
+                let last = statements.len().saturating_sub(1);
+                for (idx, (query, params)) in statements.into_iter().enumerate() {
+                    // The running statement cannot be interrupted, but the script
+                    // stops before the next one
+                    if timed_out.load(Ordering::Relaxed) {
+                        anyhow::bail!("Script stopped after timing out");
+                    }
+                    let (arrow_chunks, schema) = Self::query(&conn, &query, params)?;
+                    if all || idx == last {
+                        file_paths.push(
+                            result_store
+                                .write(&arrow_chunks, &schema)
+                                .map_err(|err| connector_internal_error(WRITE_RESULT, &err))?,
+                        );
+                    }
+                }
+                Ok::<Vec<String>, anyhow::Error>(file_paths)
+            }
+        });
+
+        with_timeout(options.timeout, task, || {
+            timed_out.store(true, Ordering::Relaxed)
+        })
+        .await
+    }
+
+    fn dialect(&self) -> String {
+        "duckdb".to_string()
     }
 
     async fn load_database_info(&self) -> Result<DatabaseInfo, OnyxError> {
+        let file_search_path = self.file_search_path.clone();
+        let task = self.spawn_with_connection(move |conn| {
+            let mut tables = Self::describe_catalog_tables(&conn)?;
+            let files = Self::list_data_files(&file_search_path, &PathBuf::from(&file_search_path));
+            for file in files {
+                match Self::describe_file(&conn, &file) {
+                    Ok(table) => tables.push(table),
+                    Err(err) => {
+                        log::warn!("Skipping {} during schema introspection: {}", file, err)
+                    }
+                }
+            }
+            Ok::<Vec<TableInfo>, anyhow::Error>(tables)
+        });
+        let tables = with_timeout(None, task, || {}).await?;
         Ok(DatabaseInfo {
-            name: self.file_search_path.to_string(),
+            name: self
+                .db_path
+                .clone()
+                .unwrap_or_else(|| self.file_search_path.to_string()),
             dialect: "duckdb".to_string(),
-            tables: vec![],
+            tables,
         })
     }
 }
 
-#[derive(Debug)]
-pub struct ConnectorX {
-    dialect: String,
-    db_path: String,
+/// MySQL is queried through its own driver, which binds parameters through the
+/// binary protocol and keeps a pooled connection for a whole script. Running
+/// statements are killed on timeout.
+pub struct MySQL {
     db_name: String,
+    opts: mysql::Opts,
+    pool: mysql::Pool,
+    result_store: ResultStore,
 }
 
-impl ConnectorX {
-    pub async fn get_schemas(&self) -> Result<Vec<String>, OnyxError> {
-        let query_string = match self.dialect.as_str() {
-            "bigquery" => {
-                format!(
-                    "SELECT ddl FROM `{}`.INFORMATION_SCHEMA.TABLES",
-                    self.db_name
-                )
+/// The connection settings include the password, so they are left out of
+/// debug output.
+impl fmt::Debug for MySQL {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MySQL")
+            .field("db_name", &self.db_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MySQL {
+    fn new(
+        conn_string: &str,
+        db_name: String,
+        pool_size: usize,
+        result_store: ResultStore,
+    ) -> Result<Self, OnyxError> {
+        // Errors do not echo the connection string, which includes the password
+        let opts = mysql::Opts::from_url(&format!("mysql://{}", conn_string)).map_err(|err| {
+            OnyxError::ConfigurationError(format!("Invalid MySQL connection string: {}", err))
+        })?;
+        // Connections are opened on first use rather than here. The pool
+        // resets the session of a connection when it is returned.
+        let constraints = mysql::PoolConstraints::new(0, pool_size.max(1))
+            .ok_or_else(|| connector_internal_error(CREATE_CONN, &"invalid pool size"))?;
+        let pool = mysql::Pool::new(
+            mysql::OptsBuilder::from_opts(opts.clone())
+                .pool_opts(mysql::PoolOpts::default().with_constraints(constraints)),
+        )
+        .map_err(|err| connector_internal_error(CREATE_CONN, &err))?;
+        Ok(MySQL {
+            db_name,
+            opts,
+            pool,
+            result_store,
+        })
+    }
+
+    /// Runs `f` on a pooled connection set up for `options`, killing the
+    /// running statement on timeout. The session is reset when the connection
+    /// goes back to the pool, so that settings, temporary tables or an open
+    /// transaction do not leak into later queries.
+    async fn with_session<T: Send + 'static>(
+        &self,
+        options: QueryOptions,
+        f: impl FnOnce(&mut mysql::PooledConn) -> Result<T, OnyxError> + Send + 'static,
+    ) -> Result<T, OnyxError> {
+        let pool = self.pool.clone();
+        let connection_id = Arc::new(Mutex::new(None));
+        let task = tokio::task::spawn_blocking({
+            let connection_id = connection_id.clone();
+            move || {
+                let mut conn = pool
+                    .get_conn()
+                    .map_err(|err| connector_internal_error(CREATE_CONN, &err))?;
+                if let Ok(mut connection_id) = connection_id.lock() {
+                    *connection_id = Some(conn.connection_id());
+                }
+                let mut setup = vec![];
+                // Only applies to `SELECT`s, other statements are killed on timeout
+                if let Some(timeout) = options.timeout {
+                    setup.push(format!(
+                        "SET SESSION max_execution_time = {}",
+                        timeout.as_millis()
+                    ));
+                }
+                if options.read_only {
+                    setup.push("SET SESSION TRANSACTION READ ONLY".to_string());
+                }
+                for statement in setup {
+                    conn.query_drop(statement)
+                        .map_err(|err| connector_internal_error(CREATE_CONN, &err))?;
+                }
+                let result = f(&mut conn);
+                if let Ok(mut connection_id) = connection_id.lock() {
+                    connection_id.take();
+                }
+                Ok::<T, anyhow::Error>(result?)
             }
-            "postgres" => {
-                "SELECT table_name FROM information_schema.tables WHERE table_schema = 'public'"
-                    .to_string()
+        });
+
+        // Killed from a connection of its own, as the pool may be exhausted
+        let opts = self.opts.clone();
+        with_timeout(options.timeout, task, move || {
+            let connection_id = connection_id.lock().ok().and_then(|mut id| id.take());
+            if let Some(connection_id) = connection_id {
+                tokio::task::spawn_blocking(move || {
+                    let killed = mysql::Conn::new(opts).and_then(|mut conn| {
+                        conn.query_drop(format!("KILL QUERY {}", connection_id))
+                    });
+                    if let Err(err) = killed {
+                        log::warn!("Failed to cancel MySQL query: {}", err);
+                    }
+                });
             }
-            _ => Err(OnyxError::DBError(format!(
-                "Unsupported dialect: {}",
-                self.dialect
-            )))?,
+        })
+        .await
+    }
+
+    /// Runs `query` with `params` bound to its `?` placeholders. Only the
+    /// first result set is loaded, e.g. of a procedure call.
+    fn query(
+        conn: &mut mysql::PooledConn,
+        query: &str,
+        params: &[ParamValue],
+    ) -> Result<(RecordBatch, SchemaRef), OnyxError> {
+        let params = params.iter().map(mysql_value).collect::<Vec<_>>();
+        let mut result = conn
+            .exec_iter(query, mysql::Params::from(params))
+            .map_err(|err| connector_internal_error(EXECUTE_QUERY, &err))?;
+        let columns = result.columns().as_ref().to_vec();
+        let mut values: Vec<Vec<MySQLValue>> = vec![vec![]; columns.len()];
+        let mut num_rows = 0;
+        for row in result.by_ref() {
+            let row = row.map_err(|err| connector_internal_error(EXECUTE_QUERY, &err))?;
+            for (column, value) in values.iter_mut().zip(row.unwrap()) {
+                column.push(value);
+            }
+            num_rows += 1;
+        }
+
+        let mut fields = vec![];
+        let mut arrays = vec![];
+        for (column, values) in columns.iter().zip(values) {
+            let array = mysql_column(column, values)
+                .map_err(|err| connector_internal_error(LOAD_ARROW_RESULT, &err))?;
+            fields.push(Field::new(
+                column.name_str(),
+                array.data_type().clone(),
+                true,
+            ));
+            arrays.push(array);
+        }
+        let schema = Arc::new(Schema::new(fields));
+        let batch = RecordBatch::try_new_with_options(
+            schema.clone(),
+            arrays,
+            &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+        )
+        .map_err(|err| connector_internal_error(LOAD_ARROW_RESULT, &err))?;
+        Ok((batch, schema))
+    }
+}
+
+impl Engine for MySQL {
+    async fn run_query(
+        &self,
+        query: &str,
+        params: &[ParamValue],
+        options: QueryOptions,
+    ) -> Result<String, OnyxError> {
+        let query = query.to_string();
+        let params = params.to_vec();
+        let result_store = self.result_store.clone();
+        self.with_session(options, move |conn| {
+            let (batch, schema) = Self::query(conn, &query, &params)?;
+            result_store
+                .write(&[batch], &schema)
+                .map_err(|err| connector_internal_error(WRITE_RESULT, &err))
+        })
+        .await
+    }
+
+    async fn run_statements(
+        &self,
+        statements: &[(String, Vec<ParamValue>)],
+        options: QueryOptions,
+        all: bool,
+    ) -> Result<Vec<String>, OnyxError> {
+        let statements = statements.to_vec();
+        let result_store = self.result_store.clone();
+        self.with_session(options, move |conn| {
+            let mut file_paths = vec![];
+            let last = statements.len().saturating_sub(1);
+            for (idx, (query, params)) in statements.iter().enumerate() {
+                let (batch, schema) = Self::query(conn, query, params)?;
+                if all || idx == last {
+                    file_paths.push(
+                        result_store
+                            .write(&[batch], &schema)
+                            .map_err(|err| connector_internal_error(WRITE_RESULT, &err))?,
+                    );
+                }
+            }
+            Ok(file_paths)
+        })
+        .await
+    }
+
+    fn dialect(&self) -> String {
+        "mysql".to_string()
+    }
+
+    async fn load_database_info(&self) -> Result<DatabaseInfo, OnyxError> {
+        let (datasets, _) = self
+            .run_query_and_load(MYSQL_COLUMNS_QUERY, &[], QueryOptions::default())
+            .await?;
+        Ok(DatabaseInfo {
+            name: self.db_name.to_string(),
+            dialect: "mysql".to_string(),
+            tables: tables_from_columns(&datasets)?,
+        })
+    }
+}
+
+/// BigQuery is queried through its REST API, with parameters bound as named
+/// query parameters. Every query runs as a job of its own, so there is no
+/// session to keep across statements and the job is cancelled on timeout.
+pub struct BigQuery {
+    dataset: String,
+    project_id: String,
+    key: ServiceAccountKey,
+    /// Authenticated on first use rather than on connect
+    client: OnceCell<BigQueryClient>,
+    /// Caps how many queries run against the dataset at once
+    concurrency: Arc<Semaphore>,
+    result_store: ResultStore,
+}
+
+/// The service account key is a secret, so it is left out of debug output.
+impl fmt::Debug for BigQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BigQuery")
+            .field("dataset", &self.dataset)
+            .field("project_id", &self.project_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl BigQuery {
+    async fn new(
+        key_path: &str,
+        dataset: String,
+        pool_size: usize,
+        result_store: ResultStore,
+    ) -> Result<Self, OnyxError> {
+        let key = yup_oauth2::read_service_account_key(key_path)
+            .await
+            .map_err(|err| {
+                OnyxError::ConfigurationError(format!(
+                    "Invalid BigQuery key file {}: {}",
+                    key_path, err
+                ))
+            })?;
+        let project_id = key.project_id.clone().ok_or_else(|| {
+            OnyxError::ConfigurationError(format!(
+                "BigQuery key file {} has no project_id",
+                key_path
+            ))
+        })?;
+        Ok(BigQuery {
+            dataset,
+            project_id,
+            key,
+            client: OnceCell::new(),
+            concurrency: Arc::new(Semaphore::new(pool_size)),
+            result_store,
+        })
+    }
+
+    async fn client(&self) -> Result<BigQueryClient, OnyxError> {
+        self.client
+            .get_or_try_init(|| BigQueryClient::from_service_account_key(self.key.clone(), false))
+            .await
+            .cloned()
+            .map_err(|err| bigquery_error(CREATE_CONN, err))
+    }
+
+    /// Runs `query` as a job with `params` bound to its `@p<n>` placeholders,
+    /// cancelling the job on timeout.
+    async fn run(
+        &self,
+        query: &str,
+        params: &[ParamValue],
+        timeout: Option<Duration>,
+    ) -> Result<String, OnyxError> {
+        let client = self.client().await?;
+        let project_id = self.project_id.clone();
+        let job = Job {
+            configuration: Some(JobConfiguration {
+                query: Some(JobConfigurationQuery {
+                    query: query.to_string(),
+                    use_legacy_sql: Some(false),
+                    parameter_mode: (!params.is_empty()).then(|| "NAMED".to_string()),
+                    query_parameters: (!params.is_empty()).then(|| {
+                        params
+                            .iter()
+                            .enumerate()
+                            .map(|(idx, value)| bigquery_parameter(idx + 1, value))
+                            .collect()
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
         };
-        let (datasets, _) = self.run_query_and_load(&query_string).await?;
-        let result_iter = datasets
+        let result_store = self.result_store.clone();
+        let permit = self
+            .concurrency
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|err| connector_internal_error(CREATE_CONN, &err))?;
+        let running_job = Arc::new(Mutex::new(None));
+        let task = tokio::spawn({
+            let client = client.clone();
+            let project_id = project_id.clone();
+            let running_job = running_job.clone();
+            async move {
+                // Held until the job finishes, even if we stop waiting for it
+                let _permit = permit;
+                let job = client
+                    .job()
+                    .insert(&project_id, job)
+                    .await
+                    .map_err(|err| bigquery_error(EXECUTE_QUERY, err))?;
+                let job_reference = job.job_reference.unwrap_or_default();
+                let job_id = job_reference
+                    .job_id
+                    .ok_or_else(|| connector_internal_error(EXECUTE_QUERY, &"no job id"))?;
+                if let Ok(mut running_job) = running_job.lock() {
+                    *running_job = Some((job_id.clone(), job_reference.location.clone()));
+                }
+                let (batch, schema) =
+                    Self::load_results(&client, &project_id, &job_id, job_reference.location)
+                        .await?;
+                if let Ok(mut running_job) = running_job.lock() {
+                    running_job.take();
+                }
+                let file_path = result_store
+                    .write(&[batch], &schema)
+                    .map_err(|err| connector_internal_error(WRITE_RESULT, &err))?;
+                Ok::<String, anyhow::Error>(file_path)
+            }
+        });
+
+        with_timeout(timeout, task, move || {
+            let running_job = running_job.lock().ok().and_then(|mut job| job.take());
+            if let Some((job_id, location)) = running_job {
+                tokio::spawn(async move {
+                    if let Err(err) = client
+                        .job()
+                        .cancel_job(&project_id, &job_id, location.as_deref())
+                        .await
+                    {
+                        log::warn!("Failed to cancel BigQuery job: {}", err);
+                    }
+                });
+            }
+        })
+        .await
+    }
+
+    /// Waits for a query job to complete and loads every page of its results.
+    async fn load_results(
+        client: &BigQueryClient,
+        project_id: &str,
+        job_id: &str,
+        location: Option<String>,
+    ) -> Result<(RecordBatch, SchemaRef), OnyxError> {
+        let mut schema = None;
+        let mut rows = vec![];
+        let mut page_token = None;
+        loop {
+            let parameters = GetQueryResultsParameters {
+                location: location.clone(),
+                page_token: page_token.clone(),
+                ..Default::default()
+            };
+            // Waits up to 10 seconds server-side for the job to complete
+            let response = client
+                .job()
+                .get_query_results(project_id, job_id, parameters)
+                .await
+                .map_err(|err| bigquery_error(EXECUTE_QUERY, err))?;
+            if !response.job_complete.unwrap_or_default() {
+                continue;
+            }
+            schema = schema.or(response.schema);
+            rows.extend(response.rows.unwrap_or_default());
+            page_token = response.page_token;
+            if page_token.is_none() {
+                break;
+            }
+        }
+        let fields = schema.and_then(|schema| schema.fields).unwrap_or_default();
+        bigquery_columns_to_record_batch(&fields, &rows)
+            .map_err(|err| connector_internal_error(LOAD_ARROW_RESULT, &err))
+    }
+}
+
+impl Engine for BigQuery {
+    async fn run_query(
+        &self,
+        query: &str,
+        params: &[ParamValue],
+        options: QueryOptions,
+    ) -> Result<String, OnyxError> {
+        self.run(query, params, options.timeout).await
+    }
+
+    /// Every query runs as a job of its own, without a session earlier
+    /// statements could set up, so scripts are rejected.
+    async fn run_statements(
+        &self,
+        statements: &[(String, Vec<ParamValue>)],
+        options: QueryOptions,
+        _all: bool,
+    ) -> Result<Vec<String>, OnyxError> {
+        match statements {
+            [] => Ok(vec![]),
+            [(query, params)] => Ok(vec![self.run(query, params, options.timeout).await?]),
+            _ => Err(OnyxError::ArgumentError(
+                "SQL with several statements is not supported for bigquery databases, run one statement at a time"
+                    .to_string(),
+            )),
+        }
+    }
+
+    fn dialect(&self) -> String {
+        "bigquery".to_string()
+    }
+
+    /// Values are bound by position, so parameters are named after it:
+    /// `@p1`, `@p2` and so on.
+    fn bind(
+        &self,
+        query: &str,
+        params: &HashMap<String, ParamValue>,
+    ) -> Result<(String, Vec<ParamValue>), OnyxError> {
+        Ok(bind_params(query, params, |_, position| {
+            format!("@p{}", position)
+        }))
+    }
+
+    async fn load_database_info(&self) -> Result<DatabaseInfo, OnyxError> {
+        let query = BIGQUERY_COLUMNS_QUERY.replace("{dataset}", &self.dataset);
+        let (datasets, _) = self
+            .run_query_and_load(&query, &[], QueryOptions::default())
+            .await?;
+        Ok(DatabaseInfo {
+            name: self.dataset.to_string(),
+            dialect: "bigquery".to_string(),
+            tables: tables_from_columns(&datasets)?,
+        })
+    }
+}
+
+/// Postgres is queried through its own driver, so that parameters are bound
+/// instead of inlined, connections are pooled and kept for a whole script, and
+/// running statements can be cancelled.
+pub struct Postgres {
+    db_name: String,
+    pool: Pool,
+    tls: MakeTlsConnector,
+    result_store: ResultStore,
+    /// Schemas to introspect
+    schemas: Vec<String>,
+}
+
+/// The pool holds the connection settings including the password, so it is
+/// left out of debug output.
+impl fmt::Debug for Postgres {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Postgres")
+            .field("db_name", &self.db_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Postgres {
+    async fn new(
+        settings: &PostgresSettings,
+        pool_size: usize,
+        config_manager: &ConfigManager,
+        result_store: ResultStore,
+    ) -> Result<Self, OnyxError> {
+        let mut config = match secret_ref(
+            &settings.connection_string,
+            &settings.connection_string_file,
+        ) {
+            Some(reference) => {
+                let conn_string = strip_scheme(
+                    config_manager.resolve_secret(&reference).await?,
+                    &["postgres://", "postgresql://"],
+                );
+                // Errors do not echo the connection string, which includes the password
+                format!("postgresql://{}", conn_string)
+                    .parse::<tokio_postgres::Config>()
+                    .map_err(|err| {
+                        OnyxError::ConfigurationError(format!(
+                            "Invalid Postgres connection string: {}",
+                            err
+                        ))
+                    })?
+            }
+            None => {
+                let mut config = tokio_postgres::Config::new();
+                if let Some(host) = &settings.host {
+                    config.host(host);
+                }
+                if let Some(port) = &settings.port {
+                    config.port(port.parse().map_err(|_| {
+                        OnyxError::ConfigurationError(format!("Invalid Postgres port: {}", port))
+                    })?);
+                }
+                if let Some(user) = &settings.user {
+                    config.user(user);
+                }
+                let password =
+                    resolve_password(config_manager, &settings.password, &settings.password_file)
+                        .await?;
+                if !password.is_empty() {
+                    config.password(password);
+                }
+                if let Some(database) = &settings.database {
+                    config.dbname(database);
+                }
+                config
+            }
+        };
+        if let Some(sslmode) = &settings.sslmode {
+            config.ssl_mode(match sslmode {
+                PostgresSSLMode::Disable => tokio_postgres::config::SslMode::Disable,
+                PostgresSSLMode::Prefer => tokio_postgres::config::SslMode::Prefer,
+                _ => tokio_postgres::config::SslMode::Require,
+            });
+        }
+        if let Some(application_name) = &settings.application_name {
+            config.application_name(application_name);
+        }
+        if let Some(search_path) = &settings.search_path {
+            let options = config
+                .get_options()
+                .into_iter()
+                .map(|options| options.to_string())
+                .chain([format!("-c search_path={}", search_path_value(search_path))])
+                .collect::<Vec<String>>();
+            config.options(options.join(" "));
+        }
+
+        let sslrootcert = match &settings.sslrootcert {
+            Some(sslrootcert) => Some(config_manager.resolve_file(sslrootcert).await?),
+            None => None,
+        };
+        let tls = Self::tls_connector(settings.sslmode.as_ref(), sslrootcert.as_deref())
+            .map_err(|err| connector_internal_error(SETUP_POSTGRES_TLS, &err))?;
+        // Connections are opened on first use rather than here
+        let pool = Pool::builder(Manager::new(config, tls.clone()))
+            .max_size(pool_size)
+            .build()
+            .map_err(|err| connector_internal_error(CREATE_CONN, &err))?;
+        Ok(Postgres {
+            db_name: settings.database.clone().unwrap_or_default(),
+            pool,
+            tls,
+            result_store,
+            schemas: settings.schemas.clone(),
+        })
+    }
+
+    /// Verifies the server certificate like libpq does: against
+    /// `sslrootcert` with `verify-ca`, and its host name too with
+    /// `verify-full`. Other modes only encrypt the connection.
+    fn tls_connector(
+        sslmode: Option<&PostgresSSLMode>,
+        sslrootcert: Option<&str>,
+    ) -> Result<MakeTlsConnector, openssl::error::ErrorStack> {
+        let mut builder = SslConnector::builder(SslMethod::tls())?;
+        if let Some(sslrootcert) = sslrootcert {
+            builder.set_ca_file(sslrootcert)?;
+        }
+        let (verify_ca, verify_host) = match sslmode {
+            Some(PostgresSSLMode::VerifyFull) => (true, true),
+            Some(PostgresSSLMode::VerifyCa) => (true, false),
+            _ => (false, false),
+        };
+        if !verify_ca {
+            builder.set_verify(SslVerifyMode::NONE);
+        }
+        let mut connector = MakeTlsConnector::new(builder.build());
+        if !verify_host {
+            connector.set_callback(|config, _| {
+                config.set_verify_hostname(false);
+                Ok(())
+            });
+        }
+        Ok(connector)
+    }
+
+    /// Runs `f` on a pooled connection set up for `options`, cancelling the
+    /// running statement on timeout. The session is reset before the
+    /// connection goes back to the pool, so that settings, temporary tables
+    /// or an open transaction do not leak into later queries.
+    async fn with_session<T: Send + 'static>(
+        &self,
+        options: QueryOptions,
+        f: impl for<'a> FnOnce(&'a tokio_postgres::Client) -> BoxFuture<'a, Result<T, OnyxError>>
+            + Send
+            + 'static,
+    ) -> Result<T, OnyxError> {
+        let pool = self.pool.clone();
+        let cancel_token = Arc::new(Mutex::new(None));
+        // Spawned, so that the connection stays busy and is reset once the
+        // query ends even if we stopped waiting for it
+        let task = tokio::spawn({
+            let cancel_token = cancel_token.clone();
+            async move {
+                let client = pool
+                    .get()
+                    .await
+                    .map_err(|err| connector_internal_error(CREATE_CONN, &err))?;
+                if let Ok(mut cancel_token) = cancel_token.lock() {
+                    *cancel_token = Some(client.cancel_token());
+                }
+                client
+                    .batch_execute(&format!(
+                        "SET statement_timeout = {}; SET default_transaction_read_only = {}",
+                        options.timeout.map(|t| t.as_millis()).unwrap_or(0),
+                        if options.read_only { "on" } else { "off" }
+                    ))
+                    .await
+                    .map_err(|err| connector_internal_error(CREATE_CONN, &err))?;
+                let result = f(&client).await;
+                if let Ok(mut cancel_token) = cancel_token.lock() {
+                    cancel_token.take();
+                }
+                // Sent as two queries, as Postgres runs a multi-statement query
+                // in one transaction and DISCARD ALL fails inside of one.
+                // ROLLBACK only warns when no transaction is open.
+                for reset in ["ROLLBACK", "DISCARD ALL"] {
+                    if let Err(err) = client.batch_execute(reset).await {
+                        log::warn!("Failed to reset Postgres session: {}", err);
+                    }
+                }
+                Ok::<T, anyhow::Error>(result?)
+            }
+        });
+
+        let tls = self.tls.clone();
+        with_timeout(options.timeout, task, move || {
+            let cancel_token = cancel_token.lock().ok().and_then(|mut token| token.take());
+            if let Some(cancel_token) = cancel_token {
+                tokio::spawn(async move {
+                    if let Err(err) = cancel_token.cancel_query(tls).await {
+                        log::warn!("Failed to cancel Postgres query: {}", err);
+                    }
+                });
+            }
+        })
+        .await
+    }
+
+    /// Runs `query` with `params` bound to its `$n` placeholders.
+    async fn query(
+        client: &tokio_postgres::Client,
+        query: &str,
+        params: &[ParamValue],
+    ) -> Result<(RecordBatch, SchemaRef), OnyxError> {
+        let types = params.iter().map(postgres_type).collect::<Vec<Type>>();
+        let statement = client
+            .prepare_typed(query, &types)
+            .await
+            .map_err(|err| connector_internal_error(PREPARE_POSTGRES_STMT, &err))?;
+        // Checked before running it, as the statement may have side effects
+        if let Some(column) = statement
+            .columns()
+            .iter()
+            .find(|column| !postgres_loadable(column.type_()))
+        {
+            return Err(connector_internal_error(
+                CAST_POSTGRES_COLUMNS,
+                &format!("column {} has type {}", column.name(), column.type_()),
+            ));
+        }
+
+        let values = params.iter().map(postgres_value).collect::<Vec<_>>();
+        let values = values
             .iter()
-            .flat_map(|batch| as_string_array(batch.column(0)).iter());
-        Ok(result_iter
-            .map(|name| name.map(|s| s.to_string()))
-            .collect::<Option<Vec<String>>>()
-            .unwrap_or_default())
+            .map(|value| value.as_ref() as &(dyn ToSql + Sync))
+            .collect::<Vec<_>>();
+        let rows = client
+            .query(&statement, &values)
+            .await
+            .map_err(|err| connector_internal_error(EXECUTE_QUERY, &err))?;
+
+        let mut fields = vec![];
+        let mut arrays = vec![];
+        for (idx, column) in statement.columns().iter().enumerate() {
+            let array = postgres_column(&rows, idx, column.type_()).map_err(|err| {
+                connector_internal_error(
+                    CAST_POSTGRES_COLUMNS,
+                    &format!("column {}: {}", column.name(), err),
+                )
+            })?;
+            fields.push(Field::new(column.name(), array.data_type().clone(), true));
+            arrays.push(array);
+        }
+        let schema = Arc::new(Schema::new(fields));
+        let batch = RecordBatch::try_new_with_options(
+            schema.clone(),
+            arrays,
+            &RecordBatchOptions::new().with_row_count(Some(rows.len())),
+        )
+        .map_err(|err| connector_internal_error(LOAD_ARROW_RESULT, &err))?;
+        Ok((batch, schema))
     }
 }
 
-impl Engine for ConnectorX {
-    async fn run_query(&self, query: &str) -> Result<String, OnyxError> {
-        let conn_string = format!("{}://{}", self.dialect, self.db_path);
+impl Engine for Postgres {
+    async fn run_query(
+        &self,
+        query: &str,
+        params: &[ParamValue],
+        options: QueryOptions,
+    ) -> Result<String, OnyxError> {
         let query = query.to_string();
-        let result = tokio::task::spawn_blocking(move || {
-            let source_conn = SourceConn::try_from(conn_string.as_str())
-                .map_err(|err| connector_internal_error(CREATE_CONN, &err))?;
-            let queries = &[CXQuery::from(query.as_str())];
-            let destination = get_arrow(&source_conn, None, queries, None)
-                .map_err(|err| connector_internal_error(EXECUTE_QUERY, &err))?;
-            let schema = destination.arrow_schema();
-            let file_path = format!("/tmp/{}.arrow", Uuid::new_v4());
-            let result = destination
-                .arrow()
-                .map_err(|err| connector_internal_error(LOAD_ARROW_RESULT, &err))?;
+        let params = params.to_vec();
+        let result_store = self.result_store.clone();
+        self.with_session(options, move |client| {
+            Box::pin(async move {
+                let (batch, schema) = Self::query(client, &query, &params).await?;
+                result_store
+                    .write(&[batch], &schema)
+                    .map_err(|err| connector_internal_error(WRITE_RESULT, &err))
+            })
+        })
+        .await
+    }
 
-            write_to_ipc(&result, &file_path, &schema)
-                .map_err(|err| connector_internal_error(WRITE_RESULT, &err))?;
-            Ok::<String, anyhow::Error>(file_path)
+    async fn run_statements(
+        &self,
+        statements: &[(String, Vec<ParamValue>)],
+        options: QueryOptions,
+        all: bool,
+    ) -> Result<Vec<String>, OnyxError> {
+        let statements = statements.to_vec();
+        let result_store = self.result_store.clone();
+        self.with_session(options, move |client| {
+            Box::pin(async move {
+                let mut file_paths = vec![];
+                let last = statements.len().saturating_sub(1);
+                for (idx, (query, params)) in statements.iter().enumerate() {
+                    let (batch, schema) = Self::query(client, query, params).await?;
+                    if all || idx == last {
+                        file_paths.push(
+                            result_store
+                                .write(&[batch], &schema)
+                                .map_err(|err| connector_internal_error(WRITE_RESULT, &err))?,
+                        );
+                    }
+                }
+                Ok(file_paths)
+            })
         })
         .await
-        .map_err(|e| connector_internal_error(FAILED_TO_RUN_BLOCKING_TASK, &e))??;
+    }
 
-        Ok(result)
+    fn dialect(&self) -> String {
+        "postgres".to_string()
+    }
+
+    fn bind(
+        &self,
+        query: &str,
+        params: &HashMap<String, ParamValue>,
+    ) -> Result<(String, Vec<ParamValue>), OnyxError> {
+        Ok(bind_params(query, params, |_, position| {
+            format!("${}", position)
+        }))
     }
 
     async fn load_database_info(&self) -> Result<DatabaseInfo, OnyxError> {
+        let schemas = self
+            .schemas
+            .iter()
+            .map(|schema| format!("'{}'", schema.replace('\'', "''")))
+            .collect::<Vec<String>>();
+        let query = POSTGRES_COLUMNS_QUERY.replace("{schemas}", &schemas.join(", "));
+        let (datasets, _) = self
+            .run_query_and_load(&query, &[], QueryOptions::default())
+            .await?;
         Ok(DatabaseInfo {
             name: self.db_name.to_string(),
-            dialect: self.dialect.to_string(),
-            tables: self.get_schemas().await?,
+            dialect: "postgres".to_string(),
+            tables: tables_from_columns(&datasets)?,
         })
     }
 }
 
 #[derive(Debug)]
+struct SQLite {
+    db_path: String,
+    result_store: ResultStore,
+}
+
+impl SQLite {
+    /// Opens the database file without creating it, so that a mistyped path
+    /// fails rather than connecting to a new empty database.
+    fn open_connection(&self, read_only: bool) -> Result<rusqlite::Connection, OnyxError> {
+        let mut flags = OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        flags |= if read_only {
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+        } else {
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+        };
+        rusqlite::Connection::open_with_flags(&self.db_path, flags).map_err(|err| {
+            if Path::new(&self.db_path).exists() {
+                connector_internal_error(CREATE_CONN, &err)
+            } else {
+                OnyxError::DBError(format!("SQLite database file not found: {}", self.db_path))
+            }
+        })
+    }
+
+    fn query(
+        conn: &rusqlite::Connection,
+        query: &str,
+        params: Vec<SQLiteValue>,
+    ) -> Result<(RecordBatch, SchemaRef), OnyxError> {
+        let mut stmt = conn
+            .prepare(query)
+            .map_err(|err| connector_internal_error(PREPARE_SQLITE_STMT, &err))?;
+        let column_names = stmt
+            .column_names()
+            .into_iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<String>>();
+        let mut columns: Vec<Vec<SQLiteValue>> = vec![vec![]; column_names.len()];
+        let mut num_rows = 0;
+        let mut rows = stmt
+            .query(rusqlite::params_from_iter(params))
+            .map_err(|err| connector_internal_error(EXECUTE_QUERY, &err))?;
+        while let Some(row) = rows
+            .next()
+            .map_err(|err| connector_internal_error(EXECUTE_QUERY, &err))?
+        {
+            for (idx, column) in columns.iter_mut().enumerate() {
+                let value = row
+                    .get::<_, SQLiteValue>(idx)
+                    .map_err(|err| connector_internal_error(EXECUTE_QUERY, &err))?;
+                column.push(value);
+            }
+            num_rows += 1;
+        }
+        sqlite_columns_to_record_batch(column_names, columns, num_rows)
+            .map_err(|err| connector_internal_error(LOAD_ARROW_RESULT, &err))
+    }
+}
+
+impl Engine for SQLite {
+    async fn run_query(
+        &self,
+        query: &str,
+        params: &[ParamValue],
+        options: QueryOptions,
+    ) -> Result<String, OnyxError> {
+        let query = query.to_string();
+        let params = params.iter().map(sqlite_value).collect::<Vec<_>>();
+        let result_store = self.result_store.clone();
+        let conn = self.open_connection(options.read_only)?;
+        let interrupt_handle = conn.get_interrupt_handle();
+        let task = tokio::task::spawn_blocking(move || {
+            let (batch, schema) = Self::query(&conn, &query, params)?;
+            let file_path = result_store
+                .write(&[batch], &schema)
+                .map_err(|err| connector_internal_error(WRITE_RESULT, &err))?;
+            Ok::<String, anyhow::Error>(file_path)
+        });
+
+        with_timeout(options.timeout, task, || interrupt_handle.interrupt()).await
+    }
+
+    async fn run_statements(
+        &self,
+        statements: &[(String, Vec<ParamValue>)],
+        options: QueryOptions,
+        all: bool,
+    ) -> Result<Vec<String>, OnyxError> {
+        let statements = statements
+            .iter()
+            .map(|(query, params)| (query.clone(), params.iter().map(sqlite_value).collect()))
+            .collect::<Vec<(String, Vec<_>)>>();
+        let result_store = self.result_store.clone();
+        let conn = self.open_connection(options.read_only)?;
+        let interrupt_handle = conn.get_interrupt_handle();
+        let task = tokio::task::spawn_blocking(move || {
+            let mut file_paths = vec![];
+            let last = statements.len().saturating_sub(1);
+            for (idx, (query, params)) in statements.into_iter().enumerate() {
+                let (batch, schema) = Self::query(&conn, &query, params)?;
+                if all || idx == last {
+                    file_paths.push(
+                        result_store
+                            .write(&[batch], &schema)
+                            .map_err(|err| connector_internal_error(WRITE_RESULT, &err))?,
+                    );
+                }
+            }
+            Ok::<Vec<String>, anyhow::Error>(file_paths)
+        });
+
+        with_timeout(options.timeout, task, || interrupt_handle.interrupt()).await
+    }
+
+    async fn load_database_info(&self) -> Result<DatabaseInfo, OnyxError> {
+        let (datasets, _) = self
+            .run_query_and_load(SQLITE_COLUMNS_QUERY, &[], QueryOptions::default())
+            .await?;
+        Ok(DatabaseInfo {
+            name: self.db_path.to_string(),
+            dialect: "sqlite".to_string(),
+            tables: tables_from_columns(&datasets)?,
+        })
+    }
+
+    fn dialect(&self) -> String {
+        "sqlite".to_string()
+    }
+
+    fn explain_query(&self, query: &str) -> String {
+        format!("EXPLAIN QUERY PLAN {}", query)
+    }
+}
+
+fn duckdb_value(value: &ParamValue) -> duckdb::types::Value {
+    use duckdb::types::{TimeUnit, Value};
+    match value {
+        ParamValue::String(value) => Value::Text(value.clone()),
+        ParamValue::Integer(value) => Value::BigInt(*value),
+        ParamValue::Float(value) => Value::Double(*value),
+        ParamValue::Boolean(value) => Value::Boolean(*value),
+        // Days since 1970-01-01, which is the default NaiveDate
+        ParamValue::Date(value) => {
+            Value::Date32((*value - chrono::NaiveDate::default()).num_days() as i32)
+        }
+        ParamValue::Timestamp(value) => {
+            Value::Timestamp(TimeUnit::Microsecond, value.and_utc().timestamp_micros())
+        }
+    }
+}
+
+/// Parameters are declared with their type when the statement is prepared,
+/// so that e.g. a string is never taken for a number.
+fn postgres_type(value: &ParamValue) -> Type {
+    match value {
+        ParamValue::String(_) => Type::TEXT,
+        ParamValue::Integer(_) => Type::INT8,
+        ParamValue::Float(_) => Type::FLOAT8,
+        ParamValue::Boolean(_) => Type::BOOL,
+        ParamValue::Date(_) => Type::DATE,
+        ParamValue::Timestamp(_) => Type::TIMESTAMP,
+    }
+}
+
+fn postgres_value(value: &ParamValue) -> Box<dyn ToSql + Sync + Send> {
+    match value {
+        ParamValue::String(value) => Box::new(value.clone()),
+        ParamValue::Integer(value) => Box::new(*value),
+        ParamValue::Float(value) => Box::new(*value),
+        ParamValue::Boolean(value) => Box::new(*value),
+        ParamValue::Date(value) => Box::new(*value),
+        ParamValue::Timestamp(value) => Box::new(*value),
+    }
+}
+
+/// Arrow type of the Postgres types that are loaded as is.
+fn postgres_arrow_type(pg_type: &Type) -> Option<DataType> {
+    let data_type = match *pg_type {
+        Type::BOOL => DataType::Boolean,
+        Type::INT2 => DataType::Int16,
+        Type::INT4 => DataType::Int32,
+        Type::INT8 => DataType::Int64,
+        Type::OID => DataType::Int64,
+        Type::FLOAT4 => DataType::Float32,
+        Type::FLOAT8 => DataType::Float64,
+        Type::DATE => DataType::Date32,
+        Type::TIME => DataType::Time64(TimeUnit::Microsecond),
+        Type::TIMESTAMP => DataType::Timestamp(TimeUnit::Microsecond, None),
+        Type::TIMESTAMPTZ => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        Type::INTERVAL => DataType::Interval(IntervalUnit::MonthDayNano),
+        Type::BYTEA => DataType::Binary,
+        _ if <String as FromSql>::accepts(pg_type) => DataType::Utf8,
+        _ => return None,
+    };
+    Some(data_type)
+}
+
+/// Whether `postgres_column` can load columns of `pg_type`.
+fn postgres_loadable(pg_type: &Type) -> bool {
+    match pg_type.kind() {
+        Kind::Domain(base_type) => postgres_loadable(base_type),
+        Kind::Enum(_) => true,
+        _ => {
+            postgres_arrow_type(pg_type).is_some()
+                || [Type::JSON, Type::JSONB, Type::UUID, Type::NUMERIC].contains(pg_type)
+        }
+    }
+}
+
+/// The binary value of a column, for the types decoded here rather than by
+/// tokio-postgres.
+struct PostgresRawValue<'a>(&'a [u8]);
+
+impl<'a> FromSql<'a> for PostgresRawValue<'a> {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        Ok(PostgresRawValue(raw))
+    }
+
+    fn accepts(_: &Type) -> bool {
+        true
+    }
+}
+
+/// Loads column `idx` of `rows`. Types arrow has no equivalent for are
+/// converted: numerics to decimals, or to text when a value does not fit in
+/// one, and UUIDs, JSON and enums to text. Other types, e.g. arrays, must be
+/// cast in the query.
+fn postgres_column(
+    rows: &[tokio_postgres::Row],
+    idx: usize,
+    pg_type: &Type,
+) -> Result<ArrayRef, Box<dyn Error + Sync + Send>> {
+    fn values<'a, T: FromSql<'a>>(
+        rows: &'a [tokio_postgres::Row],
+        idx: usize,
+    ) -> Result<Vec<Option<T>>, tokio_postgres::Error> {
+        rows.iter().map(|row| row.try_get(idx)).collect()
+    }
+    fn text(
+        rows: &[tokio_postgres::Row],
+        idx: usize,
+        decode: fn(&[u8]) -> Result<String, Box<dyn Error + Sync + Send>>,
+    ) -> Result<Vec<Option<String>>, Box<dyn Error + Sync + Send>> {
+        values::<PostgresRawValue>(rows, idx)?
+            .into_iter()
+            .map(|value| value.map(|PostgresRawValue(raw)| decode(raw)).transpose())
+            .collect()
+    }
+    fn utf8(raw: &[u8]) -> Result<String, Box<dyn Error + Sync + Send>> {
+        Ok(std::str::from_utf8(raw)?.to_string())
+    }
+
+    let Some(data_type) = postgres_arrow_type(pg_type) else {
+        let array: ArrayRef = match (pg_type.kind(), pg_type) {
+            (Kind::Domain(base_type), _) => return postgres_column(rows, idx, base_type),
+            (Kind::Enum(_), _) | (_, &Type::JSON) => {
+                Arc::new(StringArray::from(text(rows, idx, utf8)?))
+            }
+            // Binary JSONB starts with a version number
+            (_, &Type::JSONB) => Arc::new(StringArray::from(text(rows, idx, |raw| {
+                utf8(raw.get(1..).unwrap_or_default())
+            })?)),
+            (_, &Type::UUID) => Arc::new(StringArray::from(text(rows, idx, |raw| {
+                let hex = raw.iter().map(|byte| format!("{:02x}", byte));
+                let hex = hex.collect::<String>();
+                match hex.len() {
+                    32 => Ok(format!(
+                        "{}-{}-{}-{}-{}",
+                        &hex[..8],
+                        &hex[8..12],
+                        &hex[12..16],
+                        &hex[16..20],
+                        &hex[20..]
+                    )),
+                    _ => Err("invalid UUID".into()),
+                }
+            })?)),
+            (_, &Type::NUMERIC) => decimal_column(text(rows, idx, postgres_numeric)?)?,
+            _ => return Err(format!("unsupported type {}", pg_type).into()),
+        };
+        return Ok(array);
+    };
+    let array: ArrayRef = match data_type {
+        DataType::Boolean => Arc::new(BooleanArray::from(values::<bool>(rows, idx)?)),
+        DataType::Int16 => Arc::new(Int16Array::from(values::<i16>(rows, idx)?)),
+        DataType::Int32 => Arc::new(Int32Array::from(values::<i32>(rows, idx)?)),
+        DataType::Int64 if *pg_type == Type::OID => Arc::new(Int64Array::from(
+            values::<u32>(rows, idx)?
+                .into_iter()
+                .map(|oid| oid.map(i64::from))
+                .collect::<Vec<_>>(),
+        )),
+        DataType::Int64 => Arc::new(Int64Array::from(values::<i64>(rows, idx)?)),
+        DataType::Float32 => Arc::new(Float32Array::from(values::<f32>(rows, idx)?)),
+        DataType::Float64 => Arc::new(Float64Array::from(values::<f64>(rows, idx)?)),
+        // Days since 1970-01-01, which is the default NaiveDate
+        DataType::Date32 => Arc::new(Date32Array::from(
+            values::<NaiveDate>(rows, idx)?
+                .into_iter()
+                .map(|date| date.map(|date| (date - NaiveDate::default()).num_days() as i32))
+                .collect::<Vec<_>>(),
+        )),
+        DataType::Time64(_) => Arc::new(Time64MicrosecondArray::from(
+            values::<NaiveTime>(rows, idx)?
+                .into_iter()
+                .map(|time| time.and_then(|time| (time - NaiveTime::MIN).num_microseconds()))
+                .collect::<Vec<_>>(),
+        )),
+        DataType::Timestamp(_, None) => Arc::new(TimestampMicrosecondArray::from(
+            values::<NaiveDateTime>(rows, idx)?
+                .into_iter()
+                .map(|timestamp| timestamp.map(|timestamp| timestamp.and_utc().timestamp_micros()))
+                .collect::<Vec<_>>(),
+        )),
+        DataType::Timestamp(_, Some(timezone)) => Arc::new(
+            TimestampMicrosecondArray::from(
+                values::<DateTime<Utc>>(rows, idx)?
+                    .into_iter()
+                    .map(|timestamp| timestamp.map(|timestamp| timestamp.timestamp_micros()))
+                    .collect::<Vec<_>>(),
+            )
+            .with_timezone(timezone),
+        ),
+        // Microseconds, days and months
+        DataType::Interval(_) => Arc::new(IntervalMonthDayNanoArray::from(
+            values::<PostgresRawValue>(rows, idx)?
+                .into_iter()
+                .map(|value| {
+                    value
+                        .map(|PostgresRawValue(raw)| {
+                            let field = |range: std::ops::Range<usize>| {
+                                raw.get(range).ok_or("invalid interval")
+                            };
+                            let micros = i64::from_be_bytes(field(0..8)?.try_into()?);
+                            let days = i32::from_be_bytes(field(8..12)?.try_into()?);
+                            let months = i32::from_be_bytes(field(12..16)?.try_into()?);
+                            Ok::<_, Box<dyn Error + Sync + Send>>(IntervalMonthDayNano::new(
+                                months,
+                                days,
+                                micros * 1000,
+                            ))
+                        })
+                        .transpose()
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        DataType::Binary => Arc::new(BinaryArray::from(values::<&[u8]>(rows, idx)?)),
+        _ => Arc::new(StringArray::from(values::<String>(rows, idx)?)),
+    };
+    Ok(array)
+}
+
+/// Decodes a binary Postgres numeric into its text form: a sign, a count of
+/// base 10000 digits, the weight of the first one and the number of decimal
+/// digits to show, followed by the digits.
+fn postgres_numeric(raw: &[u8]) -> Result<String, Box<dyn Error + Sync + Send>> {
+    let word = |idx: usize| {
+        raw.get(idx * 2..idx * 2 + 2)
+            .map(|word| u16::from_be_bytes([word[0], word[1]]))
+            .ok_or("invalid numeric")
+    };
+    let ndigits = word(0)? as usize;
+    let weight = word(1)? as i16 as i64;
+    let sign = word(2)?;
+    let dscale = word(3)? as usize;
+    match sign {
+        0xC000 => return Ok("NaN".to_string()),
+        0xD000 => return Ok("Infinity".to_string()),
+        0xF000 => return Ok("-Infinity".to_string()),
+        _ => {}
+    }
+    let digits = (0..ndigits)
+        .map(|idx| word(4 + idx))
+        .collect::<Result<Vec<u16>, _>>()?;
+    let digit = |idx: i64| {
+        usize::try_from(idx)
+            .ok()
+            .and_then(|idx| digits.get(idx).copied())
+            .unwrap_or(0)
+    };
+    let mut value = if sign == 0x4000 { "-" } else { "" }.to_string();
+    if weight < 0 {
+        value.push('0');
+    } else {
+        value.push_str(&digit(0).to_string());
+        for idx in 1..=weight {
+            value.push_str(&format!("{:04}", digit(idx)));
+        }
+    }
+    if dscale > 0 {
+        let mut fraction = String::new();
+        let mut idx = weight + 1;
+        while fraction.len() < dscale {
+            fraction.push_str(&format!("{:04}", digit(idx)));
+            idx += 1;
+        }
+        fraction.truncate(dscale);
+        value.push('.');
+        value.push_str(&fraction);
+    }
+    Ok(value)
+}
+
+/// Loads decimals given as text into a `Decimal128` column, scaled to the
+/// value with the most decimal digits, or into a text column when a value is
+/// not finite or does not fit in 38 digits.
+fn decimal_column(values: Vec<Option<String>>) -> Result<ArrayRef, ArrowError> {
+    let scale = values
+        .iter()
+        .flatten()
+        .map(|value| {
+            value
+                .split_once('.')
+                .map_or(0, |(_, fraction)| fraction.len())
+        })
+        .max()
+        .unwrap_or(0);
+    let decimals = values
+        .iter()
+        .map(|value| match value {
+            Some(value) => decimal_mantissa(value, scale).map(Some),
+            None => Some(None),
+        })
+        .collect::<Option<Vec<Option<i128>>>>();
+    match decimals {
+        Some(decimals) if scale <= DECIMAL128_MAX_PRECISION as usize => Ok(Arc::new(
+            Decimal128Array::from(decimals)
+                .with_precision_and_scale(DECIMAL128_MAX_PRECISION, scale as i8)?,
+        )),
+        _ => Ok(Arc::new(StringArray::from(values))),
+    }
+}
+
+/// The digits of a decimal scaled to `scale` decimal digits, if it fits in a
+/// `Decimal128`.
+fn decimal_mantissa(value: &str, scale: usize) -> Option<i128> {
+    let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let (sign, integer) = match integer.strip_prefix('-') {
+        Some(integer) => ("-", integer),
+        None => ("", integer),
+    };
+    let digits = format!(
+        "{}{}{}",
+        integer,
+        fraction,
+        "0".repeat(scale - fraction.len())
+    );
+    let digits = digits.trim_start_matches('0');
+    if digits.len() > DECIMAL128_MAX_PRECISION as usize
+        || !digits.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    format!("{}0{}", sign, digits).parse().ok()
+}
+
+/// SQLite has no date or boolean storage classes, so those are bound the way
+/// its date functions and comparisons expect them.
+fn sqlite_value(value: &ParamValue) -> SQLiteValue {
+    match value {
+        ParamValue::String(value) => SQLiteValue::Text(value.clone()),
+        ParamValue::Integer(value) => SQLiteValue::Integer(*value),
+        ParamValue::Float(value) => SQLiteValue::Real(*value),
+        ParamValue::Boolean(value) => SQLiteValue::Integer(*value as i64),
+        ParamValue::Date(value) => SQLiteValue::Text(value.format("%Y-%m-%d").to_string()),
+        ParamValue::Timestamp(value) => {
+            SQLiteValue::Text(value.format("%Y-%m-%d %H:%M:%S%.f").to_string())
+        }
+    }
+}
+
+/// SQLite columns are dynamically typed, so the arrow type of each column is
+/// inferred from the values it holds: integers stay integers, mixed numerics
+/// become floats, blobs stay binary and anything else falls back to text.
+fn sqlite_columns_to_record_batch(
+    column_names: Vec<String>,
+    columns: Vec<Vec<SQLiteValue>>,
+    num_rows: usize,
+) -> Result<(RecordBatch, SchemaRef), ArrowError> {
+    let mut fields = vec![];
+    let mut arrays: Vec<ArrayRef> = vec![];
+    for (name, values) in column_names.into_iter().zip(columns) {
+        let non_null = values
+            .iter()
+            .filter(|value| !matches!(value, SQLiteValue::Null));
+        let (data_type, array): (DataType, ArrayRef) = if non_null.clone().next().is_none() {
+            (
+                DataType::Utf8,
+                Arc::new(StringArray::new_null(values.len())),
+            )
+        } else if non_null
+            .clone()
+            .all(|value| matches!(value, SQLiteValue::Integer(_)))
+        {
+            let array = values
+                .iter()
+                .map(|value| match value {
+                    SQLiteValue::Integer(i) => Some(*i),
+                    _ => None,
+                })
+                .collect::<Int64Array>();
+            (DataType::Int64, Arc::new(array))
+        } else if non_null
+            .clone()
+            .all(|value| matches!(value, SQLiteValue::Integer(_) | SQLiteValue::Real(_)))
+        {
+            let array = values
+                .iter()
+                .map(|value| match value {
+                    SQLiteValue::Integer(i) => Some(*i as f64),
+                    SQLiteValue::Real(f) => Some(*f),
+                    _ => None,
+                })
+                .collect::<Float64Array>();
+            (DataType::Float64, Arc::new(array))
+        } else if non_null
+            .clone()
+            .all(|value| matches!(value, SQLiteValue::Blob(_)))
+        {
+            let array = values
+                .iter()
+                .map(|value| match value {
+                    SQLiteValue::Blob(b) => Some(b.as_slice()),
+                    _ => None,
+                })
+                .collect::<BinaryArray>();
+            (DataType::Binary, Arc::new(array))
+        } else {
+            let array = values
+                .iter()
+                .map(|value| match value {
+                    SQLiteValue::Null => None,
+                    SQLiteValue::Integer(i) => Some(i.to_string()),
+                    SQLiteValue::Real(f) => Some(f.to_string()),
+                    SQLiteValue::Text(t) => Some(t.to_string()),
+                    SQLiteValue::Blob(b) => Some(String::from_utf8_lossy(b).to_string()),
+                })
+                .collect::<StringArray>();
+            (DataType::Utf8, Arc::new(array))
+        };
+        fields.push(Field::new(name, data_type, true));
+        arrays.push(array);
+    }
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new_with_options(
+        schema.clone(),
+        arrays,
+        &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+    )?;
+    Ok((batch, schema))
+}
+
+/// MySQL has no boolean type, so booleans are bound as integers, the way
+/// `TRUE` and `FALSE` compare.
+fn mysql_value(value: &ParamValue) -> MySQLValue {
+    match value {
+        ParamValue::String(value) => MySQLValue::Bytes(value.as_bytes().to_vec()),
+        ParamValue::Integer(value) => MySQLValue::Int(*value),
+        ParamValue::Float(value) => MySQLValue::Double(*value),
+        ParamValue::Boolean(value) => MySQLValue::Int(*value as i64),
+        ParamValue::Date(value) => MySQLValue::Date(
+            value.year() as u16,
+            value.month() as u8,
+            value.day() as u8,
+            0,
+            0,
+            0,
+            0,
+        ),
+        ParamValue::Timestamp(value) => MySQLValue::Date(
+            value.year() as u16,
+            value.month() as u8,
+            value.day() as u8,
+            value.hour() as u8,
+            value.minute() as u8,
+            value.second() as u8,
+            value.nanosecond() / 1000,
+        ),
+    }
+}
+
+fn mysql_text(value: MySQLValue) -> Option<String> {
+    match value {
+        MySQLValue::NULL => None,
+        MySQLValue::Bytes(bytes) => Some(String::from_utf8_lossy(&bytes).to_string()),
+        MySQLValue::Int(i) => Some(i.to_string()),
+        MySQLValue::UInt(u) => Some(u.to_string()),
+        MySQLValue::Float(f) => Some(f.to_string()),
+        MySQLValue::Double(f) => Some(f.to_string()),
+        MySQLValue::Date(year, month, day, hour, minute, second, micros) => Some(format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06}",
+            year, month, day, hour, minute, second, micros
+        )),
+        // Durations, which may be negative or span several days
+        MySQLValue::Time(negative, days, hours, minutes, seconds, micros) => Some(format!(
+            "{}{:02}:{:02}:{:02}.{:06}",
+            if negative { "-" } else { "" },
+            days * 24 + u32::from(hours),
+            minutes,
+            seconds,
+            micros
+        )),
+    }
+}
+
+/// Loads a column read through the MySQL binary protocol, which sends
+/// numbers and dates typed and anything else as bytes. Decimals keep their
+/// precision, binary strings stay binary and types arrow has no counterpart
+/// for, like `TIME` durations or `JSON`, are loaded as text.
+fn mysql_column(column: &mysql::Column, values: Vec<MySQLValue>) -> Result<ArrayRef, ArrowError> {
+    use mysql::consts::ColumnType::*;
+    // The character set of binary strings and blobs
+    const BINARY_CHARSET: u16 = 63;
+    let datetime = |value: &MySQLValue| match *value {
+        MySQLValue::Date(year, month, day, hour, minute, second, micros) => {
+            NaiveDate::from_ymd_opt(year.into(), month.into(), day.into())?.and_hms_micro_opt(
+                hour.into(),
+                minute.into(),
+                second.into(),
+                micros,
+            )
+        }
+        _ => None,
+    };
+    let binary = |values: &[MySQLValue]| -> ArrayRef {
+        Arc::new(BinaryArray::from(
+            values
+                .iter()
+                .map(|value| match value {
+                    MySQLValue::Bytes(bytes) => Some(bytes.as_slice()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+        ))
+    };
+    let array: ArrayRef = match column.column_type() {
+        MYSQL_TYPE_LONGLONG
+            if column
+                .flags()
+                .contains(mysql::consts::ColumnFlags::UNSIGNED_FLAG) =>
+        {
+            Arc::new(UInt64Array::from(
+                values
+                    .iter()
+                    .map(|value| match *value {
+                        MySQLValue::Int(i) => u64::try_from(i).ok(),
+                        MySQLValue::UInt(u) => Some(u),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>(),
+            ))
+        }
+        MYSQL_TYPE_TINY | MYSQL_TYPE_SHORT | MYSQL_TYPE_INT24 | MYSQL_TYPE_LONG
+        | MYSQL_TYPE_LONGLONG | MYSQL_TYPE_YEAR => Arc::new(Int64Array::from(
+            values
+                .iter()
+                .map(|value| match *value {
+                    MySQLValue::Int(i) => Some(i),
+                    MySQLValue::UInt(u) => i64::try_from(u).ok(),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+        )),
+        MYSQL_TYPE_FLOAT | MYSQL_TYPE_DOUBLE => Arc::new(Float64Array::from(
+            values
+                .iter()
+                .map(|value| match *value {
+                    MySQLValue::Float(f) => Some(f64::from(f)),
+                    MySQLValue::Double(f) => Some(f),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+        )),
+        MYSQL_TYPE_DECIMAL | MYSQL_TYPE_NEWDECIMAL => {
+            decimal_column(values.into_iter().map(mysql_text).collect())?
+        }
+        // Days since 1970-01-01, which is the default NaiveDate
+        MYSQL_TYPE_DATE | MYSQL_TYPE_NEWDATE => Arc::new(Date32Array::from(
+            values
+                .iter()
+                .map(|value| {
+                    datetime(value)
+                        .map(|date| (date.date() - NaiveDate::default()).num_days() as i32)
+                })
+                .collect::<Vec<_>>(),
+        )),
+        MYSQL_TYPE_DATETIME
+        | MYSQL_TYPE_DATETIME2
+        | MYSQL_TYPE_TIMESTAMP
+        | MYSQL_TYPE_TIMESTAMP2 => Arc::new(TimestampMicrosecondArray::from(
+            values
+                .iter()
+                .map(|value| {
+                    datetime(value).map(|timestamp| timestamp.and_utc().timestamp_micros())
+                })
+                .collect::<Vec<_>>(),
+        )),
+        MYSQL_TYPE_BIT | MYSQL_TYPE_GEOMETRY => binary(&values),
+        MYSQL_TYPE_VARCHAR
+        | MYSQL_TYPE_VAR_STRING
+        | MYSQL_TYPE_STRING
+        | MYSQL_TYPE_TINY_BLOB
+        | MYSQL_TYPE_MEDIUM_BLOB
+        | MYSQL_TYPE_LONG_BLOB
+        | MYSQL_TYPE_BLOB
+            if column.character_set() == BINARY_CHARSET =>
+        {
+            binary(&values)
+        }
+        _ => Arc::new(StringArray::from(
+            values.into_iter().map(mysql_text).collect::<Vec<_>>(),
+        )),
+    };
+    Ok(array)
+}
+
+fn bigquery_error(message: &str, err: BQError) -> OnyxError {
+    match err {
+        BQError::ResponseError { error } => connector_internal_error(message, &error.error.message),
+        err => connector_internal_error(message, &err),
+    }
+}
+
+/// The query parameter bound to the `@p<position>` placeholder.
+fn bigquery_parameter(position: usize, value: &ParamValue) -> QueryParameter {
+    let (parameter_type, value) = match value {
+        ParamValue::String(value) => ("STRING", value.clone()),
+        ParamValue::Integer(value) => ("INT64", value.to_string()),
+        ParamValue::Float(value) => ("FLOAT64", value.to_string()),
+        ParamValue::Boolean(value) => ("BOOL", value.to_string()),
+        ParamValue::Date(value) => ("DATE", value.format("%Y-%m-%d").to_string()),
+        ParamValue::Timestamp(value) => {
+            ("DATETIME", value.format("%Y-%m-%d %H:%M:%S%.f").to_string())
+        }
+    };
+    QueryParameter {
+        name: Some(format!("p{}", position)),
+        parameter_type: Some(QueryParameterType {
+            r#type: parameter_type.to_string(),
+            ..Default::default()
+        }),
+        parameter_value: Some(QueryParameterValue {
+            value: Some(value),
+            ..Default::default()
+        }),
+    }
+}
+
+/// The BigQuery REST API sends every value as a string, or as nested JSON for
+/// repeated fields and records, so values are parsed back into the type of
+/// their field. Numerics keep their precision, and repeated fields and
+/// records are loaded as JSON text.
+fn bigquery_columns_to_record_batch(
+    schema_fields: &[TableFieldSchema],
+    rows: &[TableRow],
+) -> Result<(RecordBatch, SchemaRef), ArrowError> {
+    fn parsed<T: std::str::FromStr>(values: &[Option<String>]) -> Vec<Option<T>> {
+        values
+            .iter()
+            .map(|value| value.as_ref().and_then(|value| value.parse().ok()))
+            .collect()
+    }
+    let mut fields = vec![];
+    let mut arrays = vec![];
+    for (idx, field) in schema_fields.iter().enumerate() {
+        let values = rows
+            .iter()
+            .map(|row| {
+                let value = row.columns.as_ref()?.get(idx)?.value.as_ref()?;
+                match value {
+                    serde_json::Value::Null => None,
+                    serde_json::Value::String(value) => Some(value.clone()),
+                    value => Some(value.to_string()),
+                }
+            })
+            .collect::<Vec<Option<String>>>();
+        let array: ArrayRef = match field.r#type {
+            _ if field.mode.as_deref() == Some("REPEATED") => Arc::new(StringArray::from(values)),
+            FieldType::Integer | FieldType::Int64 => {
+                Arc::new(Int64Array::from(parsed::<i64>(&values)))
+            }
+            FieldType::Float | FieldType::Float64 => {
+                Arc::new(Float64Array::from(parsed::<f64>(&values)))
+            }
+            FieldType::Boolean | FieldType::Bool => {
+                Arc::new(BooleanArray::from(parsed::<bool>(&values)))
+            }
+            FieldType::Numeric | FieldType::Bignumeric => decimal_column(values)?,
+            // Days since 1970-01-01, which is the default NaiveDate
+            FieldType::Date => Arc::new(Date32Array::from(
+                parsed::<NaiveDate>(&values)
+                    .into_iter()
+                    .map(|date| date.map(|date| (date - NaiveDate::default()).num_days() as i32))
+                    .collect::<Vec<_>>(),
+            )),
+            FieldType::Datetime => Arc::new(TimestampMicrosecondArray::from(
+                parsed::<NaiveDateTime>(&values)
+                    .into_iter()
+                    .map(|timestamp| {
+                        timestamp.map(|timestamp| timestamp.and_utc().timestamp_micros())
+                    })
+                    .collect::<Vec<_>>(),
+            )),
+            // Seconds since the epoch, in scientific notation
+            FieldType::Timestamp => Arc::new(
+                TimestampMicrosecondArray::from(
+                    parsed::<f64>(&values)
+                        .into_iter()
+                        .map(|seconds| seconds.map(|seconds| (seconds * 1e6).round() as i64))
+                        .collect::<Vec<_>>(),
+                )
+                .with_timezone("UTC"),
+            ),
+            _ => Arc::new(StringArray::from(values)),
+        };
+        fields.push(Field::new(&field.name, array.data_type().clone(), true));
+        arrays.push(array);
+    }
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new_with_options(
+        schema.clone(),
+        arrays,
+        &RecordBatchOptions::new().with_row_count(Some(rows.len())),
+    )?;
+    Ok((batch, schema))
+}
+
+#[derive(Debug, Clone)]
 pub struct Connector {
-    engine: EngineType,
+    engine: Arc<EngineType>,
+    /// Identifies the database in query cache keys
+    database: String,
+    options: QueryOptions,
+    params: HashMap<String, ParamValue>,
+    cache: Option<QueryCache>,
+    result_store: ResultStore,
 }
 
-#[derive(serde::Serialize, Clone)]
+#[derive(serde::Serialize, Clone, Debug)]
 pub struct DatabaseInfo {
-    name: String,
-    dialect: String,
-    tables: Vec<String>,
+    pub name: String,
+    pub dialect: String,
+    pub tables: Vec<TableInfo>,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct TableInfo {
+    pub name: String,
+    pub columns: Vec<ColumnInfo>,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    pub primary_key: bool,
+    /// Referenced column as `table.column`
+    pub foreign_key: Option<String>,
+    pub comment: Option<String>,
+}
+
+impl fmt::Display for TableInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| column.to_string())
+            .collect::<Vec<String>>();
+        write!(f, "{}({})", self.name, columns.join(", "))
+    }
+}
+
+impl fmt::Display for ColumnInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.data_type)?;
+        if self.primary_key {
+            write!(f, " PRIMARY KEY")?;
+        }
+        if !self.nullable {
+            write!(f, " NOT NULL")?;
+        }
+        if let Some(foreign_key) = &self.foreign_key {
+            write!(f, " REFERENCES {}", foreign_key)?;
+        }
+        if let Some(comment) = &self.comment {
+            write!(f, " COMMENT '{}'", comment.replace('\'', "''"))?;
+        }
+        Ok(())
+    }
 }
 
 impl Connector {
+    /// Returns the connector registered for `database_ref`, connecting on first use.
     pub async fn from_database(
         database_ref: &str,
         config_manager: &ConfigManager,
+    ) -> Result<Self, OnyxError> {
+        let mut connectors = config_manager.connectors().lock().await;
+        if let Some(connector) = connectors.get(database_ref) {
+            return Ok(connector.clone());
+        }
+        let connector = Self::connect(database_ref, config_manager).await?;
+        connectors.insert(database_ref.to_string(), connector.clone());
+        Ok(connector)
+    }
+
+    async fn connect(
+        database_ref: &str,
+        config_manager: &ConfigManager,
     ) -> Result<Self, OnyxError> {
         let database = config_manager.resolve_database(database_ref)?;
+        let result_store = config_manager.result_store().await?;
+        config_manager.remove_expired_results().await?;
+        let pool_size = database.pool_size.unwrap_or(DEFAULT_POOL_SIZE);
         let engine = match &database.database_type {
             DatabaseType::Bigquery(bigquery) => {
                 let key_path = config_manager
@@ -187,53 +2282,119 @@ impl Connector {
                             .ok_or(OnyxError::DBError("Key path not set".to_string()))?,
                     )
                     .await?;
-                EngineType::ConnectorX(ConnectorX {
-                    dialect: database.dialect(),
-                    db_path: key_path,
-                    db_name: bigquery.dataset.clone(),
-                })
+                EngineType::BigQuery(
+                    BigQuery::new(
+                        &key_path,
+                        bigquery.dataset.clone(),
+                        pool_size,
+                        result_store.clone(),
+                    )
+                    .await?,
+                )
             }
-            DatabaseType::DuckDB(duckdb) => EngineType::DuckDB(DuckDB {
-                file_search_path: config_manager
+            DatabaseType::DuckDB(duckdb) => {
+                let file_search_path = config_manager
                     .resolve_file(&duckdb.file_search_path)
-                    .await?,
-            }),
-            DatabaseType::Postgres(postgres) => {
-                let conn_string = if let Some(cs_file) = &postgres.connection_string_file {
-                    let mut conn_string_no_dialect = std::fs::read_to_string(cs_file)
-                        .map_err(|err| connector_internal_error(CREATE_CONN, &err))?
-                        .trim()
-                        .to_string();
-                    if conn_string_no_dialect.starts_with("postgres://") {
-                        conn_string_no_dialect =
-                            conn_string_no_dialect.replacen("postgres://", "", 1);
-                    } else if conn_string_no_dialect.starts_with("postgresql://") {
-                        conn_string_no_dialect =
-                            conn_string_no_dialect.replacen("postgresql://", "", 1);
-                    }
-                    conn_string_no_dialect
-                } else {
-                    format!(
-                        "{}:{}@{}:{}/{}",
-                        postgres.user.clone().unwrap_or_default(),
-                        std::fs::read_to_string(
-                            postgres.password_file.as_ref().unwrap_or(&PathBuf::new())
-                        )
-                        .unwrap_or_default()
-                        .trim(),
-                        postgres.host.clone().unwrap_or_default(),
-                        postgres.port.clone().unwrap_or_default(),
-                        postgres.database.clone().unwrap_or_default()
-                    )
+                    .await?;
+                let db_path = match &duckdb.path {
+                    Some(path) => Some(config_manager.resolve_file(path).await?),
+                    None => None,
                 };
-                EngineType::ConnectorX(ConnectorX {
-                    dialect: database.dialect(),
-                    db_path: conn_string,
-                    db_name: postgres.database.clone().unwrap_or_default(),
+                let init_sql = duckdb.init_sql.clone();
+                let result_store = result_store.clone();
+                // Opening the database and running init_sql may read files
+                let duckdb = tokio::task::spawn_blocking(move || {
+                    DuckDB::new(file_search_path, db_path, &init_sql, result_store)
                 })
+                .await
+                .map_err(|err| connector_internal_error(FAILED_TO_RUN_BLOCKING_TASK, &err))??;
+                EngineType::DuckDB(duckdb)
+            }
+            DatabaseType::Postgres(postgres) => EngineType::Postgres(
+                Postgres::new(postgres, pool_size, config_manager, result_store.clone()).await?,
+            ),
+            DatabaseType::SQLite(sqlite) => EngineType::SQLite(SQLite {
+                db_path: config_manager.resolve_file(&sqlite.path).await?,
+                result_store: result_store.clone(),
+            }),
+            DatabaseType::MySQL(mysql) => {
+                let conn_string =
+                    match secret_ref(&mysql.connection_string, &mysql.connection_string_file) {
+                        Some(reference) => strip_scheme(
+                            config_manager.resolve_secret(&reference).await?,
+                            &["mysql://"],
+                        ),
+                        None => build_connection_string(
+                            &mysql.user,
+                            &resolve_password(
+                                config_manager,
+                                &mysql.password,
+                                &mysql.password_file,
+                            )
+                            .await?,
+                            &mysql.host,
+                            &mysql.port,
+                            &mysql.database,
+                        ),
+                    };
+                EngineType::MySQL(MySQL::new(
+                    &conn_string,
+                    mysql.database.clone().unwrap_or_default(),
+                    pool_size,
+                    result_store.clone(),
+                )?)
             }
         };
-        Ok(Connector { engine })
+        Ok(Connector {
+            engine: Arc::new(engine),
+            // The project path tells apart databases of the same name, and the
+            // settings, which hold secret references rather than secrets, a
+            // database that was pointed elsewhere
+            database: format!(
+                "{}\n{}",
+                config_manager.resolve_file("").await?,
+                serde_json::to_string(database).unwrap_or_else(|_| database.name.clone())
+            ),
+            options: QueryOptions {
+                timeout: database.timeout.map(Duration::from_secs),
+                read_only: false,
+            },
+            params: HashMap::new(),
+            cache: config_manager.query_cache().await?,
+            result_store,
+        })
+    }
+
+    /// Overrides the database timeout, e.g. with a per-task setting.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        if timeout.is_some() {
+            self.options.timeout = timeout;
+        }
+        self
+    }
+
+    /// Runs queries in a read-only session where the database supports it,
+    /// which also stops statements that only look read-only, e.g. `SELECT`s
+    /// calling functions with side effects.
+    pub fn read_only(mut self) -> Self {
+        self.options.read_only = true;
+        self
+    }
+
+    /// Binds `params` to the `:name` placeholders of the queries run next.
+    pub fn with_params(mut self, params: HashMap<String, ParamValue>) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Always runs queries against the database, bypassing the query cache.
+    pub fn without_cache(mut self) -> Self {
+        self.cache = None;
+        self
+    }
+
+    pub fn dialect(&self) -> String {
+        self.engine.dialect()
     }
 
     pub async fn database_info(&self) -> Result<DatabaseInfo, OnyxError> {
@@ -241,46 +2402,491 @@ impl Connector {
     }
 
     pub async fn run_query(&self, query: &str) -> Result<String, OnyxError> {
-        self.engine.run_query(query).await
+        let (file_path, _) = self.run_query_cached(query).await?;
+        Ok(file_path)
+    }
+
+    /// Runs `query` and returns the path of its result file. Read-only queries
+    /// are served from the query cache when one is configured, in which case
+    /// whether the cache was hit is returned too.
+    pub async fn run_query_cached(
+        &self,
+        query: &str,
+    ) -> Result<(String, Option<CacheStatus>), OnyxError> {
+        let (query, params) = self.engine.bind(query, &self.params)?;
+        let cache = match &self.cache {
+            Some(cache) if self.is_read_only(&query) => cache,
+            _ => {
+                let file_path = self.engine.run_query(&query, &params, self.options).await?;
+                return Ok((file_path, None));
+            }
+        };
+        let key = QueryCache::key(&self.database, &self.dialect(), &query, &params);
+        if let Some(entry) = cache.get(&key) {
+            match self.result_store.copy_from(&entry) {
+                Ok(file_path) => return Ok((file_path, Some(CacheStatus::Hit))),
+                Err(e) => debug!(
+                    "Failed to read query cache entry {}: {}",
+                    entry.display(),
+                    e
+                ),
+            }
+        }
+        let file_path = self.engine.run_query(&query, &params, self.options).await?;
+        // A cache that cannot be written to only costs the next run a query
+        if let Err(e) = cache.put(&key, &file_path) {
+            debug!("Failed to write query cache entry: {}", e);
+        }
+        Ok((file_path, Some(CacheStatus::Miss)))
+    }
+
+    /// Splits a SQL script into statements using the database's dialect.
+    pub fn split_statements(&self, sql: &str) -> Vec<String> {
+        split_statements(sql, &self.dialect())
+    }
+
+    /// Runs `statements` in order, binding the parameters of each, and returns
+    /// the result file of every statement when `all` is set, otherwise of the
+    /// last one only. BigQuery only accepts a single statement.
+    /// Scripts bypass the query cache.
+    pub async fn run_statements(
+        &self,
+        statements: &[String],
+        all: bool,
+    ) -> Result<Vec<String>, OnyxError> {
+        let statements = statements
+            .iter()
+            .map(|statement| self.engine.bind(statement, &self.params))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.engine
+            .run_statements(&statements, self.options, all)
+            .await
+    }
+
+    fn is_read_only(&self, query: &str) -> bool {
+        SQLGuard {
+            read_only: true,
+            ..Default::default()
+        }
+        .check(query, &self.dialect())
+        .is_ok()
+    }
+
+    pub async fn explain_query(
+        &self,
+        query: &str,
+    ) -> Result<(Vec<RecordBatch>, SchemaRef), OnyxError> {
+        let (query, params) = self
+            .engine
+            .bind(&self.engine.explain_query(query), &self.params)?;
+        self.engine
+            .run_query_and_load(&query, &params, self.options)
+            .await
     }
 
     pub async fn run_query_and_load(
         &self,
         query: &str,
     ) -> Result<(Vec<RecordBatch>, SchemaRef), OnyxError> {
-        self.engine.run_query_and_load(query).await
+        let file_path = self.run_query(query).await?;
+        load_result(&file_path).map_err(|e| connector_internal_error(LOAD_RESULT, &e))
     }
 }
 
-pub fn load_result(file_path: &str) -> anyhow::Result<(Vec<RecordBatch>, SchemaRef)> {
-    let file = File::open(file_path).map_err(|_| {
-        anyhow::Error::msg("Executed query did not generate a valid output file. If you are using an agent to generate the query, consider giving it a shorter prompt.".to_string())
-    })?;
-    let reader = FileReader::try_new(file, None)?;
-    let schema = reader.schema();
-    // Collect results and handle potential errors
-    let batches: Result<Vec<RecordBatch>, ArrowError> = reader.collect();
-    let batches = batches?;
+/// Waits for a blocking query task, calling `interrupt` and returning
+/// `OnyxError::QueryTimeout` when it runs longer than `timeout`.
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    task: JoinHandle<anyhow::Result<T>>,
+    interrupt: impl FnOnce(),
+) -> Result<T, OnyxError> {
+    let result = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, task).await {
+            Ok(result) => result,
+            Err(_) => {
+                interrupt();
+                return Err(OnyxError::QueryTimeout(format!(
+                    "Query did not finish within {} seconds",
+                    timeout.as_secs()
+                )));
+            }
+        },
+        None => task.await,
+    };
+    let result =
+        result.map_err(|e| connector_internal_error(FAILED_TO_RUN_BLOCKING_TASK, &e))??;
+    Ok(result)
+}
 
-    Ok((batches, schema))
+/// Appends a column to the last table when it belongs to it, otherwise starts
+/// a new table. Introspection queries are ordered by table so consecutive rows
+/// share a table; repeated column names come from multi-column key joins.
+fn push_column(tables: &mut Vec<TableInfo>, table_name: String, column: ColumnInfo) {
+    match tables.last_mut() {
+        Some(table) if table.name == table_name => {
+            if !table.columns.iter().any(|c| c.name == column.name) {
+                table.columns.push(column);
+            }
+        }
+        _ => tables.push(TableInfo {
+            name: table_name,
+            columns: vec![column],
+        }),
+    }
 }
 
-fn write_to_ipc(
-    batches: &Vec<RecordBatch>,
-    file_path: &str,
-    schema: &Arc<Schema>,
-) -> anyhow::Result<()> {
-    let file = File::create(file_path)?;
-    if batches.is_empty() {
-        debug!("Warning: query returned no results.");
+/// Builds tables from the result of an introspection query returning
+/// `table_name, column_name, data_type, is_nullable, is_primary_key,
+/// foreign_key, comment`, with flags encoded as `YES`/`NO`.
+fn tables_from_columns(datasets: &[RecordBatch]) -> Result<Vec<TableInfo>, OnyxError> {
+    let mut tables = vec![];
+    for batch in datasets.iter() {
+        let mut columns = vec![];
+        for idx in 0..7 {
+            // engines map text columns to either Utf8 or LargeUtf8 depending on the source
+            let column = cast(batch.column(idx), &DataType::Utf8)
+                .map_err(|err| connector_internal_error(LOAD_ARROW_RESULT, &err))?;
+            columns.push(column);
+        }
+        for row in 0..batch.num_rows() {
+            let value = |idx: usize| {
+                let array = as_string_array(&columns[idx]);
+                array
+                    .is_valid(row)
+                    .then(|| array.value(row).to_string())
+                    .filter(|value| !value.is_empty())
+            };
+            push_column(
+                &mut tables,
+                value(0).unwrap_or_default(),
+                ColumnInfo {
+                    name: value(1).unwrap_or_default(),
+                    data_type: value(2).unwrap_or_default(),
+                    nullable: value(3).as_deref() != Some("NO"),
+                    primary_key: value(4).as_deref() == Some("YES"),
+                    foreign_key: value(5),
+                    comment: value(6),
+                },
+            );
+        }
+    }
+    Ok(tables)
+}
+
+/// Secret reference for a credential that can be given either as a reference
+/// or through the older `*_file` settings.
+fn secret_ref(reference: &Option<String>, file: &Option<PathBuf>) -> Option<String> {
+    reference
+        .clone()
+        .or_else(|| file.as_ref().map(|file| format!("file:{}", file.display())))
+}
+
+async fn resolve_password(
+    config_manager: &ConfigManager,
+    password: &Option<String>,
+    password_file: &Option<PathBuf>,
+) -> Result<String, OnyxError> {
+    match secret_ref(password, password_file) {
+        Some(reference) => config_manager.resolve_secret(&reference).await,
+        None => Ok(String::new()),
+    }
+}
+
+fn strip_scheme(conn_string: String, schemes: &[&str]) -> String {
+    for scheme in schemes {
+        if let Some(conn_string_no_dialect) = conn_string.strip_prefix(scheme) {
+            return conn_string_no_dialect.to_string();
+        }
     }
+    conn_string
+}
+
+/// Quotes each schema as an identifier, so that a comma cannot add another
+/// schema, and escapes whitespace and backslashes, which would otherwise end
+/// the `-c` option and let the rest of the name set other options.
+fn search_path_value(schemas: &[String]) -> String {
+    schemas
+        .iter()
+        .map(|schema| {
+            let mut value = String::new();
+            for c in format!("\"{}\"", schema.replace('"', "\"\"")).chars() {
+                if c.is_whitespace() || c == '\\' {
+                    value.push('\\');
+                }
+                value.push(c);
+            }
+            value
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
 
-    debug!("Schema: {:?}", schema);
-    let schema_ref = schema.as_ref();
-    let mut writer = FileWriter::try_new(file, schema_ref)?;
-    for batch in batches {
-        writer.write(batch)?;
+fn build_connection_string(
+    user: &Option<String>,
+    password: &str,
+    host: &Option<String>,
+    port: &Option<String>,
+    database: &Option<String>,
+) -> String {
+    // Secrets may hold characters that delimit the parts of a URL
+    format!(
+        "{}:{}@{}:{}/{}",
+        urlencoding::encode(user.as_deref().unwrap_or_default()),
+        urlencoding::encode(password),
+        host.clone().unwrap_or_default(),
+        port.clone().unwrap_or_default(),
+        database.clone().unwrap_or_default()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        as_string_array, bigquery_columns_to_record_batch, bigquery_parameter, bind_params,
+        build_connection_string, decimal_column, postgres_numeric, search_path_value, Arc, Array,
+        BigQuery, DataType, Decimal128Array, DuckDB, Engine, HashMap, Int64Array, Manager,
+        NaiveDate, OnceCell, ParamValue, Pool, Postgres, QueryOptions, ResultStore, SQLite,
+        Semaphore, TableFieldSchema, TableRow, TimestampMicrosecondArray,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn search_path_quotes_schemas() {
+        let schemas = ["analytics", "sales, public", "x -c role=admin", "a\\b\"c"]
+            .map(|schema| schema.to_string());
+        assert_eq!(
+            search_path_value(&schemas),
+            r#""analytics","sales,\ public","x\ -c\ role=admin","a\\b""c""#
+        );
+    }
+
+    #[test]
+    fn duckdb_init_sql_scope() {
+        assert!(DuckDB::is_session_scoped("SET search_path = 'analytics'").unwrap());
+        assert!(DuckDB::is_session_scoped("USE warehouse").unwrap());
+        assert!(!DuckDB::is_session_scoped("ATTACH 'sales.duckdb' AS sales").unwrap());
+        assert!(
+            !DuckDB::is_session_scoped("CREATE VIEW events AS SELECT * FROM 'events.csv'").unwrap()
+        );
+        assert!(DuckDB::is_session_scoped("CREATE TEMP TABLE staging AS SELECT 1").is_err());
+    }
+
+    #[test]
+    fn connection_string_encodes_credentials() {
+        let conn_string = build_connection_string(
+            &Some("app@corp".to_string()),
+            "p@ss:w/rd#1%",
+            &Some("localhost".to_string()),
+            &Some("3306".to_string()),
+            &Some("shop".to_string()),
+        );
+        assert_eq!(
+            conn_string,
+            "app%40corp:p%40ss%3Aw%2Frd%231%25@localhost:3306/shop"
+        );
+    }
+
+    #[test]
+    fn bigquery_rejects_scripts() {
+        let key = serde_json::from_value(serde_json::json!({
+            "private_key": "",
+            "client_email": "onyx@example.iam.gserviceaccount.com",
+            "token_uri": "https://oauth2.googleapis.com/token",
+        }))
+        .unwrap();
+        let bigquery = BigQuery {
+            dataset: "shop".to_string(),
+            project_id: "example".to_string(),
+            key,
+            client: OnceCell::new(),
+            concurrency: Arc::new(Semaphore::new(1)),
+            result_store: ResultStore::default(),
+        };
+        let statements = [
+            "CREATE TEMP TABLE staging AS SELECT 1",
+            "SELECT * FROM staging",
+        ]
+        .map(|statement| (statement.to_string(), vec![]));
+        let err = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(bigquery.run_statements(&statements, QueryOptions::default(), false))
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("SQL with several statements is not supported for bigquery databases"));
+    }
+
+    #[test]
+    fn bigquery_parameters_named_by_position() {
+        let (query, values) = bind_params(
+            "SELECT * FROM orders WHERE status = :status AND placed_at >= :since OR :status = ''",
+            &HashMap::from([
+                ("status".to_string(), ParamValue::String("open".to_string())),
+                (
+                    "since".to_string(),
+                    ParamValue::Date(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()),
+                ),
+            ]),
+            |_, position| format!("@p{}", position),
+        );
+        assert_eq!(
+            query,
+            "SELECT * FROM orders WHERE status = @p1 AND placed_at >= @p2 OR @p3 = ''"
+        );
+        let parameter = bigquery_parameter(2, &values[1]);
+        assert_eq!(parameter.name.as_deref(), Some("p2"));
+        assert_eq!(parameter.parameter_type.unwrap().r#type, "DATE");
+        assert_eq!(
+            parameter.parameter_value.unwrap().value.as_deref(),
+            Some("2024-01-01")
+        );
+    }
+
+    #[test]
+    fn bigquery_values_typed() {
+        let fields = serde_json::from_value::<Vec<TableFieldSchema>>(serde_json::json!([
+            {"name": "id", "type": "INTEGER"},
+            {"name": "amount", "type": "NUMERIC"},
+            {"name": "placed_at", "type": "TIMESTAMP"},
+            {"name": "tags", "type": "STRING", "mode": "REPEATED"},
+        ]))
+        .unwrap();
+        let rows = serde_json::from_value::<Vec<TableRow>>(serde_json::json!([
+            {"f": [{"v": "1"}, {"v": "12.50"}, {"v": "1.7040672E9"}, {"v": [{"v": "new"}]}]},
+            {"f": [{"v": null}, {"v": null}, {"v": null}, {"v": []}]},
+        ]))
+        .unwrap();
+        let (batch, _) = bigquery_columns_to_record_batch(&fields, &rows).unwrap();
+        let ids = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(ids.value(0), 1);
+        assert!(ids.is_null(1));
+        let amounts = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Decimal128Array>()
+            .unwrap();
+        assert_eq!(amounts.value_as_string(0), "12.50");
+        let placed_at = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap();
+        assert_eq!(placed_at.value(0), 1_704_067_200_000_000);
+        assert_eq!(
+            as_string_array(batch.column(3)).value(0),
+            r#"[{"v":"new"}]"#
+        );
+    }
+
+    #[test]
+    fn postgres_numeric_as_text() {
+        let numeric = |words: &[u16]| {
+            let raw = words
+                .iter()
+                .flat_map(|word| word.to_be_bytes())
+                .collect::<Vec<u8>>();
+            postgres_numeric(&raw).unwrap()
+        };
+        // ndigits, weight, sign, dscale, then base 10000 digits
+        assert_eq!(numeric(&[3, 1, 0, 3, 1, 2345, 6780]), "12345.678");
+        assert_eq!(numeric(&[1, 0xFFFF, 0x4000, 2, 500]), "-0.05");
+        assert_eq!(numeric(&[1, 0xFFFE, 0, 5, 1000]), "0.00001");
+        assert_eq!(numeric(&[1, 2, 0, 0, 12]), "1200000000");
+        assert_eq!(numeric(&[0, 0, 0, 2]), "0.00");
+        assert_eq!(numeric(&[0, 0, 0xC000, 0]), "NaN");
+    }
+
+    #[test]
+    fn decimal_column_keeps_precision() {
+        let values = ["12345678901234567890.12", "-0.5"]
+            .map(|value| Some(value.to_string()))
+            .into_iter()
+            .chain([None])
+            .collect();
+        let array = decimal_column(values).unwrap();
+        assert_eq!(array.data_type(), &DataType::Decimal128(38, 2));
+        let array = array.as_any().downcast_ref::<Decimal128Array>().unwrap();
+        assert_eq!(array.value_as_string(0), "12345678901234567890.12");
+        assert_eq!(array.value_as_string(1), "-0.50");
+        assert!(array.is_null(2));
+
+        for value in ["NaN", "1234567890123456789012345678901234567890"] {
+            let array = decimal_column(vec![Some(value.to_string())]).unwrap();
+            assert_eq!(as_string_array(&array).value(0), value);
+        }
+    }
+
+    #[test]
+    fn sqlite_missing_file_not_created() {
+        let db_path =
+            std::env::temp_dir().join(format!("onyx-test-{}.sqlite", uuid::Uuid::new_v4()));
+        let sqlite = SQLite {
+            db_path: db_path.to_string_lossy().to_string(),
+            result_store: ResultStore::default(),
+        };
+        for read_only in [false, true] {
+            let err = sqlite.open_connection(read_only).unwrap_err();
+            assert!(err.to_string().contains("SQLite database file not found"));
+        }
+        assert!(!db_path.exists());
+    }
+
+    #[test]
+    #[ignore = "needs the Postgres server from docker-compose.yml"]
+    fn postgres_session_reset_between_checkouts() {
+        let mut config = tokio_postgres::Config::new();
+        config
+            .host("localhost")
+            .port(5432)
+            .user("testuser")
+            .password("testpassword")
+            .dbname("testdb");
+        let tls = Postgres::tls_connector(None, None).unwrap();
+        let postgres = Postgres {
+            db_name: "testdb".to_string(),
+            // A single connection, so that the second checkout gets the session
+            // the script ran on
+            pool: Pool::builder(Manager::new(config, tls.clone()))
+                .max_size(1)
+                .build()
+                .unwrap(),
+            tls,
+            result_store: ResultStore::new(
+                std::env::temp_dir().join(format!("onyx-test-{}", uuid::Uuid::new_v4())),
+                Duration::from_secs(60),
+            ),
+            schemas: vec![],
+        };
+        let statements = [
+            "CREATE TEMP TABLE onyx_session (id int)",
+            "SET search_path = onyx_session",
+        ]
+        .map(|statement| (statement.to_string(), vec![]));
+
+        let (batches, _) = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async {
+                postgres
+                    .run_statements(&statements, QueryOptions::default(), false)
+                    .await
+                    .unwrap();
+                postgres
+                    .run_query_and_load(
+                        "SELECT to_regclass('pg_temp.onyx_session')::text AS temp_table, current_setting('search_path') AS search_path",
+                        &[],
+                        QueryOptions::default(),
+                    )
+                    .await
+            })
+            .unwrap();
+        assert!(batches[0].column(0).is_null(0));
+        assert_eq!(
+            as_string_array(batches[0].column(1)).value(0),
+            "\"$user\", public"
+        );
     }
-    writer.finish()?;
-    Ok(())
 }
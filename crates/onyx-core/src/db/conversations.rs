@@ -6,12 +6,16 @@ use sea_orm::ActiveModelTrait;
 use sea_orm::ActiveValue;
 use sea_orm::EntityTrait;
 use sea_orm::QueryFilter;
+use sea_orm::QueryOrder;
 use uuid::Uuid;
 
+/// The latest conversation with an agent, so that starting a new one, e.g.
+/// with `/reset` in `onyx chat`, leaves the previous ones out.
 pub async fn get_conversation_by_agent(agent: &str) -> Option<conversations::Model> {
     let connection = establish_connection().await;
     let conversations = Conversations::find()
         .filter(conversations::Column::Agent.eq(agent))
+        .order_by_desc(conversations::Column::CreatedAt)
         .one(&connection)
         .await;
 
@@ -22,13 +22,27 @@ pub async fn get_messages_by_conversation(
 }
 
 pub async fn save_message(conversation_id: Uuid, content: &str, is_human: bool) -> messages::Model {
+    save_message_with_id(Uuid::new_v4(), conversation_id, content, is_human, None).await
+}
+
+/// Saves a message under an id chosen beforehand, e.g. the one its streamed
+/// pieces were sent with. `tool_calls` holds the tool calls made to answer,
+/// serialized as JSON.
+pub async fn save_message_with_id(
+    id: Uuid,
+    conversation_id: Uuid,
+    content: &str,
+    is_human: bool,
+    tool_calls: Option<String>,
+) -> messages::Model {
     let connection: sea_orm::DatabaseConnection = establish_connection().await;
     let new_message = entity::messages::ActiveModel {
-        id: ActiveValue::Set(Uuid::new_v4()),
+        id: ActiveValue::Set(id),
         is_human: ActiveValue::Set(is_human),
         conversation_id: ActiveValue::set(conversation_id),
         content: ActiveValue::Set(content.to_string()),
         created_at: ActiveValue::not_set(),
+        tool_calls: ActiveValue::Set(tool_calls),
     };
 
     new_message
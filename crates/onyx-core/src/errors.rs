@@ -1,4 +1,4 @@
-use std::sync::PoisonError;
+use std::{sync::PoisonError, time::Duration};
 
 use thiserror::Error;
 use tokio::{sync::mpsc::error::SendError, task::JoinError};
@@ -13,6 +13,13 @@ pub enum OnyxError {
     RuntimeError(String),
     #[error("LLM error:\n{0}")]
     LLMError(String),
+    /// An error of the LLM API that may go away when retried, e.g. a rate
+    /// limit or an overloaded server, with how long it asked to wait, if it did
+    #[error("LLM API unavailable:\n{message}")]
+    TransientLLMError {
+        message: String,
+        retry_after: Option<Duration>,
+    },
     #[error("Agent error:\n{0}")]
     AgentError(String),
     #[error("Anonymizer error:\n{0}")]
@@ -23,6 +30,10 @@ pub enum OnyxError {
     IOError(String),
     #[error("DB error:\n{0}")]
     DBError(String),
+    #[error("Query timeout:\n{0}")]
+    QueryTimeout(String),
+    #[error("SQL policy violation:\n{0}")]
+    SQLPolicyError(String),
 }
 
 impl From<Box<dyn std::error::Error>> for OnyxError {
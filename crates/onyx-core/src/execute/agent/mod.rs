@@ -1,24 +1,32 @@
 use std::{
+    io::Write,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
 use contexts::Contexts;
 use minijinja::{context, Value};
+use serde::{Deserialize, Serialize};
 use tools::ToolsContext;
 
 use crate::{
     ai::{
-        agent::{AgentResult, OpenAIAgent},
+        agent::{Agent, AgentResult},
         setup_agent,
+        usage::Usage,
         utils::record_batches_to_table,
     },
     config::{
         model::{AgentConfig, FileFormat},
         ConfigManager,
     },
-    connector::load_result,
     errors::OnyxError,
+    query_cache::CacheStatus,
+    result_store::load_result,
     utils::{print_colored_sql, truncate_datasets, truncate_with_ellipsis, MAX_DISPLAY_ROWS},
     StyledText,
 };
@@ -44,6 +52,8 @@ pub enum ToolMetadata {
     ExecuteSQL {
         sql_query: String,
         output_file: String,
+        /// Whether the result came from the query cache, if one is configured
+        cache_status: Option<CacheStatus>,
     },
 }
 
@@ -52,6 +62,8 @@ pub struct ToolCall {
     pub name: String,
     pub output: String,
     pub metadata: Option<ToolMetadata>,
+    /// Tokens the tool used, e.g. to embed a search query
+    pub usage: Option<Usage>,
 }
 
 impl ToolCall {
@@ -64,6 +76,7 @@ impl ToolCall {
             name: self.name.clone(),
             output: self.output.clone(),
             metadata: Some(metadata),
+            usage: self.usage.clone(),
         }
     }
 }
@@ -71,17 +84,110 @@ impl ToolCall {
 #[derive(Debug, Clone)]
 pub enum AgentEvent {
     Started,
+    /// The next piece of the model's answer, as it is generated
+    Delta(String),
+    /// The pieces streamed since the last request to the model are not part
+    /// of the answer, as the model went on to call tools
+    DeltasDiscarded,
+    /// Sent for every tool call the model asks for, before any of them runs
+    ToolCallStarted {
+        id: String,
+        name: String,
+        arguments: String,
+    },
+    /// The result of a tool call, in the order the calls were started
     ToolCall(ToolCall),
-    Finished { output: String },
+    /// The agent reached a limit of its budget and must answer without tools
+    BudgetExhausted {
+        reason: String,
+    },
+    /// A request to the model failed with a transient error and is sent
+    /// again after `after`
+    Retry {
+        err: OnyxError,
+        after: Duration,
+    },
+    /// Tokens used by a request to the model or by a tool call
+    Usage(Usage),
+    Finished {
+        output: String,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub struct AgentInput {
     pub prompt: Option<String>,
     pub system_instructions: String,
+    /// Previous turns of the conversation, oldest first
+    pub history: Vec<ConversationTurn>,
+}
+
+/// A question asked to an agent earlier in a conversation, the tool calls it
+/// made to answer it and its answer.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConversationTurn {
+    pub question: String,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCallRecord>,
+    pub answer: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    pub id: String,
+    pub name: String,
+    pub arguments: String,
+    pub output: String,
 }
 
-pub struct AgentReceiver;
+/// Collects the tool calls of a run from its events, matching each result
+/// with its call as they come in the same order.
+#[derive(Debug, Default)]
+pub struct ToolCallRecorder {
+    records: Vec<ToolCallRecord>,
+    finished: usize,
+}
+
+impl ToolCallRecorder {
+    pub fn record(&mut self, event: &AgentEvent) {
+        match event {
+            AgentEvent::ToolCallStarted {
+                id,
+                name,
+                arguments,
+            } => self.records.push(ToolCallRecord {
+                id: id.clone(),
+                name: name.clone(),
+                arguments: arguments.clone(),
+                output: String::new(),
+            }),
+            AgentEvent::ToolCall(tool_call) => {
+                if let Some(record) = self.records.get_mut(self.finished) {
+                    record.output = tool_call.get_truncated_output();
+                    self.finished += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<ToolCallRecord> {
+        self.finished = 0;
+        std::mem::take(&mut self.records)
+    }
+}
+
+#[derive(Default)]
+pub struct AgentReceiver {
+    /// Whether the answer so far has been printed from deltas
+    streaming: AtomicBool,
+}
+
+impl AgentReceiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
 
 impl Handler for AgentReceiver {
     type Event = AgentEvent;
@@ -89,16 +195,59 @@ impl Handler for AgentReceiver {
     fn handle(&self, event: &Self::Event) {
         match &event {
             AgentEvent::Started => {}
+            AgentEvent::Delta(delta) => {
+                if !self.streaming.swap(true, Ordering::Relaxed) {
+                    println!("{}", "\nOutput:".primary());
+                }
+                print!("{}", delta);
+                let _ = std::io::stdout().flush();
+            }
+            // The text was printed already, the tool calls that follow show
+            // it was not the answer
+            AgentEvent::DeltasDiscarded => {}
             AgentEvent::Finished { output } => {
-                println!("{}", "\nOutput:".primary());
-                println!("{}", output);
+                if self.streaming.swap(false, Ordering::Relaxed) {
+                    println!();
+                } else {
+                    println!("{}", "\nOutput:".primary());
+                    println!("{}", output);
+                }
+            }
+            AgentEvent::ToolCallStarted { name, .. } => {
+                if self.streaming.swap(false, Ordering::Relaxed) {
+                    println!();
+                }
+                println!("{}", format!("\nCalling tool {}...", name).text());
+            }
+            AgentEvent::BudgetExhausted { reason } => {
+                if self.streaming.swap(false, Ordering::Relaxed) {
+                    println!();
+                }
+                println!(
+                    "{}",
+                    format!("\nReached the {}, answering without more tools.", reason).warning()
+                );
+            }
+            AgentEvent::Retry { err, after } => {
+                if self.streaming.swap(false, Ordering::Relaxed) {
+                    println!();
+                }
+                println!("{}", format!("\nRetrying after {:?} ...", after).warning());
+                println!("Reason {:?}", err);
+            }
+            AgentEvent::Usage(usage) => {
+                log::debug!("Agent usage: {}", usage);
             }
             AgentEvent::ToolCall(tool_call) => match &tool_call.metadata {
                 Some(ToolMetadata::ExecuteSQL {
                     sql_query,
                     output_file,
+                    cache_status,
                 }) => {
                     print_colored_sql(sql_query);
+                    if let Some(CacheStatus::Hit) = cache_status {
+                        println!("{}", "Query result served from the query cache.".primary());
+                    }
                     match load_result(output_file) {
                         Ok((batches, schema)) => {
                             let (batches, truncated) = truncate_datasets(batches);
@@ -140,7 +289,7 @@ pub async fn build_agent<P: AsRef<Path>>(
     file_format: &FileFormat,
     prompt: Option<String>,
     config: Arc<ConfigManager>,
-) -> Result<(OpenAIAgent, AgentConfig, Value), OnyxError> {
+) -> Result<(Agent, AgentConfig, Value), OnyxError> {
     let (agent, agent_config) = setup_agent(agent_file, file_format, config.clone()).await?;
     let contexts = Contexts::new(
         agent_config.context.clone().unwrap_or_default(),
@@ -161,20 +310,46 @@ pub async fn run_agent(
     file_format: &FileFormat,
     prompt: Option<String>,
     config: Arc<ConfigManager>,
+) -> Result<AgentResult, OnyxError> {
+    run_agent_with_handler(
+        agent_file,
+        file_format,
+        prompt,
+        vec![],
+        config,
+        AgentReceiver::new(),
+    )
+    .await
+}
+
+/// Runs an agent as a follow-up to the `history` of a conversation, keeping
+/// the last `history_window` turns, and sends its events to `handler`
+/// instead of printing them.
+pub async fn run_agent_with_handler(
+    agent_file: &PathBuf,
+    file_format: &FileFormat,
+    prompt: Option<String>,
+    mut history: Vec<ConversationTurn>,
+    config: Arc<ConfigManager>,
+    handler: impl Handler<Event = AgentEvent> + 'static,
 ) -> Result<AgentResult, OnyxError> {
     let (agent, agent_config, global_context) =
         build_agent(agent_file, file_format, prompt.clone(), config.clone()).await?;
 
+    let skipped = history.len().saturating_sub(agent_config.history_window);
+    history.drain(..skipped);
+
     let output = run(
         &agent,
         AgentInput {
             prompt,
             system_instructions: agent_config.system_instructions.clone(),
+            history,
         },
         config.clone(),
         global_context,
         Some(&agent_config),
-        AgentReceiver,
+        handler,
     )
     .await?;
     Ok(AgentResult { output })
@@ -10,7 +10,10 @@ use tokio::sync::mpsc::{Receiver, Sender};
 use crate::{
     ai::setup_eval_agent,
     errors::OnyxError,
-    execute::workflow::{WorkflowEvent, WorkflowInput},
+    execute::{
+        agent::AgentEvent,
+        workflow::{WorkflowEvent, WorkflowInput},
+    },
     theme::StyledText,
 };
 
@@ -344,11 +347,20 @@ impl ConsensusExecutor {
             }
         }
 
-        while let Ok(event) = event_receiver.try_recv() {
-            if let Some((index, _)) = most_consistent_output {
-                if index == event.0 {
-                    self.collected_events.push(event.1);
+        while let Ok((index, event)) = event_receiver.try_recv() {
+            let from_output = most_consistent_output
+                .as_ref()
+                .is_some_and(|(output_index, _)| *output_index == index);
+            // Every run counts towards usage, not only the one kept
+            let is_usage = matches!(
+                event,
+                WorkflowEvent::Agent {
+                    orig: AgentEvent::Usage(_),
+                    ..
                 }
+            );
+            if from_output || is_usage {
+                self.collected_events.push(event);
             }
         }
 
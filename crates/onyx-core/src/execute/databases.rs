@@ -3,10 +3,16 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use minijinja::value::{Object, ObjectRepr, Value};
+use minijinja::{
+    context,
+    value::{Enumerator, Object, ObjectRepr, Value},
+};
 use tokio::runtime::Handle;
 
-use crate::{config::ConfigManager, connector::Connector};
+use crate::{
+    config::ConfigManager,
+    connector::{Connector, DatabaseInfo, TableInfo},
+};
 
 #[derive(Debug, Clone)]
 pub struct DatabasesContext {
@@ -42,7 +48,7 @@ impl Object for DatabasesContext {
                             .block_on(Connector::from_database(database_key, &self.config))
                             .ok()?;
                         let database_info = rt.block_on(connector.database_info()).ok()?;
-                        let value = Value::from_serialize(database_info);
+                        let value = database_value(database_info);
                         cache.insert(database_key.to_string(), value.clone());
                         Some(value)
                     }
@@ -56,3 +62,44 @@ impl Object for DatabasesContext {
         }
     }
 }
+
+fn database_value(database_info: DatabaseInfo) -> Value {
+    let tables = database_info
+        .tables
+        .into_iter()
+        .map(|table| Value::from_object(TableObject(table)))
+        .collect::<Vec<Value>>();
+    context! {
+        name => database_info.name,
+        dialect => database_info.dialect,
+        tables => tables,
+    }
+}
+
+/// Renders as a compact one-line schema, e.g.
+/// `orders(id integer PRIMARY KEY NOT NULL, customer_id integer REFERENCES customers.id)`,
+/// while still exposing `name` and `columns` for custom formatting.
+#[derive(Debug)]
+struct TableObject(TableInfo);
+
+impl Object for TableObject {
+    fn repr(self: &Arc<Self>) -> ObjectRepr {
+        ObjectRepr::Map
+    }
+
+    fn get_value(self: &Arc<Self>, key: &Value) -> Option<Value> {
+        match key.as_str()? {
+            "name" => Some(Value::from(self.0.name.clone())),
+            "columns" => Some(Value::from_serialize(&self.0.columns)),
+            _ => None,
+        }
+    }
+
+    fn enumerate(self: &Arc<Self>) -> Enumerator {
+        Enumerator::Str(&["name", "columns"])
+    }
+
+    fn render(self: &Arc<Self>, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -514,6 +514,7 @@ pub async fn run_eval(path: PathBuf, quiet: bool) -> Result<(), OnyxError> {
                             Eval::Consistency(consistency) => AgentInput {
                                 system_instructions: agent.system_instructions.clone(),
                                 prompt: consistency.task_description.clone(),
+                                history: vec![],
                             },
                         },
                     }),
@@ -8,10 +8,10 @@ use crate::ai::utils::record_batches_to_rows;
 use crate::config::model::AgentTask;
 use crate::config::model::ExportFormat;
 use crate::config::model::TaskExport;
-use crate::connector::load_result;
 use crate::errors::OnyxError;
 use crate::execute::agent::ToolCall;
 use crate::execute::agent::ToolMetadata;
+use crate::result_store::load_result;
 use crate::StyledText;
 use arrow::array::RecordBatch;
 use arrow::datatypes::Schema;
@@ -28,6 +28,7 @@ pub fn export_agent_task<P: AsRef<Path>>(
             if let Some(ToolMetadata::ExecuteSQL {
                 sql_query,
                 output_file,
+                ..
             }) = &output.metadata
             {
                 let result_file_path = output_file.clone();
@@ -133,7 +134,7 @@ fn export_sql<P: AsRef<Path>>(
     Ok(())
 }
 
-fn export_csv<P: AsRef<Path>>(
+pub fn export_csv<P: AsRef<Path>>(
     file_path: P,
     schema: &Arc<Schema>,
     datasets: &[RecordBatch],
@@ -1,4 +1,8 @@
-use std::{path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use arrow::{array::RecordBatch, datatypes::Schema};
 use minijinja::Value;
@@ -15,7 +19,7 @@ use crate::{
     errors::OnyxError,
     execute::exporter::{export_agent_task, export_execute_sql, export_formatter},
     utils::{find_project_path, print_colored_sql},
-    workflow::{executor::WorkflowExecutor, WorkflowResult},
+    workflow::{executor::WorkflowExecutor, WorkflowResult, WorkflowUsage},
     StyledText,
 };
 
@@ -92,6 +96,12 @@ pub enum WorkflowEvent {
         schema: Arc<Schema>,
         export_file_path: String,
     },
+    QueryCacheHit {
+        database: String,
+    },
+    QueryCacheMiss {
+        database: String,
+    },
 
     // formatter
     Formatter {
@@ -102,6 +112,8 @@ pub enum WorkflowEvent {
     SubWorkflow {
         step: WorkflowTask,
     },
+    /// Usage of a subworkflow, added to the usage of its tasks
+    Usage(WorkflowUsage),
 }
 
 impl TemplateRegister for Workflow {
@@ -139,6 +151,14 @@ impl TemplateRegister for &Task {
                             .collect::<Vec<&str>>(),
                     )?;
                 }
+                if let Some(params) = &execute_sql.params {
+                    register.entries(
+                        params
+                            .values()
+                            .map(|param| param.value.as_str())
+                            .collect::<Vec<&str>>(),
+                    )?;
+                }
                 if let Some(export) = &execute_sql.export {
                     register.entry(&export.path.as_str())?;
                 }
@@ -170,15 +190,37 @@ impl TemplateRegister for Vec<Task> {
 }
 
 pub struct WorkflowReceiver {
+    agent_receiver: AgentReceiver,
     consensus_receiver: ConsensusReceiver,
+    usage: Arc<Mutex<WorkflowUsage>>,
 }
 
 impl WorkflowReceiver {
     pub fn new() -> Self {
         Self {
+            agent_receiver: AgentReceiver::new(),
             consensus_receiver: ConsensusReceiver::new(),
+            usage: Arc::new(Mutex::new(WorkflowUsage::default())),
         }
     }
+
+    /// Usage of the tasks the receiver got events of, which is complete once
+    /// the workflow has run.
+    pub fn usage(&self) -> Arc<Mutex<WorkflowUsage>> {
+        self.usage.clone()
+    }
+
+    fn print_usage(&self) {
+        let usage = self.usage.lock().unwrap();
+        if usage.tasks.is_empty() {
+            return;
+        }
+        println!("{}", "\nUsage:".primary());
+        for (task, task_usage) in &usage.tasks {
+            println!("  {}: {}", task, task_usage);
+        }
+        println!("  {}", format!("Total: {}", usage.total).text());
+    }
 }
 
 impl Handler for WorkflowReceiver {
@@ -232,6 +274,12 @@ impl Handler for WorkflowReceiver {
                     format!("Failed to write cache to {}: {}", path, err).error()
                 );
             }
+            WorkflowEvent::QueryCacheHit { database } => {
+                println!(
+                    "{}",
+                    format!("Query result served from {} query cache.", database).primary()
+                );
+            }
             WorkflowEvent::TaskUnknown { name } => {
                 println!(
                     "{}",
@@ -240,13 +288,20 @@ impl Handler for WorkflowReceiver {
             }
             WorkflowEvent::Finished => {
                 println!("{}", "\n✅Workflow executed successfully".success());
+                self.print_usage();
+            }
+            WorkflowEvent::Usage(usage) => {
+                self.usage.lock().unwrap().merge(usage);
             }
             WorkflowEvent::Retry { err, after } => {
                 println!("{}", format!("\nRetrying after {:?} ...", after).warning());
                 println!("Reason {:?}", err);
             }
-            WorkflowEvent::Agent { orig, .. } => {
-                AgentReceiver.handle(orig);
+            WorkflowEvent::Agent { orig, task, .. } => {
+                if let AgentEvent::Usage(usage) = orig {
+                    self.usage.lock().unwrap().add(&task.name, usage);
+                }
+                self.agent_receiver.handle(orig);
             }
             WorkflowEvent::Consensus { orig, .. } => {
                 self.consensus_receiver.handle(orig);
@@ -318,10 +373,9 @@ pub async fn run_workflow(workflow_path: &PathBuf) -> Result<WorkflowResult, Ony
         .build()
         .await?;
     let workflow = config.resolve_workflow(workflow_path).await?;
-    let dispatcher = Dispatcher::new(vec![
-        Box::new(WorkflowReceiver::new()),
-        Box::new(WorkflowExporter),
-    ]);
+    let receiver = WorkflowReceiver::new();
+    let usage = receiver.usage();
+    let dispatcher = Dispatcher::new(vec![Box::new(receiver), Box::new(WorkflowExporter)]);
     let executor = WorkflowExecutor::new(workflow.clone());
     let ctx = Value::from_serialize(&workflow.variables);
     let output = run(
@@ -333,5 +387,6 @@ pub async fn run_workflow(workflow_path: &PathBuf) -> Result<WorkflowResult, Ony
         dispatcher,
     )
     .await?;
-    Ok(WorkflowResult { output })
+    let usage = usage.lock().unwrap().clone();
+    Ok(WorkflowResult { output, usage })
 }
@@ -5,7 +5,12 @@ pub mod connector;
 pub mod db;
 pub mod errors;
 pub mod execute;
+pub mod query_cache;
+pub mod result_store;
 pub mod service;
+pub mod sql_guard;
+pub mod sql_params;
+pub mod sql_script;
 pub mod theme;
 pub mod utils;
 pub mod workflow;
@@ -18,7 +23,10 @@ use config::{model::RetrievalTool, ConfigManager};
 
 pub async fn build(config: &ConfigManager) -> anyhow::Result<()> {
     println!("{}", "Building...".text());
-    build_embeddings(config).await?;
+    let usage = build_embeddings(config).await?;
+    if usage.embedding_tokens > 0 {
+        println!("{}", format!("Usage: {}", usage).text());
+    }
     Ok(())
 }
 
@@ -40,9 +48,10 @@ pub async fn vector_search(
     let db_path = config
         .resolve_file(format!(".db-{}-{}", agent, retrieval.name))
         .await?;
-    let db = get_vector_store(retrieval, &db_path)?;
-    let documents = db.search(query).await?;
-    for document in documents {
+    let api_key = config.resolve_secret(&retrieval.api_key_ref()).await?;
+    let db = get_vector_store(retrieval, &db_path, &api_key)?;
+    let results = db.search(query).await?;
+    for document in results.documents {
         println!("{}", format!("{}\n", document.content).text());
         println!("____________________________________________________");
     }
@@ -0,0 +1,197 @@
+use log::debug;
+use sha2::{Digest, Sha256};
+use sqlparser::{
+    dialect::{dialect_from_str, GenericDialect},
+    tokenizer::{Token, Tokenizer, Whitespace},
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+use crate::db::client::get_state_dir;
+use crate::sql_params::ParamValue;
+
+const CACHE_EXTENSION: &str = "arrow";
+pub const DEFAULT_QUERY_CACHE_TTL_SECONDS: u64 = 60 * 60;
+pub const DEFAULT_QUERY_CACHE_MAX_SIZE_MB: u64 = 1024;
+
+/// Whether a query result was served from the query cache.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+}
+
+/// Result files of read-only queries, keyed by database and normalized SQL, so
+/// that repeated queries, e.g. from consistency or consensus runs, skip the
+/// database. Entries expire after the TTL and the oldest ones are evicted once
+/// the cache grows past its max size.
+#[derive(Debug, Clone)]
+pub struct QueryCache {
+    dir: PathBuf,
+    ttl: Duration,
+    max_size: u64,
+}
+
+impl QueryCache {
+    pub fn new(dir: PathBuf, ttl: Duration, max_size: u64) -> Self {
+        Self { dir, ttl, max_size }
+    }
+
+    pub fn default_dir() -> PathBuf {
+        Path::new(&get_state_dir()).join("query_cache")
+    }
+
+    /// Hashes the query with its parameters and `database`, which must tell
+    /// apart every database sharing the cache directory, e.g. databases of the
+    /// same name in different projects. The hash is stable across builds, so
+    /// entries outlive an upgrade.
+    pub fn key(database: &str, dialect: &str, query: &str, params: &[ParamValue]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(database);
+        hasher.update([0]);
+        hasher.update(normalize_sql(query, dialect));
+        for param in params {
+            hasher.update([0]);
+            hasher.update(format!("{:?}", param));
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Returns the cached result file for `key` unless it has expired.
+    pub fn get(&self, key: &str) -> Option<PathBuf> {
+        let path = self.entry_path(key);
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        (age <= self.ttl).then_some(path)
+    }
+
+    /// Stores a copy of `result_file` under `key`, then evicts the oldest
+    /// entries while the cache is larger than its max size.
+    pub fn put(&self, key: &str, result_file: &str) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        // Copy to a temporary file first so readers never see a partial entry
+        let tmp_path = self.dir.join(format!("{}.tmp", Uuid::new_v4()));
+        fs::copy(result_file, &tmp_path)?;
+        fs::rename(&tmp_path, self.entry_path(key))?;
+        self.evict()
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", key, CACHE_EXTENSION))
+    }
+
+    fn evict(&self) -> std::io::Result<()> {
+        let mut entries = vec![];
+        for entry in fs::read_dir(&self.dir)?.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) != Some(CACHE_EXTENSION) {
+                continue;
+            }
+            if let Ok(metadata) = entry.metadata() {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                entries.push((modified, metadata.len(), path));
+            }
+        }
+        let mut size = entries.iter().map(|(_, len, _)| len).sum::<u64>();
+        entries.sort();
+        for (_, len, path) in entries {
+            if size <= self.max_size {
+                break;
+            }
+            debug!("Evicting query cache entry {}", path.display());
+            fs::remove_file(&path)?;
+            size -= len;
+        }
+        Ok(())
+    }
+}
+
+/// Lists the tokens of the query without whitespace and trailing semicolons,
+/// so that formatting differences do not cause cache misses while literals,
+/// being single tokens, keep their whitespace. A query that cannot be
+/// tokenized is used verbatim.
+fn normalize_sql(query: &str, dialect: &str) -> String {
+    let dialect = dialect_from_str(dialect).unwrap_or_else(|| Box::new(GenericDialect {}));
+    let mut tokens = match Tokenizer::new(dialect.as_ref(), query).tokenize() {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            debug!("Failed to tokenize query, caching it verbatim: {}", e);
+            return query.to_string();
+        }
+    };
+    tokens.retain(|token| {
+        !matches!(
+            token,
+            Token::Whitespace(Whitespace::Space | Whitespace::Newline | Whitespace::Tab)
+                | Token::EOF
+        )
+    });
+    while tokens.last() == Some(&Token::SemiColon) {
+        tokens.pop();
+    }
+    // Debug output escapes the text of each token, so joined by newlines
+    // different token lists cannot give the same string
+    tokens
+        .iter()
+        .map(|token| format!("{:?}", token))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QueryCache;
+    use crate::sql_params::ParamValue;
+    use std::fs;
+    use std::time::Duration;
+
+    #[test]
+    fn key_ignores_whitespace_outside_literals() {
+        let key = |query| QueryCache::key("sqlite", "sqlite", query, &[]);
+        assert_eq!(
+            key("SELECT a FROM t WHERE b = 'x y'"),
+            key("SELECT a\n  FROM t\nWHERE b = 'x y';")
+        );
+        assert_ne!(
+            key("SELECT a FROM t WHERE b = 'x y'"),
+            key("SELECT a FROM t WHERE b = 'x  y'")
+        );
+    }
+
+    #[test]
+    fn key_depends_on_database_and_params() {
+        let query = "SELECT a FROM t WHERE b = ?";
+        let key = QueryCache::key("one", "sqlite", query, &[ParamValue::Integer(1)]);
+        assert_ne!(
+            key,
+            QueryCache::key("two", "sqlite", query, &[ParamValue::Integer(1)])
+        );
+        assert_ne!(
+            key,
+            QueryCache::key("one", "sqlite", query, &[ParamValue::Integer(2)])
+        );
+    }
+
+    #[test]
+    fn evicts_oldest_entries_past_max_size() {
+        let dir = std::env::temp_dir().join(format!("onyx-query-cache-{}", std::process::id()));
+        let result = dir.join("result.arrow.src");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&result, [0; 10]).unwrap();
+        let cache = QueryCache::new(dir.join("cache"), Duration::from_secs(60), 15);
+
+        cache.put("first", result.to_str().unwrap()).unwrap();
+        assert!(cache.get("first").is_some());
+        // Modification times must differ for the first entry to be the oldest
+        std::thread::sleep(Duration::from_millis(10));
+        cache.put("second", result.to_str().unwrap()).unwrap();
+        assert!(cache.get("first").is_none());
+        assert!(cache.get("second").is_some());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
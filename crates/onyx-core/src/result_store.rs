@@ -0,0 +1,119 @@
+use arrow::datatypes::SchemaRef;
+use arrow::error::ArrowError;
+use arrow::ipc::{reader::FileReader, writer::FileWriter};
+use arrow::record_batch::RecordBatch;
+use log::debug;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+use crate::db::client::get_state_dir;
+
+const RESULT_EXTENSION: &str = "arrow";
+pub const DEFAULT_RESULT_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+/// Directory holding query results as arrow IPC files. Results are addressed
+/// by their file path, which is what tools and events pass around, and expire
+/// once they have not been modified for longer than the TTL.
+#[derive(Debug, Clone)]
+pub struct ResultStore {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl Default for ResultStore {
+    fn default() -> Self {
+        Self::new(
+            Self::default_dir(),
+            Duration::from_secs(DEFAULT_RESULT_TTL_SECONDS),
+        )
+    }
+}
+
+impl ResultStore {
+    pub fn new(dir: PathBuf, ttl: Duration) -> Self {
+        Self { dir, ttl }
+    }
+
+    pub fn default_dir() -> PathBuf {
+        Path::new(&get_state_dir()).join("results")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes `batches` to a new result file and returns its path.
+    pub fn write(&self, batches: &[RecordBatch], schema: &SchemaRef) -> anyhow::Result<String> {
+        fs::create_dir_all(&self.dir)?;
+        let file_path = self
+            .dir
+            .join(format!("{}.{}", Uuid::new_v4(), RESULT_EXTENSION));
+        let file = File::create(&file_path)?;
+        if batches.is_empty() {
+            debug!("Warning: query returned no results.");
+        }
+
+        debug!("Schema: {:?}", schema);
+        let mut writer = FileWriter::try_new(file, schema.as_ref())?;
+        for batch in batches {
+            writer.write(batch)?;
+        }
+        writer.finish()?;
+        Ok(file_path.to_string_lossy().to_string())
+    }
+
+    /// Copies an existing result file, e.g. a cached one, into the store and
+    /// returns the path of the copy.
+    pub fn copy_from<P: AsRef<Path>>(&self, source: P) -> anyhow::Result<String> {
+        fs::create_dir_all(&self.dir)?;
+        let file_path = self
+            .dir
+            .join(format!("{}.{}", Uuid::new_v4(), RESULT_EXTENSION));
+        fs::copy(source, &file_path)?;
+        Ok(file_path.to_string_lossy().to_string())
+    }
+
+    /// Removes result files older than the TTL, or every result file when
+    /// `all` is set. Returns the number of files removed.
+    pub fn gc(&self, all: bool) -> std::io::Result<usize> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+        let now = SystemTime::now();
+        let mut removed = 0;
+        for entry in fs::read_dir(&self.dir)?.flatten() {
+            let path = entry.path();
+            if !path.is_file()
+                || path.extension().and_then(|s| s.to_str()) != Some(RESULT_EXTENSION)
+            {
+                continue;
+            }
+            let expired = all
+                || entry
+                    .metadata()
+                    .and_then(|metadata| metadata.modified())
+                    .map(|modified| now.duration_since(modified).unwrap_or_default() > self.ttl)
+                    .unwrap_or(false);
+            if expired {
+                fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+pub fn load_result(file_path: &str) -> anyhow::Result<(Vec<RecordBatch>, SchemaRef)> {
+    let file = File::open(file_path).map_err(|_| {
+        anyhow::Error::msg("Executed query did not generate a valid output file. If you are using an agent to generate the query, consider giving it a shorter prompt.".to_string())
+    })?;
+    let reader = FileReader::try_new(file, None)?;
+    let schema = reader.schema();
+    // Collect results and handle potential errors
+    let batches: Result<Vec<RecordBatch>, ArrowError> = reader.collect();
+    let batches = batches?;
+
+    Ok((batches, schema))
+}
@@ -1,20 +1,23 @@
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use crate::config::ConfigBuilder;
+use crate::execute::agent::{
+    run_agent_with_handler, AgentEvent, ConversationTurn, ToolCallRecorder, ToolMetadata,
+};
+use crate::execute::core::event::Handler;
 use crate::{
     config::model::FileFormat,
     db::{
         conversations::{create_conversation, get_conversation_by_agent},
-        message::save_message,
+        message::{get_messages_by_conversation, save_message, save_message_with_id},
     },
-    execute::agent::run_agent,
 };
 use async_stream::stream;
 use futures::Stream;
 use sea_orm::prelude::DateTimeWithTimeZone;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use uuid::Uuid;
 
 #[derive(Deserialize)]
@@ -32,12 +35,265 @@ pub struct AskRequest {
     pub memory: Vec<Memory>,
 }
 
+/// A line of the answer stream. Pieces of the answer share the answer's id
+/// and carry the next part of its `content`; tool call progress is sent with
+/// an empty `content`. A line with `reset` set means the pieces received so
+/// far were not the answer and should be cleared.
 #[derive(Serialize, Clone)]
 pub struct Message {
     content: String,
     id: Uuid,
     is_human: bool,
     created_at: DateTimeWithTimeZone,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call: Option<ToolCallProgress>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    reset: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ToolCallProgress {
+    name: String,
+    status: ToolCallStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sql: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallStatus {
+    Started,
+    Finished,
+}
+
+enum AnswerEvent {
+    Agent(AgentEvent),
+    /// The answer, or the error the agent failed with
+    Done {
+        answer: String,
+        failed: bool,
+    },
+}
+
+struct AnswerHandler {
+    sender: UnboundedSender<AnswerEvent>,
+    tool_calls: Arc<Mutex<ToolCallRecorder>>,
+}
+
+impl Handler for AnswerHandler {
+    type Event = AgentEvent;
+
+    fn handle(&self, event: &Self::Event) {
+        self.tool_calls.lock().unwrap().record(event);
+        let _ = self.sender.send(AnswerEvent::Agent(event.clone()));
+    }
+}
+
+/// Turns alternating questions and answers into conversation turns,
+/// dropping questions that were never answered.
+fn to_turns<'a>(
+    messages: impl IntoIterator<Item = (&'a str, bool, Option<&'a str>)>,
+) -> Vec<ConversationTurn> {
+    let mut turns = vec![];
+    let mut question: Option<&str> = None;
+    for (content, is_human, tool_calls) in messages {
+        if is_human {
+            question = Some(content);
+        } else if let Some(question) = question.take() {
+            turns.push(ConversationTurn {
+                question: question.to_string(),
+                tool_calls: tool_calls
+                    .and_then(|tool_calls| serde_json::from_str(tool_calls).ok())
+                    .unwrap_or_default(),
+                answer: content.to_string(),
+            });
+        }
+    }
+    turns
+}
+
+/// History of a conversation: the memory sent with the request if any, else
+/// the messages saved for the conversation.
+async fn load_history(
+    payload: &AskRequest,
+    conversation_id: Option<Uuid>,
+) -> Vec<ConversationTurn> {
+    if !payload.memory.is_empty() {
+        return to_turns(
+            payload
+                .memory
+                .iter()
+                .map(|memory| (memory.content.as_str(), memory.is_human, None)),
+        );
+    }
+    match conversation_id {
+        Some(conversation_id) => conversation_history(conversation_id).await,
+        None => vec![],
+    }
+}
+
+/// Turns of a conversation saved in the database, oldest first.
+pub async fn conversation_history(conversation_id: Uuid) -> Vec<ConversationTurn> {
+    match get_messages_by_conversation(conversation_id).await {
+        Ok(messages) => to_turns(messages.iter().map(|message| {
+            (
+                message.content.as_str(),
+                message.is_human,
+                message.tool_calls.as_deref(),
+            )
+        })),
+        Err(e) => {
+            log::warn!("Failed to load conversation history: {}", e);
+            vec![]
+        }
+    }
+}
+
+/// Where to save an answer: its id and the conversation it belongs to.
+struct SavedAnswer {
+    id: Uuid,
+    conversation_id: Uuid,
+}
+
+/// Runs the agent in the background, sending its events and then its answer.
+/// The answer is saved by the task itself, so that it is kept even when the
+/// client stops listening before the agent is done.
+fn run_agent_in_background(
+    payload: &AskRequest,
+    history: Vec<ConversationTurn>,
+    saved_answer: Option<SavedAnswer>,
+) -> UnboundedReceiver<AnswerEvent> {
+    let (sender, receiver) = unbounded_channel();
+    let project_path = PathBuf::from(&payload.project_path);
+    let agent_path = project_path.join(&payload.agent);
+    let question = payload.question.clone();
+    tokio::spawn(async move {
+        let tool_calls = Arc::new(Mutex::new(ToolCallRecorder::default()));
+        let handler = AnswerHandler {
+            sender: sender.clone(),
+            tool_calls: tool_calls.clone(),
+        };
+        let result = async {
+            let config = ConfigBuilder::new()
+                .with_project_path(project_path)?
+                .build()
+                .await?;
+            run_agent_with_handler(
+                &agent_path,
+                &FileFormat::Markdown,
+                Some(question),
+                history,
+                Arc::new(config),
+                handler,
+            )
+            .await
+        }
+        .await;
+        let (answer, failed) = match result {
+            Ok(output) => (output.output.to_string(), false),
+            Err(e) => (format!("Error running agent: {}", e), true),
+        };
+        if let Some(saved_answer) = saved_answer {
+            let tool_calls = tool_calls.lock().unwrap().take();
+            let tool_calls = if tool_calls.is_empty() {
+                None
+            } else {
+                serde_json::to_string(&tool_calls).ok()
+            };
+            save_message_with_id(
+                saved_answer.id,
+                saved_answer.conversation_id,
+                &answer,
+                false,
+                tool_calls,
+            )
+            .await;
+        }
+        let _ = sender.send(AnswerEvent::Done { answer, failed });
+    });
+    receiver
+}
+
+/// Streams the answer to `payload` as it is generated, sending it whole if it
+/// could not be streamed.
+fn stream_answer(
+    payload: &AskRequest,
+    history: Vec<ConversationTurn>,
+    answer_id: Uuid,
+    created_at: DateTimeWithTimeZone,
+    saved_answer: Option<SavedAnswer>,
+) -> impl Stream<Item = Message> {
+    let events = run_agent_in_background(payload, history, saved_answer);
+    answer_messages(events, answer_id, created_at)
+}
+
+/// Turns the events of an agent run into the lines of the answer stream.
+fn answer_messages(
+    mut events: UnboundedReceiver<AnswerEvent>,
+    answer_id: Uuid,
+    created_at: DateTimeWithTimeZone,
+) -> impl Stream<Item = Message> {
+    let message = move |content: String, tool_call: Option<ToolCallProgress>| Message {
+        content,
+        id: answer_id,
+        is_human: false,
+        created_at,
+        tool_call,
+        reset: false,
+    };
+    stream! {
+        let mut streamed = false;
+        while let Some(event) = events.recv().await {
+            match event {
+                AnswerEvent::Agent(AgentEvent::Delta(delta)) => {
+                    streamed = true;
+                    yield message(delta, None);
+                }
+                AnswerEvent::Agent(AgentEvent::DeltasDiscarded) => {
+                    streamed = false;
+                    yield Message {
+                        reset: true,
+                        ..message(String::new(), None)
+                    };
+                }
+                AnswerEvent::Agent(AgentEvent::ToolCallStarted { name, .. }) => {
+                    let progress = ToolCallProgress {
+                        name,
+                        status: ToolCallStatus::Started,
+                        sql: None,
+                    };
+                    yield message(String::new(), Some(progress));
+                }
+                AnswerEvent::Agent(AgentEvent::ToolCall(tool_call)) => {
+                    let sql = tool_call
+                        .metadata
+                        .map(|ToolMetadata::ExecuteSQL { sql_query, .. }| sql_query);
+                    let progress = ToolCallProgress {
+                        name: tool_call.name,
+                        status: ToolCallStatus::Finished,
+                        sql,
+                    };
+                    yield message(String::new(), Some(progress));
+                }
+                AnswerEvent::Agent(_) => {}
+                AnswerEvent::Done { answer, failed } => {
+                    // The error replaces the part of the answer streamed
+                    // before the agent failed
+                    if streamed && failed {
+                        yield Message {
+                            reset: true,
+                            ..message(String::new(), None)
+                        };
+                    }
+                    // Answers that are anonymized or read from a file are
+                    // not streamed
+                    if !streamed || failed {
+                        yield message(answer, None);
+                    }
+                }
+            }
+        }
+    }
 }
 
 pub async fn ask(payload: AskRequest) -> impl Stream<Item = Message> {
@@ -52,115 +308,115 @@ pub async fn ask(payload: AskRequest) -> impl Stream<Item = Message> {
             conversation_id = new_conversation.id;
         }
     }
+    let history = load_history(&payload, Some(conversation_id)).await;
     let question = save_message(conversation_id, &payload.question, true).await;
-    let stream = stream! {
+    let answer_id = Uuid::new_v4();
+    let answer_stream = stream_answer(
+        &payload,
+        history,
+        answer_id,
+        chrono::offset::Utc::now().into(),
+        Some(SavedAnswer {
+            id: answer_id,
+            conversation_id,
+        }),
+    );
+    stream! {
         yield Message {
             content: payload.question.clone(),
             id: question.id,
             is_human: question.is_human,
             created_at: question.created_at,
+            tool_call: None,
+            reset: false,
         };
 
-    let project_path = PathBuf::from(payload.project_path.clone());
-    let agent_path= project_path.join(&payload.agent);
-    let config = ConfigBuilder::new()
-        .with_project_path(project_path)
-        .unwrap()
-        .build()
-        .await
-        .unwrap();
-
-    let result = match run_agent(
-        &agent_path,
-        &FileFormat::Markdown,
-        Some(payload.question),
-        Arc::new(config),
-    ).await {
-        Ok(output) => output.output.to_string(),
-        Err(e) => format!("Error running agent: {}", e),
-    };
-
-    let answer = save_message(
-        conversation_id,
-        &result,
-        false,
-    ).await;
-
-    let chunks = answer.content.chars()
-    .collect::<Vec<_>>()
-    .chunks(3)
-    .map(|chunk| chunk.iter().collect::<String>())
-    .collect::<Vec<_>>();
-
-    let msgs = chunks.into_iter().map(|chunk| {
-        Message {
-            content: chunk.to_string(),
-            id: answer.id,
-            is_human: answer.is_human,
-            created_at: answer.created_at,
+        for await message in answer_stream {
+            yield message;
         }
-    }).collect::<Vec<_>>();
-
-    for msg in msgs {
-        tokio::time::sleep(Duration::from_millis(10)).await;
-        yield msg;
     }
-    };
-
-    stream
 }
 
 pub async fn ask_preview(payload: AskRequest) -> impl Stream<Item = Message> {
-    let stream = stream! {
+    let history = load_history(&payload, None).await;
+    let answer_stream = stream_answer(
+        &payload,
+        history,
+        Uuid::new_v4(),
+        chrono::offset::Utc::now().into(),
+        None,
+    );
+    stream! {
         yield Message {
             content: payload.question.clone(),
             id: Uuid::new_v4(),
             is_human: true,
             created_at: chrono::offset::Utc::now().into(),
+            tool_call: None,
+            reset: false,
         };
 
-    let project_path = PathBuf::from(payload.project_path.clone());
-    let agent_path= project_path.join(&payload.agent);
-    let config = ConfigBuilder::new()
-        .with_project_path(project_path)
-        .unwrap()
-        .build()
-        .await
-        .unwrap();
-
-    let result = match run_agent(
-        &agent_path,
-        &FileFormat::Markdown,
-        Some(payload.question),
-        Arc::new(config),
-    ).await {
-        Ok(output) => output.output.to_string(),
-        Err(e) => format!("Error running agent: {}", e),
-    };
-
-    let answer_id = Uuid::new_v4();
-    let answer_created_at = chrono::offset::Utc::now().into();
+        for await message in answer_stream {
+            yield message;
+        }
+    }
+}
 
-    let chunks = result.chars()
-    .collect::<Vec<_>>()
-    .chunks(3)
-    .map(|chunk| chunk.iter().collect::<String>())
-    .collect::<Vec<_>>();
+#[cfg(test)]
+mod tests {
+    use super::{answer_messages, AnswerEvent};
+    use crate::execute::agent::AgentEvent;
+    use futures::StreamExt;
+    use tokio::sync::mpsc::unbounded_channel;
+    use uuid::Uuid;
 
-    let msgs = chunks.into_iter().map(|chunk| {
-        Message {
-            content: chunk.to_string(),
-            id: answer_id,
-            is_human: false,
-            created_at: answer_created_at,
+    /// Contents and reset flags of the lines streamed for `events`.
+    fn stream_events(events: Vec<AnswerEvent>) -> Vec<(String, bool)> {
+        let (sender, receiver) = unbounded_channel();
+        for event in events {
+            sender.send(event).unwrap();
         }
-    }).collect::<Vec<_>>();
+        drop(sender);
+        let messages = tokio::runtime::Runtime::new().unwrap().block_on(
+            answer_messages(receiver, Uuid::new_v4(), chrono::offset::Utc::now().into())
+                .collect::<Vec<_>>(),
+        );
+        messages
+            .into_iter()
+            .map(|message| (message.content, message.reset))
+            .collect()
+    }
 
-    for msg in msgs {
-        tokio::time::sleep(Duration::from_millis(10)).await;
-        yield msg;
+    #[test]
+    fn failure_after_deltas_resets_streamed_answer() {
+        let lines = stream_events(vec![
+            AnswerEvent::Agent(AgentEvent::Delta("There are ".to_string())),
+            AnswerEvent::Agent(AgentEvent::Delta("12".to_string())),
+            AnswerEvent::Done {
+                answer: "Error running agent: connection reset".to_string(),
+                failed: true,
+            },
+        ]);
+        assert_eq!(
+            lines,
+            vec![
+                ("There are ".to_string(), false),
+                ("12".to_string(), false),
+                (String::new(), true),
+                ("Error running agent: connection reset".to_string(), false),
+            ]
+        );
     }
-    };
 
-    stream
+    #[test]
+    fn streamed_answer_not_sent_again() {
+        let lines = stream_events(vec![
+            AnswerEvent::Agent(AgentEvent::Delta("There are 12 sales".to_string())),
+            AnswerEvent::Done {
+                answer: "There are 12 sales".to_string(),
+                failed: false,
+            },
+        ]);
+        assert_eq!(lines, vec![("There are 12 sales".to_string(), false)]);
+    }
 }
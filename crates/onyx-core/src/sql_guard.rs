@@ -0,0 +1,662 @@
+use std::{collections::HashSet, ops::ControlFlow};
+
+use sqlparser::{
+    ast::{
+        Expr, FunctionArg, FunctionArgExpr, FunctionArguments, JoinConstraint, JoinOperator,
+        ObjectName, Query, SelectItem, SetExpr, Statement, TableFactor, TableWithJoins, Visit,
+        Visitor,
+    },
+    dialect::{dialect_from_str, GenericDialect},
+    parser::Parser,
+};
+
+use crate::errors::OnyxError;
+
+/// Static checks applied to SQL generated by an agent before it reaches the database.
+#[derive(Debug, Default)]
+pub struct SQLGuard {
+    /// Only allow SELECT, WITH and EXPLAIN statements
+    pub read_only: bool,
+    /// Tables the query may reference, as `table` or `schema.table`
+    pub allowed_tables: Option<Vec<String>>,
+    /// Columns the query must not reference, as `column` or `table.column`
+    pub denied_columns: Vec<String>,
+}
+
+impl SQLGuard {
+    pub fn check(&self, sql: &str, dialect: &str) -> Result<(), OnyxError> {
+        if !self.read_only && self.allowed_tables.is_none() && self.denied_columns.is_empty() {
+            return Ok(());
+        }
+        let statements = parse_sql(sql, dialect)?;
+        if self.read_only {
+            ensure_read_only(&statements)?;
+        }
+        self.ensure_allowed(&statements)
+    }
+
+    fn ensure_allowed(&self, statements: &[Statement]) -> Result<(), OnyxError> {
+        let mut references = References::default();
+        for statement in statements.iter() {
+            let _ = statement.visit(&mut references);
+        }
+        let relations = &references.relations;
+
+        if self.allowed_tables.is_some() || !self.denied_columns.is_empty() {
+            if let Some(function) = references.sql_text_functions.first() {
+                return Err(OnyxError::SQLPolicyError(format!(
+                    "`{}` runs SQL passed as text, which cannot be checked against the tables and columns this agent may query, query the tables directly instead",
+                    function
+                )));
+            }
+        }
+
+        if let Some(allowed_tables) = &self.allowed_tables {
+            for relation in relations.iter() {
+                if !allowed_tables
+                    .iter()
+                    .any(|table| name_matches(&split_name(table), relation))
+                {
+                    return Err(OnyxError::SQLPolicyError(format!(
+                        "Table `{}` is not allowed for this agent. Allowed tables: {}",
+                        relation.join("."),
+                        allowed_tables.join(", ")
+                    )));
+                }
+            }
+        }
+
+        let mut restricted = vec![];
+        for denied_column in self.denied_columns.iter() {
+            let mut parts = split_name(denied_column);
+            let column = parts.pop().unwrap_or_default();
+            // `table.column` entries only apply when the table is queried
+            if !parts.is_empty()
+                && !relations
+                    .iter()
+                    .any(|relation| name_matches(&parts, relation))
+            {
+                continue;
+            }
+            restricted.push(denied_column);
+            if references
+                .columns
+                .iter()
+                .any(|identifier| identifier.last() == Some(&column))
+            {
+                return Err(OnyxError::SQLPolicyError(format!(
+                    "Column `{}` is restricted and cannot be queried, remove it from the query",
+                    denied_column
+                )));
+            }
+            if references.has_wildcard {
+                return Err(OnyxError::SQLPolicyError(format!(
+                    "`*` would expose the restricted column `{}`, list the needed columns explicitly",
+                    denied_column
+                )));
+            }
+        }
+
+        // Whole rows and implicit join columns cover restricted columns
+        // without naming them
+        let Some(denied_column) = restricted.first() else {
+            return Ok(());
+        };
+        if let Some(row) = references.columns.iter().find(|identifier| {
+            identifier.len() == 1 && references.row_names.contains(&identifier[0])
+        }) {
+            return Err(OnyxError::SQLPolicyError(format!(
+                "`{}` refers to a whole row, which would expose the restricted column `{}`, select the needed columns instead",
+                row[0], denied_column
+            )));
+        }
+        if let Some(join) = references.implicit_joins.first() {
+            return Err(OnyxError::SQLPolicyError(format!(
+                "`{}` joins on columns it does not name, which may include the restricted column `{}`, use JOIN ... ON instead",
+                join, denied_column
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Parses `sql` with the database dialect, falling back to the generic one.
+fn parse_sql(sql: &str, dialect: &str) -> Result<Vec<Statement>, OnyxError> {
+    let dialect = dialect_from_str(dialect).unwrap_or_else(|| Box::new(GenericDialect {}));
+    let statements = Parser::parse_sql(dialect.as_ref(), sql).map_err(|e| {
+        OnyxError::SQLPolicyError(format!(
+            "Could not parse the query, rewrite it as a plain SELECT: {}",
+            e
+        ))
+    })?;
+    if statements.is_empty() {
+        return Err(OnyxError::SQLPolicyError(
+            "The query does not contain any statement".to_string(),
+        ));
+    }
+    Ok(statements)
+}
+
+/// Rejects anything but SELECT, WITH and EXPLAIN statements, including writes
+/// nested inside them such as data-modifying CTEs or `SELECT ... INTO`.
+fn ensure_read_only(statements: &[Statement]) -> Result<(), OnyxError> {
+    match statements
+        .iter()
+        .try_for_each(|statement| statement.visit(&mut ReadOnlyVisitor))
+    {
+        ControlFlow::Continue(()) => Ok(()),
+        ControlFlow::Break(found) => Err(OnyxError::SQLPolicyError(format!(
+            "Only read-only queries (SELECT, WITH, EXPLAIN) are allowed, found {}",
+            found
+        ))),
+    }
+}
+
+fn split_name(name: &str) -> Vec<String> {
+    name.split('.').map(|part| part.to_lowercase()).collect()
+}
+
+fn object_name_parts(name: &ObjectName) -> Vec<String> {
+    name.0
+        .iter()
+        .map(|ident| ident.value.to_lowercase())
+        .collect()
+}
+
+/// Tells whether `name` is a function that runs SQL or reads a table given to
+/// it as text, e.g. Postgres `query_to_xml` or DuckDB `query`, which hides the
+/// tables and columns it reads from the checks.
+fn runs_sql_text(name: &ObjectName) -> bool {
+    let name = object_name_parts(name).pop().unwrap_or_default();
+    matches!(name.as_str(), "query" | "query_table")
+        || [
+            "query_to_xml",
+            "table_to_xml",
+            "cursor_to_xml",
+            "schema_to_xml",
+            "database_to_xml",
+            "dblink",
+        ]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+/// Compares names on their trailing parts so that `salaries` and
+/// `hr.salaries` match while `hr.salaries` and `public.salaries` do not.
+fn name_matches(a: &[String], b: &[String]) -> bool {
+    a.iter().rev().zip(b.iter().rev()).all(|(a, b)| a == b)
+}
+
+struct ReadOnlyVisitor;
+
+impl Visitor for ReadOnlyVisitor {
+    type Break = String;
+
+    fn pre_visit_statement(&mut self, statement: &Statement) -> ControlFlow<Self::Break> {
+        match statement {
+            Statement::Query(_) | Statement::Explain { .. } | Statement::ExplainTable { .. } => {
+                ControlFlow::Continue(())
+            }
+            statement => {
+                let keyword = statement
+                    .to_string()
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default()
+                    .to_uppercase();
+                ControlFlow::Break(keyword)
+            }
+        }
+    }
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<Self::Break> {
+        if !query.locks.is_empty() {
+            return ControlFlow::Break("a locking clause (FOR UPDATE/SHARE)".to_string());
+        }
+        if let SetExpr::Select(select) = query.body.as_ref() {
+            if select.into.is_some() {
+                return ControlFlow::Break("SELECT ... INTO".to_string());
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// CTEs declared by a query, of which only those declared before the one
+/// being visited can be referenced unless the `WITH` is `RECURSIVE`.
+struct CteScope {
+    names: Vec<String>,
+    queries: Vec<*const Query>,
+    recursive: bool,
+    visible: usize,
+}
+
+impl CteScope {
+    fn position(&self, query: &Query) -> Option<usize> {
+        self.queries
+            .iter()
+            .position(|cte_query| std::ptr::eq(*cte_query, query))
+    }
+}
+
+/// Relations and column identifiers referenced anywhere in a query, leaving
+/// out references to CTEs.
+#[derive(Default)]
+struct References {
+    relations: Vec<Vec<String>>,
+    /// CTEs in scope, innermost query last
+    cte_scopes: Vec<CteScope>,
+    columns: Vec<Vec<String>>,
+    has_wildcard: bool,
+    /// Names and aliases of queried tables, which as an identifier stand for
+    /// a whole row, e.g. `row_to_json(s)`
+    row_names: HashSet<String>,
+    /// `JOIN ... USING` and `NATURAL JOIN`s, which join on columns the query
+    /// does not reference
+    implicit_joins: Vec<String>,
+    /// Calls of functions that run SQL given as text, see `runs_sql_text`
+    sql_text_functions: Vec<String>,
+}
+
+impl References {
+    fn visit_set_expr(&mut self, set_expr: &SetExpr) {
+        match set_expr {
+            SetExpr::Select(select) => {
+                if select.projection.iter().any(|item| {
+                    matches!(
+                        item,
+                        SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(_, _)
+                    )
+                }) {
+                    self.has_wildcard = true;
+                }
+                for table in select.from.iter() {
+                    self.visit_joins(table);
+                }
+            }
+            SetExpr::SetOperation { left, right, .. } => {
+                self.visit_set_expr(left);
+                self.visit_set_expr(right);
+            }
+            _ => {}
+        }
+    }
+
+    fn is_cte(&self, relation: &[String]) -> bool {
+        relation.len() == 1
+            && self.cte_scopes.iter().any(|scope| {
+                scope.names[..scope.visible]
+                    .iter()
+                    .any(|name| name == &relation[0])
+            })
+    }
+
+    fn visit_joins(&mut self, table: &TableWithJoins) {
+        for join in table.joins.iter() {
+            let constraint = match &join.join_operator {
+                JoinOperator::Inner(constraint)
+                | JoinOperator::LeftOuter(constraint)
+                | JoinOperator::RightOuter(constraint)
+                | JoinOperator::FullOuter(constraint)
+                | JoinOperator::LeftSemi(constraint)
+                | JoinOperator::RightSemi(constraint)
+                | JoinOperator::LeftAnti(constraint)
+                | JoinOperator::RightAnti(constraint) => constraint,
+                _ => continue,
+            };
+            if matches!(
+                constraint,
+                JoinConstraint::Using(_) | JoinConstraint::Natural
+            ) {
+                self.implicit_joins.push(join.relation.to_string());
+            }
+        }
+    }
+}
+
+impl Visitor for References {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<Self::Break> {
+        if let Some(scope) = self.cte_scopes.last_mut() {
+            if let (Some(index), false) = (scope.position(query), scope.recursive) {
+                scope.visible = index;
+            }
+        }
+        let ctes = query
+            .with
+            .as_ref()
+            .map(|with| with.cte_tables.as_slice())
+            .unwrap_or_default();
+        let recursive = query.with.as_ref().is_some_and(|with| with.recursive);
+        self.cte_scopes.push(CteScope {
+            names: ctes
+                .iter()
+                .map(|cte| cte.alias.name.value.to_lowercase())
+                .collect(),
+            queries: ctes
+                .iter()
+                .map(|cte| cte.query.as_ref() as *const Query)
+                .collect(),
+            recursive,
+            visible: if recursive { ctes.len() } else { 0 },
+        });
+        self.visit_set_expr(&query.body);
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, query: &Query) -> ControlFlow<Self::Break> {
+        self.cte_scopes.pop();
+        if let Some(scope) = self.cte_scopes.last_mut() {
+            if let (Some(index), false) = (scope.position(query), scope.recursive) {
+                scope.visible = index + 1;
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_table_factor(&mut self, table_factor: &TableFactor) -> ControlFlow<Self::Break> {
+        match table_factor {
+            TableFactor::Table {
+                name, alias, args, ..
+            } => {
+                self.row_names.extend(object_name_parts(name).pop());
+                if let Some(alias) = alias {
+                    self.row_names.insert(alias.name.value.to_lowercase());
+                }
+                if args.is_some() && runs_sql_text(name) {
+                    self.sql_text_functions.push(name.to_string());
+                }
+            }
+            TableFactor::Function { name, .. } if runs_sql_text(name) => {
+                self.sql_text_functions.push(name.to_string())
+            }
+            TableFactor::NestedJoin {
+                table_with_joins, ..
+            } => self.visit_joins(table_with_joins),
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_relation(&mut self, relation: &ObjectName) -> ControlFlow<Self::Break> {
+        let relation = object_name_parts(relation);
+        if !self.is_cte(&relation) {
+            self.relations.push(relation);
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<Self::Break> {
+        match expr {
+            Expr::Identifier(ident) => self.columns.push(vec![ident.value.to_lowercase()]),
+            Expr::CompoundIdentifier(idents) => self.columns.push(
+                idents
+                    .iter()
+                    .map(|ident| ident.value.to_lowercase())
+                    .collect(),
+            ),
+            Expr::Wildcard(_) | Expr::QualifiedWildcard(_, _) => self.has_wildcard = true,
+            Expr::Function(function) => {
+                if runs_sql_text(&function.name) {
+                    self.sql_text_functions.push(function.name.to_string());
+                }
+                let name = function.name.to_string().to_lowercase();
+                // DuckDB's `COLUMNS(...)` selects every column matching a pattern
+                if name == "columns" {
+                    self.has_wildcard = true;
+                }
+                // `row_to_json(s.*)` passes whole rows, while `count(*)` only counts them
+                if let FunctionArguments::List(list) = &function.args {
+                    for arg in list.args.iter() {
+                        let (FunctionArg::Named { arg, .. }
+                        | FunctionArg::ExprNamed { arg, .. }
+                        | FunctionArg::Unnamed(arg)) = arg;
+                        match arg {
+                            FunctionArgExpr::QualifiedWildcard(_) => self.has_wildcard = true,
+                            FunctionArgExpr::Wildcard if name != "count" => {
+                                self.has_wildcard = true
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_only() -> SQLGuard {
+        SQLGuard {
+            read_only: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn read_only_allows_queries() {
+        for sql in [
+            "SELECT * FROM orders",
+            "WITH recent AS (SELECT * FROM orders) SELECT count(*) FROM recent",
+            "EXPLAIN SELECT * FROM orders",
+        ] {
+            assert!(read_only().check(sql, "postgres").is_ok(), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn read_only_rejects_writes() {
+        for sql in [
+            "DELETE FROM orders",
+            "DROP TABLE orders",
+            "COPY orders TO '/tmp/orders.csv'",
+            "SELECT * INTO orders_copy FROM orders",
+            "SELECT * FROM orders FOR UPDATE",
+            "WITH deleted AS (DELETE FROM orders RETURNING *) SELECT * FROM deleted",
+            "SELECT 1; DELETE FROM orders",
+        ] {
+            assert!(
+                matches!(
+                    read_only().check(sql, "postgres"),
+                    Err(OnyxError::SQLPolicyError(_))
+                ),
+                "{}",
+                sql
+            );
+        }
+    }
+
+    fn restricted() -> SQLGuard {
+        SQLGuard {
+            read_only: true,
+            allowed_tables: Some(vec!["orders".to_string(), "hr.salaries".to_string()]),
+            denied_columns: vec!["salaries.ssn".to_string()],
+        }
+    }
+
+    fn assert_rejected(guard: &SQLGuard, sql: &str, message: &str) {
+        match guard.check(sql, "postgres") {
+            Err(OnyxError::SQLPolicyError(error)) => {
+                assert!(error.contains(message), "{}: {}", sql, error)
+            }
+            result => panic!("{} was not rejected: {:?}", sql, result),
+        }
+    }
+
+    #[test]
+    fn allows_permitted_tables_and_columns() {
+        for sql in [
+            "SELECT id, amount FROM orders",
+            "SELECT * FROM orders",
+            "SELECT s.name, s.salary FROM hr.salaries s JOIN orders o ON o.employee_id = s.id",
+        ] {
+            assert!(restricted().check(sql, "postgres").is_ok(), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn rejects_disallowed_table() {
+        assert_rejected(
+            &restricted(),
+            "SELECT id FROM customers",
+            "Table `customers` is not allowed",
+        );
+        assert_rejected(
+            &restricted(),
+            "SELECT name FROM public.salaries",
+            "Table `public.salaries` is not allowed",
+        );
+    }
+
+    #[test]
+    fn rejects_denied_column() {
+        for sql in [
+            "SELECT ssn FROM salaries",
+            "SELECT s.ssn FROM hr.salaries s",
+            "SELECT name FROM salaries WHERE ssn LIKE '123%'",
+            "SELECT o.id FROM orders o JOIN salaries s ON s.ssn = o.ssn",
+        ] {
+            assert_rejected(&restricted(), sql, "Column `salaries.ssn` is restricted");
+        }
+        assert_rejected(
+            &restricted(),
+            "SELECT * FROM salaries",
+            "`*` would expose the restricted column",
+        );
+        assert_rejected(
+            &restricted(),
+            "SELECT s.* FROM salaries s",
+            "`*` would expose the restricted column",
+        );
+    }
+
+    #[test]
+    fn rejects_wildcard_function_arguments() {
+        for sql in [
+            "SELECT row_to_json(s.*) FROM salaries s",
+            "SELECT json_agg(salaries.*) FROM salaries",
+            "SELECT COLUMNS('.*') FROM salaries",
+        ] {
+            assert_rejected(&restricted(), sql, "`*` would expose the restricted column");
+        }
+        assert!(restricted()
+            .check("SELECT count(*) FROM salaries", "postgres")
+            .is_ok());
+    }
+
+    #[test]
+    fn ctes_only_shadow_tables_in_their_scope() {
+        assert_rejected(
+            &restricted(),
+            "SELECT ssn FROM (WITH salaries AS (SELECT 1) SELECT 1) x, salaries",
+            "Column `salaries.ssn` is restricted",
+        );
+        assert_rejected(
+            &restricted(),
+            "SELECT id FROM (WITH customers AS (SELECT 1 AS id) SELECT id FROM customers) x, customers",
+            "Table `customers` is not allowed",
+        );
+        assert_rejected(
+            &restricted(),
+            "WITH recent AS (SELECT id FROM customers), customers AS (SELECT 1 AS id) SELECT id FROM recent",
+            "Table `customers` is not allowed",
+        );
+        assert!(restricted()
+            .check(
+                "WITH customers AS (SELECT id FROM orders) SELECT c.id FROM customers c",
+                "postgres"
+            )
+            .is_ok());
+        assert!(restricted()
+            .check(
+                "WITH RECURSIVE customers AS (SELECT id FROM orders UNION ALL SELECT id FROM customers) SELECT id FROM customers",
+                "postgres"
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn rejects_whole_row_references() {
+        for sql in [
+            "SELECT to_json(s) FROM salaries s",
+            "SELECT row_to_json(salaries) FROM salaries",
+            "SELECT s FROM salaries s",
+            "SELECT o.id, s FROM orders o JOIN salaries s ON s.id = o.employee_id",
+        ] {
+            assert_rejected(&restricted(), sql, "refers to a whole row");
+        }
+    }
+
+    #[test]
+    fn rejects_functions_running_sql_text() {
+        for sql in [
+            "SELECT query_to_xml('SELECT ssn FROM salaries', true, false, '')",
+            "SELECT pg_catalog.query_to_xmlschema('SELECT * FROM customers', true, false, '')",
+            "SELECT table_to_xml('salaries', true, false, '')",
+            "SELECT * FROM dblink('dbname=hr', 'SELECT ssn FROM salaries') AS t(ssn text)",
+        ] {
+            assert_rejected(&restricted(), sql, "runs SQL passed as text");
+        }
+        for sql in [
+            "SELECT * FROM query('SELECT ssn FROM salaries')",
+            "SELECT * FROM query_table('salaries')",
+        ] {
+            match restricted().check(sql, "duckdb") {
+                Err(OnyxError::SQLPolicyError(error)) => {
+                    assert!(
+                        error.contains("runs SQL passed as text"),
+                        "{}: {}",
+                        sql,
+                        error
+                    )
+                }
+                result => panic!("{} was not rejected: {:?}", sql, result),
+            }
+        }
+        let denied_columns_only = SQLGuard {
+            denied_columns: vec!["ssn".to_string()],
+            ..Default::default()
+        };
+        assert_rejected(
+            &denied_columns_only,
+            "SELECT query_to_xml('SELECT ssn FROM salaries', true, false, '')",
+            "runs SQL passed as text",
+        );
+        assert!(read_only()
+            .check(
+                "SELECT query_to_xml('SELECT 1', true, false, '')",
+                "postgres"
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn rejects_implicit_join_columns() {
+        for sql in [
+            "SELECT o.id FROM orders o JOIN salaries s USING (ssn)",
+            "SELECT o.id FROM orders o NATURAL JOIN salaries s",
+        ] {
+            assert_rejected(&restricted(), sql, "joins on columns it does not name");
+        }
+    }
+
+    #[test]
+    fn denied_columns_only_apply_to_their_table() {
+        assert!(restricted()
+            .check("SELECT o FROM orders o NATURAL JOIN orders p", "postgres")
+            .is_ok());
+    }
+
+    #[test]
+    fn rejects_unparsable_sql() {
+        assert!(read_only()
+            .check("SELEC * FROM orders", "postgres")
+            .is_err());
+    }
+}
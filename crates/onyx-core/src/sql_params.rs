@@ -0,0 +1,165 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::config::model::ParamType;
+use crate::errors::OnyxError;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const TIMESTAMP_FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"];
+
+/// Typed value bound to a `:name` placeholder in a query.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Date(NaiveDate),
+    Timestamp(NaiveDateTime),
+}
+
+impl ParamValue {
+    /// Parses `value` as `param_type`, failing instead of letting a malformed
+    /// value reach the database.
+    pub fn parse(name: &str, param_type: &ParamType, value: &str) -> Result<Self, OnyxError> {
+        let invalid = |e: &dyn fmt::Display| {
+            OnyxError::ArgumentError(format!(
+                "Invalid value '{}' for {} parameter '{}': {}",
+                value, param_type, name, e
+            ))
+        };
+        let value = match param_type {
+            ParamType::String => ParamValue::String(value.to_string()),
+            ParamType::Integer => {
+                ParamValue::Integer(value.trim().parse().map_err(|e| invalid(&e))?)
+            }
+            ParamType::Float => {
+                let float: f64 = value.trim().parse().map_err(|e| invalid(&e))?;
+                if !float.is_finite() {
+                    return Err(invalid(&"expected a finite number"));
+                }
+                ParamValue::Float(float)
+            }
+            ParamType::Boolean => ParamValue::Boolean(
+                value
+                    .trim()
+                    .to_lowercase()
+                    .parse()
+                    .map_err(|e| invalid(&e))?,
+            ),
+            ParamType::Date => ParamValue::Date(
+                NaiveDate::parse_from_str(value.trim(), DATE_FORMAT).map_err(|e| invalid(&e))?,
+            ),
+            ParamType::Timestamp => {
+                let value = value.trim();
+                let timestamp = TIMESTAMP_FORMATS
+                    .iter()
+                    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+                    .or_else(|| {
+                        NaiveDate::parse_from_str(value, DATE_FORMAT)
+                            .ok()
+                            .and_then(|date| date.and_hms_opt(0, 0, 0))
+                    })
+                    .ok_or_else(|| invalid(&"expected YYYY-MM-DD HH:MM:SS"))?;
+                ParamValue::Timestamp(timestamp)
+            }
+        };
+        Ok(value)
+    }
+
+    /// Parses a command line parameter given as `name=value` or
+    /// `name:type=value`, defaulting to a string.
+    pub fn from_arg(key: &str, value: &str) -> Result<(String, Self), OnyxError> {
+        let (name, param_type) = match key.split_once(':') {
+            Some((name, param_type)) => (
+                name,
+                param_type
+                    .parse::<ParamType>()
+                    .map_err(OnyxError::ArgumentError)?,
+            ),
+            None => (key, ParamType::String),
+        };
+        Ok((name.to_string(), Self::parse(name, &param_type, value)?))
+    }
+}
+
+/// Rewrites `:name` placeholders in `query` using `placeholder`, which maps
+/// the name and its 1-based position to the engine's own syntax, and returns
+/// the rewritten query with the values in placeholder order.
+///
+/// Placeholders inside string literals, quoted identifiers and comments are
+/// left alone, as are `::` casts and names that are not declared in `params`.
+pub fn bind_params(
+    query: &str,
+    params: &HashMap<String, ParamValue>,
+    mut placeholder: impl FnMut(&str, usize) -> String,
+) -> (String, Vec<ParamValue>) {
+    if params.is_empty() {
+        return (query.to_string(), vec![]);
+    }
+    let chars = query.chars().collect::<Vec<char>>();
+    let mut output = String::with_capacity(query.len());
+    let mut values = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let skip_until = match c {
+            '\'' | '"' | '`' => Some(closing_quote(&chars, i, c)),
+            '-' if chars.get(i + 1) == Some(&'-') => Some(
+                (i..chars.len())
+                    .find(|&j| chars[j] == '\n')
+                    .unwrap_or(chars.len()),
+            ),
+            '/' if chars.get(i + 1) == Some(&'*') => Some(
+                (i + 2..chars.len())
+                    .find(|&j| chars[j] == '*' && chars.get(j + 1) == Some(&'/'))
+                    .map(|j| j + 2)
+                    .unwrap_or(chars.len()),
+            ),
+            _ => None,
+        };
+        if let Some(end) = skip_until {
+            output.extend(&chars[i..end]);
+            i = end;
+            continue;
+        }
+        if c == ':'
+            && chars
+                .get(i + 1)
+                .is_some_and(|c| c.is_alphabetic() || *c == '_')
+            && (i == 0 || chars[i - 1] != ':')
+        {
+            let end = (i + 1..chars.len())
+                .find(|&j| !(chars[j].is_alphanumeric() || chars[j] == '_'))
+                .unwrap_or(chars.len());
+            let name = chars[i + 1..end].iter().collect::<String>();
+            if let Some(value) = params.get(&name) {
+                values.push(value.clone());
+                output.push_str(&placeholder(&name, values.len()));
+                i = end;
+                continue;
+            }
+        }
+        output.push(c);
+        i += 1;
+    }
+    (output, values)
+}
+
+/// Returns the index right after the quote closing the one at `start`,
+/// treating a doubled quote character as an escaped one.
+fn closing_quote(chars: &[char], start: usize, quote: char) -> usize {
+    let mut i = start + 1;
+    while i < chars.len() {
+        if chars[i] == quote {
+            if chars.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    chars.len()
+}
@@ -0,0 +1,66 @@
+use log::debug;
+use sqlparser::{
+    dialect::{dialect_from_str, GenericDialect},
+    tokenizer::{Location, Token, Tokenizer},
+};
+
+/// Splits a SQL script into its `;`-separated statements. The script is
+/// tokenized with the database's dialect, so semicolons inside string
+/// literals, quoted identifiers, comments or e.g. Postgres dollar-quoted
+/// bodies do not split it. Statements keep their original text, and empty
+/// ones, including trailing comments, are dropped.
+///
+/// A script that cannot be tokenized is returned as a single statement, so
+/// that the database gets to report the error.
+pub fn split_statements(sql: &str, dialect: &str) -> Vec<String> {
+    let dialect = dialect_from_str(dialect).unwrap_or_else(|| Box::new(GenericDialect {}));
+    let tokens = match Tokenizer::new(dialect.as_ref(), sql).tokenize_with_location() {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            debug!(
+                "Failed to split SQL script, running it as one statement: {}",
+                e
+            );
+            return vec![sql.trim().to_string()];
+        }
+    };
+
+    // Location of each separator, and whether the statement it ends is empty
+    let mut separators: Vec<(Location, bool)> = vec![];
+    let mut empty = true;
+    for token in &tokens {
+        match token.token {
+            Token::SemiColon => {
+                separators.push((token.span.start, empty));
+                empty = true;
+            }
+            Token::Whitespace(_) | Token::EOF => {}
+            _ => empty = false,
+        }
+    }
+
+    // Locations are 1-based lines and columns counted in characters
+    let mut statements = vec![];
+    let mut separators = separators.into_iter().peekable();
+    let (mut line, mut column, mut start) = (1, 1, 0);
+    for (offset, c) in sql.char_indices() {
+        if let Some((_, separated_empty)) =
+            separators.next_if(|(location, _)| location.line == line && location.column == column)
+        {
+            if !separated_empty {
+                statements.push(sql[start..offset].trim().to_string());
+            }
+            start = offset + c.len_utf8();
+        }
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    if !empty {
+        statements.push(sql[start..].trim().to_string());
+    }
+    statements
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::fs;
+use std::time::Duration;
 
 use minijinja::value::Kwargs;
 use minijinja::{context, Value};
@@ -9,6 +10,7 @@ use crate::config::model::FileFormat;
 use crate::config::model::FormatterTask;
 use crate::config::model::LoopSequentialTask;
 use crate::config::model::LoopValues;
+use crate::config::model::StatementResults;
 use crate::config::model::Task;
 use crate::config::model::TaskType;
 use crate::config::model::Workflow;
@@ -22,13 +24,16 @@ use crate::execute::consensus::ConsensusExecutor;
 use crate::execute::core::arrow_table::ArrowTable;
 use crate::execute::core::cache::Cacheable;
 use crate::execute::core::event::Dispatcher;
-use crate::execute::core::value::ContextValue;
+use crate::execute::core::value::{Array, ContextValue};
 use crate::execute::core::write::Write;
 use crate::execute::core::ExecutionContext;
 use crate::execute::core::{run, Executable};
 use crate::execute::workflow::WorkflowEvent;
 use crate::execute::workflow::WorkflowInput;
 use crate::execute::workflow::{LoopInput, WorkflowExporter, WorkflowReceiver};
+use crate::query_cache::CacheStatus;
+use crate::result_store::load_result;
+use crate::sql_params::ParamValue;
 
 use super::cache::AgentCache;
 use super::cache::FileCache;
@@ -102,6 +107,7 @@ impl Executable<WorkflowInput, WorkflowEvent> for AgentTask {
                 AgentInput {
                     prompt: Some(prompt),
                     system_instructions: agent_config.system_instructions.clone(),
+                    history: vec![],
                 },
                 map_agent_event,
                 global_context,
@@ -128,6 +134,16 @@ impl Executable<WorkflowInput, WorkflowEvent> for ExecuteSQLTask {
                 variables.insert(key.clone(), rendered_value);
             }
         }
+        let mut params = HashMap::new();
+        if let Some(sql_params) = &self.params {
+            for (name, param) in sql_params {
+                let rendered_value = execution_context.renderer.render(&param.value)?;
+                params.insert(
+                    name.clone(),
+                    ParamValue::parse(name, &param.param_type, &rendered_value)?,
+                );
+            }
+        }
 
         let query = match &self.sql {
             SQL::Query { sql_query } => {
@@ -169,11 +185,46 @@ impl Executable<WorkflowInput, WorkflowEvent> for ExecuteSQLTask {
             }
         };
 
-        let (datasets, schema) =
-            Connector::from_database(&self.database, execution_context.config.as_ref())
-                .await?
-                .run_query_and_load(&query)
-                .await?;
+        let connector = Connector::from_database(&self.database, execution_context.config.as_ref())
+            .await?
+            .with_timeout(self.timeout.map(Duration::from_secs))
+            .with_params(params);
+        let all = self.results == StatementResults::All;
+        let statements = connector.split_statements(&query);
+        // The query shown for each result kept, with its result file. Scripts
+        // bypass the query cache, as their earlier statements set up state
+        // that a cached result would not reflect
+        let results = if statements.len() > 1 {
+            let file_paths = connector.run_statements(&statements, all).await?;
+            if all {
+                statements.into_iter().zip(file_paths).collect::<Vec<_>>()
+            } else {
+                file_paths
+                    .into_iter()
+                    .map(|file_path| (query.clone(), file_path))
+                    .collect()
+            }
+        } else {
+            let (file_path, cache_status) = connector.run_query_cached(&query).await?;
+            match cache_status {
+                Some(CacheStatus::Hit) => {
+                    execution_context
+                        .notify(WorkflowEvent::QueryCacheHit {
+                            database: self.database.clone(),
+                        })
+                        .await?
+                }
+                Some(CacheStatus::Miss) => {
+                    execution_context
+                        .notify(WorkflowEvent::QueryCacheMiss {
+                            database: self.database.clone(),
+                        })
+                        .await?
+                }
+                None => {}
+            }
+            vec![(query, file_path)]
+        };
         let mut export_file_path = String::new();
         if let Some(export) = &self.export {
             let relative_export_file_path = execution_context.renderer.render(&export.path)?;
@@ -183,16 +234,25 @@ impl Executable<WorkflowInput, WorkflowEvent> for ExecuteSQLTask {
                 .await?;
         }
 
-        execution_context
-            .notify(WorkflowEvent::ExecuteSQL {
-                task: self.clone(),
-                query,
-                datasets: datasets.clone(),
-                schema,
-                export_file_path,
-            })
-            .await?;
-        execution_context.write(ContextValue::Table(ArrowTable::new(datasets)));
+        let mut tables = vec![];
+        for (query, file_path) in results {
+            let (datasets, schema) = load_result(&file_path)?;
+            execution_context
+                .notify(WorkflowEvent::ExecuteSQL {
+                    task: self.clone(),
+                    query,
+                    datasets: datasets.clone(),
+                    schema,
+                    export_file_path: export_file_path.clone(),
+                })
+                .await?;
+            tables.push(ContextValue::Table(ArrowTable::new(datasets)));
+        }
+        if all {
+            execution_context.write(ContextValue::Array(Array(tables)));
+        } else if let Some(table) = tables.pop() {
+            execution_context.write(table);
+        }
         Ok(())
     }
 }
@@ -235,10 +295,9 @@ impl Executable<WorkflowInput, WorkflowEvent> for WorkflowTask {
         _input: WorkflowInput,
     ) -> Result<(), OnyxError> {
         let workflow = execution_context.config.resolve_workflow(&self.src).await?;
-        let dispatcher = Dispatcher::new(vec![
-            Box::new(WorkflowReceiver::new()),
-            Box::new(WorkflowExporter),
-        ]);
+        let receiver = WorkflowReceiver::new();
+        let usage = receiver.usage();
+        let dispatcher = Dispatcher::new(vec![Box::new(receiver), Box::new(WorkflowExporter)]);
         let executor = WorkflowExecutor::new(workflow.clone());
         let default_variables = workflow.variables.clone();
         let variables = if let Some(vars) = &self.variables {
@@ -264,6 +323,10 @@ impl Executable<WorkflowInput, WorkflowEvent> for WorkflowTask {
         execution_context
             .notify(WorkflowEvent::SubWorkflow { step: self.clone() })
             .await?;
+        let usage = usage.lock().unwrap().clone();
+        execution_context
+            .notify(WorkflowEvent::Usage(usage))
+            .await?;
 
         execution_context.write(output);
         Ok(())
@@ -370,6 +433,11 @@ impl Executable<(), WorkflowEvent> for Task {
     ) -> Result<(), OnyxError> {
         match &self.task_type {
             TaskType::Agent(agent) => {
+                // Usage of the agent is reported under the task's name
+                let agent = &AgentTask {
+                    name: self.name.clone(),
+                    ..agent.clone()
+                };
                 if agent.consensus_run > 1 {
                     let mut consensus_executor = ConsensusExecutor::new();
                     consensus_executor
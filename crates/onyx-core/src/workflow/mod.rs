@@ -1,5 +1,6 @@
 use pyo3::prelude::*;
 
+use crate::ai::usage::Usage;
 use crate::execute::core::value::ContextValue;
 
 pub mod executor;
@@ -11,4 +12,34 @@ pub mod cache;
 pub struct WorkflowResult {
     #[pyo3(get)]
     pub output: ContextValue,
+    #[pyo3(get)]
+    pub usage: WorkflowUsage,
+}
+
+/// Usage of a workflow run, in total and per task.
+#[pyclass(module = "onyx_py")]
+#[derive(Debug, Clone, Default)]
+pub struct WorkflowUsage {
+    #[pyo3(get)]
+    pub total: Usage,
+    /// Usage of each task that ran an agent, in the order they first reported
+    /// any, including the tasks of subworkflows
+    #[pyo3(get)]
+    pub tasks: Vec<(String, Usage)>,
+}
+
+impl WorkflowUsage {
+    pub fn add(&mut self, task: &str, usage: &Usage) {
+        self.total.add(usage);
+        match self.tasks.iter_mut().find(|(name, _)| name == task) {
+            Some((_, task_usage)) => task_usage.add(usage),
+            None => self.tasks.push((task.to_string(), usage.clone())),
+        }
+    }
+
+    pub fn merge(&mut self, other: &WorkflowUsage) {
+        for (task, usage) in &other.tasks {
+            self.add(task, usage);
+        }
+    }
 }
@@ -0,0 +1,113 @@
+mod common;
+
+#[cfg(test)]
+mod agent {
+    use crate::common::{setup_project, start_mock_server, text_response, tool_use_response};
+    use assert_cmd::Command;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn run_agent_with_parallel_tool_calls_ok() {
+        let tool_use = |id: &str, sql: &str| json!({"type": "tool_use", "id": id, "name": "execute_sql", "input": {"sql": sql}});
+        let (api_url, requests) = start_mock_server(vec![
+            json!({
+                "role": "assistant",
+                "content": [
+                    tool_use("toolu_01", "SELECT COUNT(*) AS sales FROM fruit_sales"),
+                    tool_use("toolu_02", "SELECT 1 AS one"),
+                    tool_use("toolu_03", "SELECT 2 AS two"),
+                ],
+                "stop_reason": "tool_use"
+            }),
+            text_response("There are many fruit sales."),
+        ]);
+        let project = setup_project("parallel", &api_url);
+        fs::write(
+            project.join("parallel.agent.yml"),
+            r#"
+model: claude
+system_instructions: You answer questions about fruit sales.
+parallel_tool_calls: true
+max_concurrent_tool_calls: 2
+tools:
+  - name: execute_sql
+    type: execute_sql
+    database: sqlite
+"#,
+        )
+        .unwrap();
+
+        let mut cmd = Command::cargo_bin("onyx").unwrap();
+        cmd.current_dir(&project)
+            .arg("run")
+            .arg("parallel.agent.yml")
+            .arg("how many fruit sales are there")
+            .assert()
+            .success();
+
+        let (_, first) = requests.recv().unwrap();
+        assert!(first.get("tool_choice").is_none());
+
+        let (_, second) = requests.recv().unwrap();
+        let messages = second["messages"].as_array().unwrap();
+        let tool_results = messages[2]["content"].as_array().unwrap();
+        let ids = tool_results
+            .iter()
+            .map(|result| result["tool_use_id"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["toolu_01", "toolu_02", "toolu_03"]);
+        assert!(tool_results[1]["content"].as_str().unwrap().contains("one"));
+        assert!(tool_results[2]["content"].as_str().unwrap().contains("two"));
+
+        fs::remove_dir_all(project).unwrap();
+    }
+
+    #[test]
+    fn run_agent_answers_when_out_of_tool_rounds_ok() {
+        let (api_url, requests) = start_mock_server(vec![
+            tool_use_response("toolu_01", "SELECT COUNT(*) AS sales FROM fruit_sales"),
+            text_response("There are many fruit sales."),
+        ]);
+        let project = setup_project("budget", &api_url);
+        fs::write(
+            project.join("budget.agent.yml"),
+            r#"
+model: claude
+system_instructions: You answer questions about fruit sales.
+max_tool_rounds: 1
+tools:
+  - name: execute_sql
+    type: execute_sql
+    database: sqlite
+"#,
+        )
+        .unwrap();
+
+        let mut cmd = Command::cargo_bin("onyx").unwrap();
+        let result = cmd
+            .current_dir(&project)
+            .arg("run")
+            .arg("budget.agent.yml")
+            .arg("how many fruit sales are there")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(output.contains("limit of 1 tool call rounds"));
+        assert!(output.contains("There are many fruit sales."));
+
+        requests.recv().unwrap();
+        let (_, last) = requests.recv().unwrap();
+        assert_eq!(last["tool_choice"]["type"], "none");
+        assert_eq!(last["tools"][0]["name"], "execute_sql");
+        let messages = last["messages"].as_array().unwrap();
+        let final_turn = messages.last().unwrap()["content"].as_array().unwrap();
+        assert_eq!(final_turn[0]["type"], "tool_result");
+        assert!(final_turn[1]["text"]
+            .as_str()
+            .unwrap()
+            .contains("without calling any more tools"));
+
+        fs::remove_dir_all(project).unwrap();
+    }
+}
@@ -0,0 +1,68 @@
+mod common;
+
+#[cfg(test)]
+mod anthropic {
+    use crate::common::{setup_project, start_mock_server};
+    use assert_cmd::Command;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn run_agent_with_tool_use_ok() {
+        let (api_url, requests) = start_mock_server(vec![
+            json!({
+                "role": "assistant",
+                "content": [
+                    {"type": "text", "text": "Let me count the sales."},
+                    {
+                        "type": "tool_use",
+                        "id": "toolu_01",
+                        "name": "execute_sql",
+                        "input": {"sql": "SELECT COUNT(*) AS sales FROM fruit_sales"}
+                    }
+                ],
+                "stop_reason": "tool_use"
+            }),
+            json!({
+                "role": "assistant",
+                "content": [{"type": "text", "text": "There are many fruit sales."}],
+                "stop_reason": "end_turn"
+            }),
+        ]);
+        let project = setup_project("run", &api_url);
+
+        let mut cmd = Command::cargo_bin("onyx").unwrap();
+        let result = cmd
+            .current_dir(&project)
+            .arg("run")
+            .arg("mock.agent.yml")
+            .arg("how many fruit sales are there")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(output.contains("There are many fruit sales."));
+
+        let (headers, first) = requests.recv().unwrap();
+        assert!(headers.contains("x-api-key: test-key"));
+        assert!(headers.contains("anthropic-version"));
+        assert_eq!(first["model"], "claude-test");
+        assert_eq!(first["system"], "You answer questions about fruit sales.");
+        assert_eq!(first["tools"][0]["name"], "execute_sql");
+        assert!(first["tools"][0]["input_schema"]["properties"]["sql"].is_object());
+        assert_eq!(first["messages"][0]["role"], "user");
+        assert_eq!(first["stream"], true);
+
+        let (_, second) = requests.recv().unwrap();
+        let messages = second["messages"].as_array().unwrap();
+        let tool_use = &messages[1]["content"][0];
+        assert_eq!(tool_use["type"], "tool_use");
+        assert_eq!(tool_use["id"], "toolu_01");
+        let tool_result = &messages[2]["content"][0];
+        assert_eq!(messages[2]["role"], "user");
+        assert_eq!(tool_result["type"], "tool_result");
+        assert_eq!(tool_result["tool_use_id"], "toolu_01");
+        assert!(tool_result["content"].as_str().unwrap().contains("sales"));
+
+        fs::remove_dir_all(project).unwrap();
+    }
+}
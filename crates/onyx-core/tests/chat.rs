@@ -0,0 +1,79 @@
+mod common;
+
+#[cfg(test)]
+mod chat {
+    use crate::common::{setup_project, start_mock_server, text_response, tool_use_response};
+    use assert_cmd::Command;
+    use std::fs;
+
+    #[test]
+    fn chat_with_follow_up_questions_ok() {
+        let (api_url, requests) = start_mock_server(vec![
+            tool_use_response("toolu_01", "SELECT COUNT(*) AS sales FROM fruit_sales"),
+            text_response("There are many fruit sales."),
+            text_response("Mostly apples."),
+            text_response("You're welcome."),
+        ]);
+        let project = setup_project("chat", &api_url);
+        let home = project.join("home");
+
+        let mut cmd = Command::cargo_bin("onyx").unwrap();
+        let result = cmd
+            .current_dir(&project)
+            .env("HOME", &home)
+            .arg("chat")
+            .arg("--agent")
+            .arg("mock.agent.yml")
+            .write_stdin(
+                "how many fruit sales are there\n/sql\nwhich fruit sells most?\n/export csv out/sales.csv\n/exit\n",
+            )
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(output.contains("There are many fruit sales."));
+        assert!(output.contains("Mostly apples."));
+        assert!(
+            output
+                .matches("SELECT COUNT(*) AS sales FROM fruit_sales")
+                .count()
+                >= 2
+        );
+        let csv = fs::read_to_string(project.join("out/sales.csv")).unwrap();
+        assert!(csv.starts_with("sales\n"));
+
+        requests.recv().unwrap();
+        requests.recv().unwrap();
+        let (_, follow_up) = requests.recv().unwrap();
+        let messages = follow_up["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 5);
+        assert_eq!(
+            messages[0]["content"][0]["text"],
+            "how many fruit sales are there"
+        );
+        assert_eq!(messages[1]["content"][0]["type"], "tool_use");
+        assert_eq!(messages[2]["content"][0]["type"], "tool_result");
+        assert_eq!(
+            messages[3]["content"][0]["text"],
+            "There are many fruit sales."
+        );
+        assert_eq!(messages[4]["content"][0]["text"], "which fruit sells most?");
+
+        // The conversation is saved, so a new session picks it up
+        let mut cmd = Command::cargo_bin("onyx").unwrap();
+        cmd.current_dir(&project)
+            .env("HOME", &home)
+            .arg("chat")
+            .arg("--agent")
+            .arg("mock.agent.yml")
+            .write_stdin("thanks\n")
+            .assert()
+            .success();
+        let (_, resumed) = requests.recv().unwrap();
+        let messages = resumed["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 7);
+        assert_eq!(messages[5]["content"][0]["text"], "Mostly apples.");
+        assert_eq!(messages[6]["content"][0]["text"], "thanks");
+
+        fs::remove_dir_all(project).unwrap();
+    }
+}
@@ -0,0 +1,241 @@
+//! Fixtures shared by the CLI tests that run agents against a mock model API.
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+
+/// Serves one canned Messages API response per request, over a new
+/// connection each, as server-sent events if the request asks for a
+/// stream, and sends back the headers and body it received. Error
+/// responses are sent as 529 overloaded, asking to retry at once.
+pub fn start_mock_server(responses: Vec<Value>) -> (String, mpsc::Receiver<(String, Value)>) {
+    serve(responses, "529 Overloaded", to_event_stream)
+}
+
+/// Like `start_mock_server`, ending the lines of its events in `\r\n`.
+pub fn start_crlf_mock_server(responses: Vec<Value>) -> (String, mpsc::Receiver<(String, Value)>) {
+    serve(responses, "529 Overloaded", |response| {
+        to_event_stream(response).replace('\n', "\r\n")
+    })
+}
+
+/// Like `start_mock_server`, for the OpenAI chat completions API. Error
+/// responses are sent as 503 service unavailable.
+pub fn start_openai_mock_server(
+    responses: Vec<Value>,
+) -> (String, mpsc::Receiver<(String, Value)>) {
+    serve(responses, "503 Service Unavailable", to_openai_event_stream)
+}
+
+fn serve(
+    responses: Vec<Value>,
+    error_status: &'static str,
+    to_event_stream: fn(&Value) -> String,
+) -> (String, mpsc::Receiver<(String, Value)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut headers = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    content_length = value.trim().parse().unwrap();
+                }
+                headers.push_str(&line);
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            let (status, content_type, response) = if response.get("error").is_some() {
+                (error_status, "application/json", response.to_string())
+            } else if body["stream"] == true {
+                ("200 OK", "text/event-stream", to_event_stream(&response))
+            } else {
+                ("200 OK", "application/json", response.to_string())
+            };
+            // Errors ask to be retried at once
+            let retry_after = if status == error_status {
+                "Retry-After: 0\r\n"
+            } else {
+                ""
+            };
+            sender.send((headers, body)).unwrap();
+
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\n{}Content-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                retry_after,
+                content_type,
+                response.len(),
+                response
+            )
+            .unwrap();
+        }
+    });
+    (url, receiver)
+}
+
+/// Streams each content block of `response` as a start event followed by
+/// one delta with all of its text or input, and its `usage` split between
+/// the start and the end of the message like the API does.
+fn to_event_stream(response: &Value) -> String {
+    let mut message = json!({"role": "assistant"});
+    if let Some(usage) = response.get("usage") {
+        message["usage"] = json!({"input_tokens": usage["input_tokens"], "output_tokens": 0});
+    }
+    let mut events = vec![json!({"type": "message_start", "message": message})];
+    for (index, block) in response["content"].as_array().unwrap().iter().enumerate() {
+        let (start, delta) = match block["type"].as_str().unwrap() {
+            "tool_use" => (
+                json!({"type": "tool_use", "id": block["id"], "name": block["name"], "input": {}}),
+                json!({"type": "input_json_delta", "partial_json": block["input"].to_string()}),
+            ),
+            _ => (
+                json!({"type": "text", "text": ""}),
+                json!({"type": "text_delta", "text": block["text"]}),
+            ),
+        };
+        events.push(json!({"type": "content_block_start", "index": index, "content_block": start}));
+        events.push(json!({"type": "content_block_delta", "index": index, "delta": delta}));
+        events.push(json!({"type": "content_block_stop", "index": index}));
+    }
+    if let Some(usage) = response.get("usage") {
+        events.push(json!({
+            "type": "message_delta",
+            "delta": {"stop_reason": response["stop_reason"]},
+            "usage": {"output_tokens": usage["output_tokens"]}
+        }));
+    }
+    events.push(json!({"type": "message_stop"}));
+    events
+        .iter()
+        .map(|event| {
+            format!(
+                "event: {}\ndata: {}\n\n",
+                event["type"].as_str().unwrap(),
+                event
+            )
+        })
+        .collect()
+}
+
+/// Streams a chat completion as a single chunk holding all of its message,
+/// with tool calls numbered, followed by the end of the stream.
+fn to_openai_event_stream(response: &Value) -> String {
+    let choices = response["choices"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|choice| {
+            let mut delta = choice["message"].clone();
+            if let Some(tool_calls) = delta["tool_calls"].as_array_mut() {
+                for (index, tool_call) in tool_calls.iter_mut().enumerate() {
+                    tool_call["index"] = json!(index);
+                }
+            }
+            json!({"index": choice["index"], "delta": delta, "finish_reason": choice["finish_reason"]})
+        })
+        .collect::<Vec<_>>();
+    let chunk = json!({
+        "id": response["id"],
+        "object": "chat.completion.chunk",
+        "created": response["created"],
+        "model": response["model"],
+        "choices": choices,
+        "usage": response["usage"]
+    });
+    format!("data: {}\n\ndata: [DONE]\n\n", chunk)
+}
+
+/// Writes a project with the fruit sales SQLite database, a `claude` model
+/// served from `api_url` and a `mock.agent.yml` agent that can run SQL.
+pub fn setup_project(name: &str, api_url: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("onyx-test-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let database = fs::canonicalize("examples/data/fruit_sales.sqlite").unwrap();
+    fs::write(
+        dir.join("config.yml"),
+        format!(
+            r#"
+databases:
+  - name: sqlite
+    type: sqlite
+    path: {}
+
+models:
+  - name: claude
+    vendor: anthropic
+    model_ref: claude-test
+    api_key: test-key
+    api_url: {}
+"#,
+            database.display(),
+            api_url
+        ),
+    )
+    .unwrap();
+    fs::write(
+        dir.join("mock.agent.yml"),
+        r#"
+name: mock
+model: claude
+system_instructions: You answer questions about fruit sales.
+tools:
+  - name: execute_sql
+    type: execute_sql
+    database: sqlite
+"#,
+    )
+    .unwrap();
+    dir
+}
+
+pub fn text_response(text: &str) -> Value {
+    json!({
+        "role": "assistant",
+        "content": [{"type": "text", "text": text}],
+        "stop_reason": "end_turn"
+    })
+}
+
+pub fn tool_use_response(id: &str, sql: &str) -> Value {
+    json!({
+        "role": "assistant",
+        "content": [{
+            "type": "tool_use",
+            "id": id,
+            "name": "execute_sql",
+            "input": {"sql": sql}
+        }],
+        "stop_reason": "tool_use"
+    })
+}
+
+pub fn openai_text_response(text: &str) -> Value {
+    json!({
+        "id": "chatcmpl-01",
+        "object": "chat.completion",
+        "created": 0,
+        "model": "gpt-test",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": text},
+            "finish_reason": "stop"
+        }]
+    })
+}
@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod db {
+    use assert_cmd::Command;
+
+    fn setup_command() -> Command {
+        let mut cmd: Command = Command::cargo_bin("onyx").unwrap();
+        cmd.current_dir("examples").arg("db");
+        cmd
+    }
+
+    #[test]
+    fn ping_ok() {
+        let mut cmd = setup_command();
+        let result = cmd.arg("ping").arg("sqlite").assert().success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(output.contains("Connected"));
+    }
+
+    #[test]
+    fn ping_failed_if_database_not_configured() {
+        let mut cmd = setup_command();
+        let result = cmd.arg("ping").arg("missing").assert().failure();
+        let output = String::from_utf8(result.get_output().stderr.clone()).unwrap();
+        assert!(output.contains("Database 'missing' not found in config"));
+    }
+
+    #[test]
+    fn tables_ok() {
+        let mut cmd = setup_command();
+        let result = cmd.arg("tables").arg("sqlite").assert().success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(output.contains("fruit_sales"));
+    }
+
+    #[test]
+    fn describe_ok() {
+        let mut cmd = setup_command();
+        let result = cmd
+            .arg("describe")
+            .arg("sqlite")
+            .arg("fruit_sales")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(output.contains("sales"));
+        assert!(output.contains("INTEGER"));
+    }
+
+    #[test]
+    fn sample_ok() {
+        let mut cmd = setup_command();
+        let result = cmd
+            .arg("sample")
+            .arg("sqlite")
+            .arg("fruit_sales")
+            .arg("--limit")
+            .arg("1")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(output.contains("name"));
+    }
+
+    #[test]
+    fn sample_quotes_qualified_names() {
+        let mut cmd = setup_command();
+        cmd.arg("sample")
+            .arg("local_views")
+            .arg("fruits_veggies")
+            .arg("--limit")
+            .arg("1")
+            .assert()
+            .success();
+    }
+
+    #[test]
+    fn sample_failed_if_table_not_exist() {
+        let mut cmd = setup_command();
+        let result = cmd
+            .arg("sample")
+            .arg("sqlite")
+            .arg("vegetables")
+            .assert()
+            .failure();
+        let output = String::from_utf8(result.get_output().stderr.clone()).unwrap();
+        assert!(output.contains("Table 'vegetables' not found"));
+    }
+}
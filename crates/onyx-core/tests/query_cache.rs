@@ -0,0 +1,49 @@
+mod common;
+
+#[cfg(test)]
+mod query_cache {
+    use crate::common::{setup_project, start_mock_server, text_response, tool_use_response};
+    use assert_cmd::Command;
+    use std::fs;
+
+    #[test]
+    fn run_agent_serves_repeated_query_from_cache_ok() {
+        let count_sales =
+            |id: &str| tool_use_response(id, "SELECT COUNT(*) AS sales\nFROM fruit_sales");
+        let (api_url, _requests) = start_mock_server(vec![
+            count_sales("toolu_01"),
+            count_sales("toolu_02"),
+            text_response("There are many fruit sales."),
+        ]);
+        let project = setup_project("query-cache", &api_url);
+        let config = fs::read_to_string(project.join("config.yml")).unwrap();
+        fs::write(
+            project.join("config.yml"),
+            format!("{}\nquery_cache:\n  dir: query_cache\n", config),
+        )
+        .unwrap();
+
+        let mut cmd = Command::cargo_bin("onyx").unwrap();
+        let result = cmd
+            .current_dir(&project)
+            .arg("run")
+            .arg("mock.agent.yml")
+            .arg("how many fruit sales are there")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert_eq!(
+            output
+                .matches("Query result served from the query cache.")
+                .count(),
+            1
+        );
+        assert!(output.contains("There are many fruit sales."));
+        assert_eq!(
+            fs::read_dir(project.join("query_cache")).unwrap().count(),
+            1
+        );
+
+        fs::remove_dir_all(project).unwrap();
+    }
+}
@@ -0,0 +1,51 @@
+mod common;
+
+#[cfg(test)]
+mod results {
+    use crate::common::setup_project;
+    use assert_cmd::Command;
+    use std::fs;
+    use std::time::Duration;
+
+    #[test]
+    fn gc_ok() {
+        let mut binding = Command::cargo_bin("onyx").unwrap();
+        let cmd = binding.current_dir("examples").arg("results").arg("gc");
+        let result = cmd.assert().success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(output.contains("Removed"));
+    }
+
+    #[test]
+    fn run_removes_expired_results_ok() {
+        let project = setup_project("results-gc", "http://127.0.0.1:9");
+        let config = fs::read_to_string(project.join("config.yml")).unwrap();
+        fs::write(
+            project.join("config.yml"),
+            format!("{}\nresults:\n  dir: results\n  ttl_seconds: 0\n", config),
+        )
+        .unwrap();
+        fs::write(
+            project.join("count.sql"),
+            "SELECT COUNT(*) AS sales FROM fruit_sales",
+        )
+        .unwrap();
+        let expired = project.join("results").join("expired.arrow");
+        fs::create_dir_all(project.join("results")).unwrap();
+        fs::write(&expired, []).unwrap();
+        // Modification times must differ for the result to be past the TTL
+        std::thread::sleep(Duration::from_millis(10));
+
+        let mut cmd = Command::cargo_bin("onyx").unwrap();
+        cmd.current_dir(&project)
+            .arg("run")
+            .arg("count.sql")
+            .arg("--database")
+            .arg("sqlite")
+            .assert()
+            .success();
+        assert!(!expired.exists());
+
+        fs::remove_dir_all(project).unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+mod common;
+
+#[cfg(test)]
+mod retry {
+    use crate::common::{
+        openai_text_response, setup_project, start_crlf_mock_server, start_mock_server,
+        start_openai_mock_server, text_response,
+    };
+    use assert_cmd::Command;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn run_agent_retries_overloaded_api_ok() {
+        let overloaded = json!({
+            "type": "error",
+            "error": {"type": "overloaded_error", "message": "Overloaded"}
+        });
+        let (api_url, requests) = start_mock_server(vec![
+            overloaded.clone(),
+            overloaded,
+            text_response("There are many fruit sales."),
+        ]);
+        let project = setup_project("retry", &api_url);
+
+        let mut cmd = Command::cargo_bin("onyx").unwrap();
+        let result = cmd
+            .current_dir(&project)
+            .arg("run")
+            .arg("mock.agent.yml")
+            .arg("how many fruit sales are there")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert_eq!(output.matches("Retrying after").count(), 2);
+        assert!(output.contains("overloaded_error"));
+        assert!(output.contains("There are many fruit sales."));
+        assert_eq!(requests.iter().count(), 3);
+
+        fs::remove_dir_all(project).unwrap();
+    }
+
+    #[test]
+    fn run_agent_reads_crlf_event_stream_ok() {
+        let overloaded = json!({
+            "type": "error",
+            "error": {"type": "overloaded_error", "message": "Overloaded"}
+        });
+        let (api_url, requests) = start_crlf_mock_server(vec![
+            overloaded,
+            text_response("There are many fruit sales."),
+        ]);
+        let project = setup_project("crlf-retry", &api_url);
+
+        let mut cmd = Command::cargo_bin("onyx").unwrap();
+        let result = cmd
+            .current_dir(&project)
+            .arg("run")
+            .arg("mock.agent.yml")
+            .arg("how many fruit sales are there")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert_eq!(output.matches("Retrying after").count(), 1);
+        assert!(output.contains("There are many fruit sales."));
+        assert_eq!(requests.iter().count(), 2);
+
+        fs::remove_dir_all(project).unwrap();
+    }
+
+    #[test]
+    fn run_openai_agent_retries_server_errors_ok() {
+        let server_error = json!({
+            "error": {
+                "message": "The server had an error while processing your request.",
+                "type": "server_error",
+                "param": null,
+                "code": null
+            }
+        });
+        let (api_url, requests) = start_openai_mock_server(vec![
+            server_error.clone(),
+            server_error,
+            openai_text_response("There are many fruit sales."),
+        ]);
+        let project = setup_project("openai-retry", &api_url);
+        let config = fs::read_to_string(project.join("config.yml")).unwrap();
+        fs::write(
+            project.join("config.yml"),
+            format!(
+                "{}  - name: gpt\n    vendor: openai\n    model_ref: gpt-test\n    api_key: test-key\n    api_url: {}\n",
+                config, api_url
+            ),
+        )
+        .unwrap();
+        fs::write(
+            project.join("gpt.agent.yml"),
+            r#"
+model: gpt
+system_instructions: You answer questions about fruit sales.
+tools:
+  - name: execute_sql
+    type: execute_sql
+    database: sqlite
+"#,
+        )
+        .unwrap();
+
+        let mut cmd = Command::cargo_bin("onyx").unwrap();
+        let result = cmd
+            .current_dir(&project)
+            .arg("run")
+            .arg("gpt.agent.yml")
+            .arg("how many fruit sales are there")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert_eq!(output.matches("Retrying after").count(), 2);
+        assert!(output.contains("503 Service Unavailable"));
+        assert!(output.contains("There are many fruit sales."));
+
+        let (headers, first) = requests.recv().unwrap();
+        assert!(headers.contains("authorization: Bearer test-key"));
+        assert_eq!(first["model"], "gpt-test");
+        assert_eq!(first["stream"], true);
+        assert_eq!(requests.iter().count(), 2);
+
+        fs::remove_dir_all(project).unwrap();
+    }
+}
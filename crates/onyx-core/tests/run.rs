@@ -47,6 +47,32 @@ pub mod run {
         assert!(output.contains("Database 'test' not found in config"));
     }
 
+    #[test]
+    fn run_sql_file_against_sqlite_ok() {
+        let mut cmd = setup_command();
+        let result = cmd
+            .arg("data/fruit_sales_sqlite.sql")
+            .arg("--database")
+            .arg("sqlite")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(output.contains("banana"));
+    }
+
+    #[test]
+    fn run_sql_file_against_duckdb_init_sql_view_ok() {
+        let mut cmd = setup_command();
+        let result = cmd
+            .arg("data/fruits_veggies_view.sql")
+            .arg("--database")
+            .arg("local_views")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(output.contains("Fruit"));
+    }
+
     #[test]
     fn run_sql_file_with_variables_ok() {
         let mut cmd = setup_command();
@@ -64,6 +90,104 @@ pub mod run {
         assert!(output.contains("testalias"));
     }
 
+    #[test]
+    fn run_sql_file_with_params_ok() {
+        let mut cmd = setup_command();
+        let result = cmd
+            .arg("data/fruit_sales_by_name.sql")
+            .arg("--database")
+            .arg("sqlite")
+            .arg("-p")
+            .arg("fruit=banana")
+            .arg("min_sales:integer=100")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(output.contains("banana"));
+    }
+
+    #[test]
+    fn run_sql_file_with_params_does_not_inject() {
+        let mut cmd = setup_command();
+        let result = cmd
+            .arg("data/fruit_sales_by_name.sql")
+            .arg("--database")
+            .arg("sqlite")
+            .arg("-p")
+            .arg("fruit=banana' OR '1'='1")
+            .arg("min_sales:integer=0")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(!output.contains("apple"));
+    }
+
+    #[test]
+    fn run_sql_file_with_invalid_param_failed() {
+        let mut cmd = setup_command();
+        let result = cmd
+            .arg("data/fruit_sales_by_name.sql")
+            .arg("--database")
+            .arg("sqlite")
+            .arg("-p")
+            .arg("fruit=banana")
+            .arg("min_sales:integer=lots")
+            .assert()
+            .failure();
+        let output = String::from_utf8(result.get_output().stderr.clone()).unwrap();
+        assert!(output.contains("Invalid value 'lots' for integer parameter 'min_sales'"));
+    }
+
+    #[test]
+    fn run_sql_file_with_non_finite_param_failed() {
+        let mut cmd = setup_command();
+        let result = cmd
+            .arg("data/fruit_sales_by_name.sql")
+            .arg("--database")
+            .arg("sqlite")
+            .arg("-p")
+            .arg("fruit=banana")
+            .arg("min_sales:float=NaN")
+            .assert()
+            .failure();
+        let output = String::from_utf8(result.get_output().stderr.clone()).unwrap();
+        assert!(output.contains("expected a finite number"));
+    }
+
+    #[test]
+    fn run_sql_file_with_multiple_statements_ok() {
+        let mut cmd = setup_command();
+        let result = cmd
+            .arg("data/top_fruit_sales.sql")
+            .arg("--database")
+            .arg("sqlite")
+            .arg("-p")
+            .arg("min_sales:integer=1000")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(output.contains("banana"));
+        assert!(output.contains("top; seller"));
+        assert!(!output.contains("cherry"));
+        assert_eq!(output.matches("Results:").count(), 1);
+    }
+
+    #[test]
+    fn run_sql_file_with_all_results_ok() {
+        let mut cmd = setup_command();
+        let result = cmd
+            .arg("data/top_fruit_sales.sql")
+            .arg("--database")
+            .arg("sqlite")
+            .arg("-p")
+            .arg("min_sales:integer=1000")
+            .arg("--all-results")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert_eq!(output.matches("Results:").count(), 2);
+    }
+
     #[test]
     fn run_example_workflow_ok() {
         let mut cmd = setup_command();
@@ -98,6 +222,18 @@ pub mod run {
         assert!(output.contains("Workflow executed successfully"));
     }
 
+    #[test]
+    fn run_workflow_with_sql_params_ok() {
+        let mut cmd = setup_command();
+        let result = cmd
+            .arg("workflows/sql_params.workflow.yml")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(output.contains("banana"));
+        assert!(!output.contains("orange"));
+    }
+
     #[test]
     fn run_agent_ok() {
         let mut cmd = setup_command();
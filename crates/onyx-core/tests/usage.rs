@@ -0,0 +1,111 @@
+mod common;
+
+#[cfg(test)]
+mod usage {
+    use crate::common::{setup_project, start_mock_server};
+    use assert_cmd::Command;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn run_workflow_reports_usage_ok() {
+        let (api_url, _requests) = start_mock_server(vec![
+            json!({
+                "role": "assistant",
+                "content": [{
+                    "type": "tool_use",
+                    "id": "toolu_01",
+                    "name": "execute_sql",
+                    "input": {"sql": "SELECT COUNT(*) AS sales FROM fruit_sales"}
+                }],
+                "stop_reason": "tool_use",
+                "usage": {"input_tokens": 1000, "output_tokens": 100}
+            }),
+            json!({
+                "role": "assistant",
+                "content": [{"type": "text", "text": "There are many fruit sales."}],
+                "stop_reason": "end_turn",
+                "usage": {"input_tokens": 2000, "output_tokens": 100}
+            }),
+        ]);
+        let project = setup_project("usage", &api_url);
+        let config = fs::read_to_string(project.join("config.yml"))
+            .unwrap()
+            .replace(
+                "api_key: test-key",
+                "api_key: test-key\n    pricing:\n      prompt: 3\n      completion: 15",
+            );
+        fs::write(project.join("config.yml"), config).unwrap();
+        fs::write(
+            project.join("usage.workflow.yml"),
+            r#"
+tasks:
+  - name: count_sales
+    type: agent
+    agent_ref: mock.agent.yml
+    prompt: how many fruit sales are there
+"#,
+        )
+        .unwrap();
+
+        let mut cmd = Command::cargo_bin("onyx").unwrap();
+        let result = cmd
+            .current_dir(&project)
+            .arg("run")
+            .arg("usage.workflow.yml")
+            .assert()
+            .success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(output.contains("Workflow executed successfully"));
+        assert!(output
+            .contains("count_sales: 2 requests, 3000 prompt + 200 completion tokens, $0.0120"));
+        assert!(output.contains("Total: 2 requests, 3000 prompt + 200 completion tokens"));
+
+        fs::remove_dir_all(project).unwrap();
+    }
+
+    #[test]
+    fn build_reports_embedding_tokens_ok() {
+        let (api_url, _requests) = start_mock_server(vec![json!({
+            "object": "list",
+            "data": [{"object": "embedding", "index": 0, "embedding": [0.6, 0.8]}],
+            "model": "embed-test",
+            "usage": {"prompt_tokens": 42, "total_tokens": 42}
+        })]);
+        let project = setup_project("build-usage", &api_url);
+        fs::create_dir_all(project.join("docs")).unwrap();
+        fs::write(
+            project.join("docs/sales.md"),
+            "Fruit sales are recorded per day.",
+        )
+        .unwrap();
+        fs::write(
+            project.join("docs.agent.yml"),
+            format!(
+                r#"
+name: docs
+model: claude
+system_instructions: You answer questions about the docs.
+tools:
+  - name: search_docs
+    type: retrieval
+    src:
+      - docs/*.md
+    embed_model: embed-test
+    api_url: {}
+    api_key: test-key
+    n_dims: 2
+"#,
+                api_url
+            ),
+        )
+        .unwrap();
+
+        let mut cmd = Command::cargo_bin("onyx").unwrap();
+        let result = cmd.current_dir(&project).arg("build").assert().success();
+        let output = String::from_utf8(result.get_output().stdout.clone()).unwrap();
+        assert!(output.contains("42 embedding tokens"));
+
+        fs::remove_dir_all(project).unwrap();
+    }
+}
@@ -20,14 +20,30 @@ class WorkflowResultTask:
 
 WorkflowOutput = str | dict[str, WorkflowOutput] | list[WorkflowOutput] | list[RecordBatch]
 
+@dataclass
+class Usage:
+    requests: int
+    prompt_tokens: int
+    completion_tokens: int
+    embedding_tokens: int
+    cost: float | None
+
+@dataclass
+class WorkflowUsage:
+    total: Usage
+    tasks: list[tuple[str, Usage]]
+
 @dataclass
 class WorkflowResult:
     output: WorkflowOutput
     tasks: WorkflowResultTask
+    usage: WorkflowUsage
 
 class RunOptions(TypedDict):
     question: str | None
     variables: list[tuple[str, str]] | None
+    params: list[tuple[str, str]] | None
+    all_results: bool | None
     database: str | None
 
 RunOutput = str | AgentResult | WorkflowResult
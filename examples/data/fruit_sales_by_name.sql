@@ -0,0 +1 @@
+SELECT name, sales FROM fruit_sales WHERE name = :fruit AND sales >= :min_sales
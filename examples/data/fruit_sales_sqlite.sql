@@ -0,0 +1 @@
+SELECT name, sales FROM fruit_sales ORDER BY sales DESC
@@ -0,0 +1 @@
+SELECT DISTINCT property_grouping FROM fruits_veggies ORDER BY property_grouping
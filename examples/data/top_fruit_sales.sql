@@ -0,0 +1,5 @@
+-- Stage the best sellers in a temporary table; this comment has a ; in it
+CREATE TEMP TABLE top_fruits AS
+SELECT name, sales FROM fruit_sales WHERE sales >= :min_sales;
+
+SELECT name, sales, 'top; seller' AS label FROM top_fruits ORDER BY sales DESC;